@@ -0,0 +1,13 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+
+fn decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    for scenario in textstream::bench::scenarios() {
+        group.throughput(Throughput::Bytes(scenario.input.len() as u64));
+        group.bench_function(scenario.name, |b| b.iter(|| scenario.run().unwrap()));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, decode);
+criterion_main!(benches);
@@ -0,0 +1,30 @@
+use std::env;
+use std::process::Command;
+
+fn main() {
+    println!("cargo:rerun-if-env-changed=TEXTSTREAM_ICU_VERSION");
+    if env::var_os("CARGO_FEATURE_ICU").is_none() {
+        return;
+    }
+    // The symbols of ICU are suffixed with the major version, such as `ucnv_open_72`.
+    let version = match env::var("TEXTSTREAM_ICU_VERSION") {
+        Ok(version) => version,
+        Err(_) => {
+            let output = Command::new("pkg-config").args(["--modversion", "icu-uc"]).output()
+                .expect("the icu feature needs pkg-config, or TEXTSTREAM_ICU_VERSION set to the version of ICU");
+            assert!(output.status.success(), "ICU is not found by pkg-config: {}",
+                String::from_utf8_lossy(&output.stderr).trim());
+            String::from_utf8(output.stdout).unwrap()
+        },
+    };
+    let major = version.trim().split('.').next().unwrap_or_default();
+    println!("cargo:rustc-env=TEXTSTREAM_ICU_SUFFIX=_{}", major);
+    if let Ok(output) = Command::new("pkg-config").args(["--libs-only-L", "icu-uc"]).output() {
+        for dir in String::from_utf8_lossy(&output.stdout).split_whitespace() {
+            if let Some(dir) = dir.strip_prefix("-L") {
+                println!("cargo:rustc-link-search=native={}", dir);
+            }
+        }
+    }
+    println!("cargo:rustc-link-lib=icuuc");
+}
@@ -0,0 +1,150 @@
+//! Benchmark scenarios.
+//!
+//! The scenarios are used by `cargo bench --features bench`, and are public so that they can be
+//! run against your own hardware and your own reader settings.
+
+use encoding::{DecoderTrap, EncoderTrap, Encoding, EncodingRef};
+use encoding::all::{UTF_16LE, UTF_8, WINDOWS_31J};
+
+use crate::{Result, TextReader};
+
+/// A benchmark scenario: an input and the settings to decode it with.
+pub struct Scenario {
+    /// Name of the scenario.
+    pub name: &'static str,
+    /// Encoding of `input`.
+    pub encoding: EncodingRef,
+    /// Trap used for decoding.
+    pub trap: DecoderTrap,
+    /// Encoded input.
+    pub input: Vec<u8>,
+}
+
+impl Scenario {
+    /// Creates a `TextReader` for the input with the settings of the scenario.
+    pub fn reader(&self) -> TextReader<&[u8]> {
+        TextReader::from_bufread(&self.input[..], self.encoding, self.trap)
+    }
+
+    /// Reads all lines of the input, returning the number of lines.
+    pub fn run(&self) -> Result<usize> {
+        self.run_with(|_| {})
+    }
+
+    /// Reads all lines of the input like `run`, after `configure` has changed the settings of
+    /// the reader.
+    ///
+    /// # Examples
+    /// ```
+    /// use textstream::NewlinePolicy;
+    /// use textstream::bench::scenarios;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let scenario = scenarios().into_iter().find(|s| s.name == "utf16-export").unwrap();
+    /// let n = scenario.run_with(|reader| reader.set_newline_policy(NewlinePolicy::Lf))?;
+    /// assert_eq!(n, scenario.run()?);
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn run_with<F>(&self, configure: F) -> Result<usize>
+        where F: FnOnce(&mut TextReader<&[u8]>)
+    {
+        let mut reader = self.reader();
+        configure(&mut reader);
+        let mut n = 0;
+        for line in reader.lines() {
+            line?;
+            n += 1;
+        }
+        Ok(n)
+    }
+}
+
+/// Approximate size of the input of each scenario.
+const INPUT_SIZE: usize = 256 * 1024;
+
+/// For internal use. Repeats `line` with a line number until `INPUT_SIZE` is reached.
+fn repeat_lines(line: &str) -> String {
+    let mut s = String::with_capacity(INPUT_SIZE + line.len() + 16);
+    let mut n = 0;
+    while s.len() < INPUT_SIZE {
+        s.push_str(&line.replace("{}", &n.to_string()));
+        n += 1;
+    }
+    s
+}
+
+/// Returns the standard scenarios.
+///
+/// * `sjis-log`: Shift_JIS application log.
+/// * `sjis-log-lossy`: Shift_JIS application log with invalid bytes, decoded with
+///   `DecoderTrap::Replace`.
+/// * `utf8-log`: the same log in UTF-8.
+/// * `utf16-export`: UTF-16LE CSV export with CRLF.
+/// * `tiny-lines`: very short lines.
+/// * `huge-line`: a single line without newline.
+pub fn scenarios() -> Vec<Scenario> {
+    let log = repeat_lines("2017-04-01 12:34:56 INFO 処理を開始しました。 id={}\n");
+    let sjis_log = WINDOWS_31J.encode(&log, EncoderTrap::Strict).unwrap();
+    let mut sjis_log_broken = Vec::with_capacity(sjis_log.len() + sjis_log.len() / 32);
+    for line in sjis_log.split(|&b| b == b'\n') {
+        sjis_log_broken.extend_from_slice(line);
+        sjis_log_broken.extend_from_slice(b"\xff\n");
+    }
+    let csv = repeat_lines("{},山田太郎,東京都千代田区,03-1234-5678\r\n");
+    vec![
+        Scenario {
+            name: "sjis-log",
+            encoding: WINDOWS_31J,
+            trap: DecoderTrap::Strict,
+            input: sjis_log,
+        },
+        Scenario {
+            name: "sjis-log-lossy",
+            encoding: WINDOWS_31J,
+            trap: DecoderTrap::Replace,
+            input: sjis_log_broken,
+        },
+        Scenario {
+            name: "utf8-log",
+            encoding: UTF_8,
+            trap: DecoderTrap::Strict,
+            input: log.into_bytes(),
+        },
+        Scenario {
+            name: "utf16-export",
+            encoding: UTF_16LE,
+            trap: DecoderTrap::Strict,
+            input: UTF_16LE.encode(&csv, EncoderTrap::Strict).unwrap(),
+        },
+        Scenario {
+            name: "tiny-lines",
+            encoding: WINDOWS_31J,
+            trap: DecoderTrap::Strict,
+            input: b"a\n".iter().cycle().take(INPUT_SIZE).cloned().collect(),
+        },
+        Scenario {
+            name: "huge-line",
+            encoding: WINDOWS_31J,
+            trap: DecoderTrap::Strict,
+            input: b"\x82\xa0".iter().cycle().take(INPUT_SIZE).cloned().collect(),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scenarios_run() {
+        for scenario in scenarios() {
+            let n = scenario.run().unwrap();
+            match scenario.name {
+                "tiny-lines" => assert_eq!(n, INPUT_SIZE / 2),
+                "huge-line" => assert_eq!(n, 1),
+                _ => assert!(n > 1000),
+            }
+        }
+    }
+}
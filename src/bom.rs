@@ -0,0 +1,172 @@
+//! Byte order marks at the start of the stream.
+
+use std::io::{self, BufRead, ErrorKind};
+
+use encoding::all::{UTF_16BE, UTF_16LE};
+use encoding::EncodingRef;
+
+use crate::{decoder, Error, Result, TextReader};
+
+/// Number of bytes looked at to resolve the byte order of UTF-16 without a byte order mark.
+const UTF16_WINDOW: usize = 1024;
+
+/// What `TextReader` does with a byte order mark, U+FEFF, at the start of the stream.
+/// Please see `TextReader::set_bom_policy`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BomPolicy {
+    /// The byte order mark is returned as text.
+    #[default]
+    Keep,
+    /// The byte order mark is removed from the text.
+    Strip,
+    /// The byte order mark is removed from the text, and reading fails with
+    /// `Error::MissingBom` if there is none.
+    Require,
+}
+
+impl<R: BufRead> TextReader<R> {
+    /// Sets what to do with a byte order mark at the start of the stream. The default is
+    /// `BomPolicy::Keep`, which returns it as U+FEFF at the start of the first line.
+    /// This must be set before reading.
+    ///
+    /// With `BomPolicy::Require`, the first read fails with `Error::MissingBom` if the stream,
+    /// even an empty one, doesn't start with a byte order mark. The text can be read after the
+    /// error. Encodings other than UTF-8 and UTF-16 have no byte order mark.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::UTF_8;
+    /// use encoding::DecoderTrap;
+    /// use textstream::{BomPolicy, TextReader};
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::new(&b"\xef\xbb\xbfid,name\n"[..], UTF_8, DecoderTrap::Strict);
+    /// reader.set_bom_policy(BomPolicy::Strip);
+    /// let mut header = String::new();
+    /// reader.read_line(&mut header)?;
+    /// assert_eq!(header, "id,name\n");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn set_bom_policy(&mut self, policy: BomPolicy) {
+        self.bom = policy;
+        self.bom_pending = policy != BomPolicy::Keep;
+    }
+
+    /// Gets what to do with a byte order mark at the start of the stream.
+    pub fn bom_policy(&self) -> BomPolicy { self.bom }
+
+    /// For internal use. Resolves the byte order of the generic "utf-16" label from the byte
+    /// order mark at the start of the stream. Without one, the stream is taken for big endian if
+    /// more NUL bytes, the upper halves of ASCII characters, are at even offsets than at odd
+    /// ones, and for little endian, the one of Windows, otherwise.
+    /// The bytes read are left in `binbuf`.
+    pub(crate) fn resolve_utf16(&mut self) -> io::Result<()> {
+        while self.binbuf.len() < 2 {
+            let buf = match self.reader.fill_buf() {
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                result => result?,
+            };
+            if buf.is_empty() {
+                break;
+            }
+            let n = buf.len().min(UTF16_WINDOW - self.binbuf.len());
+            self.binbuf.extend_from_slice(&buf[..n]);
+            self.reader.consume(n);
+        }
+        self.utf16_unresolved = false;
+        let bytes = &self.binbuf;
+        let big_endian = if bytes.starts_with(b"\xfe\xff") || bytes.starts_with(b"\xff\xfe") {
+            bytes[0] == 0xfe
+        }
+        else {
+            let even = bytes.iter().step_by(2).filter(|&&b| b == 0).count();
+            let odd = bytes.iter().skip(1).step_by(2).filter(|&&b| b == 0).count();
+            even > odd
+        };
+        let encoding: EncodingRef = if big_endian { UTF_16BE } else { UTF_16LE };
+        self.encoding = encoding;
+        self.decoder = decoder::new_decoder(encoding);
+        Ok(())
+    }
+
+    /// For internal use. Applies the `BomPolicy` to `s[strlen..]`, the first text decoded from
+    /// the start of the stream, once it is not empty or the end of the stream is reached.
+    pub(crate) fn check_bom(&mut self, s: &mut String, strlen: usize, eof: bool) -> Result<()> {
+        if !self.bom_pending || (s.len() == strlen && !eof) {
+            return Ok(());
+        }
+        self.bom_pending = false;
+        if s[strlen..].starts_with('\u{FEFF}') {
+            s.drain(strlen..strlen + '\u{FEFF}'.len_utf8());
+            Ok(())
+        }
+        else if self.bom == BomPolicy::Require {
+            Err(Error::MissingBom)
+        }
+        else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::DecoderTrap;
+    use encoding::all::UTF_8;
+    use crate::TextReaderBuilder;
+
+    fn read(bytes: &[u8], policy: BomPolicy, chunk_size: usize) -> Result<String> {
+        let mut reader = TextReaderBuilder::new(UTF_8).chunk_size(chunk_size).bom_policy(policy).build(bytes);
+        let mut s = String::new();
+        reader.read_to_end(&mut s)?;
+        Ok(s)
+    }
+    #[test]
+    fn bom_policy_test() {
+        for chunk_size in [1, 2048] {
+            assert_eq!(read(b"\xef\xbb\xbfa\xef\xbb\xbf", BomPolicy::Keep, chunk_size).unwrap(), "\u{feff}a\u{feff}");
+            assert_eq!(read(b"\xef\xbb\xbfa\xef\xbb\xbf", BomPolicy::Strip, chunk_size).unwrap(), "a\u{feff}");
+            assert_eq!(read(b"a", BomPolicy::Strip, chunk_size).unwrap(), "a");
+            assert_eq!(read(b"\xef\xbb\xbfa", BomPolicy::Require, chunk_size).unwrap(), "a");
+            assert!(matches!(read(b"a", BomPolicy::Require, chunk_size), Err(Error::MissingBom)));
+            assert!(matches!(read(b"", BomPolicy::Require, chunk_size), Err(Error::MissingBom)));
+        }
+        let mut reader = TextReader::new(&b"a\nb"[..], UTF_8, DecoderTrap::Strict);
+        reader.set_bom_policy(BomPolicy::Require);
+        assert_eq!(reader.bom_policy(), BomPolicy::Require);
+        let mut s = String::new();
+        assert!(matches!(reader.read_line(&mut s), Err(Error::MissingBom)));
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "a\nb");
+        let mut reader = TextReader::new(&b"\xff\xfea\0"[..], UTF_16LE, DecoderTrap::Strict);
+        reader.set_bom_policy(BomPolicy::Strip);
+        assert_eq!(reader.read_char().unwrap(), Some('a'));
+        let pos = reader.position();
+        assert_eq!((pos.byte, pos.column), (4, 2));
+    }
+    #[test]
+    fn utf16_byte_order() {
+        let read = |bytes: &'static [u8]| {
+            let mut reader = TextReader::with_label(bytes, "UTF-16", DecoderTrap::Strict).unwrap();
+            let mut s = String::new();
+            reader.read_to_end(&mut s).unwrap();
+            (reader.encoding_name(), s)
+        };
+        assert_eq!(read(b"\xfe\xff\0a\x30\x42"), ("utf-16be", "\u{feff}aあ".to_string()));
+        assert_eq!(read(b"\xff\xfea\0\x42\x30"), ("utf-16le", "\u{feff}aあ".to_string()));
+        // Without a byte order mark, as written by Windows tools such as Notepad or PowerShell.
+        assert_eq!(read(b"a\0b\0\r\0\n\0\x42\x30"), ("utf-16le", "ab\r\nあ".to_string()));
+        assert_eq!(read(b"\0a\0b\x30\x42"), ("utf-16be", "abあ".to_string()));
+        assert_eq!(read(b"\x42\x30"), ("utf-16le", "あ".to_string()));
+        assert_eq!(read(b""), ("utf-16le", String::new()));
+        let mut reader = TextReader::with_label(&b"\xfe\xff\0a"[..], "utf-16", DecoderTrap::Strict).unwrap();
+        reader.set_bom_policy(BomPolicy::Strip);
+        assert_eq!(reader.read_char().unwrap(), Some('a'));
+        // Read a byte at a time.
+        let bytes = std::io::BufReader::with_capacity(1, &b"\0a"[..]);
+        let mut reader = TextReader::with_label(bytes, "utf-16", DecoderTrap::Strict).unwrap();
+        assert_eq!(reader.read_char().unwrap(), Some('a'));
+    }
+}
@@ -0,0 +1,161 @@
+//! Builder for `TextReader`.
+
+use std::cmp;
+use std::io::{BufRead, BufReader, Read};
+
+use encoding::{DecoderTrap, EncodingRef};
+
+use crate::{BomPolicy, NewlinePolicy, TextDecoder, TextReader, CHUNK_SIZE, MIN_CHUNK_SIZE};
+
+/// The `TextReaderBuilder` struct configures and creates a `TextReader`.
+///
+/// # Examples
+/// ```
+/// use encoding::all::WINDOWS_31J;
+/// use encoding::DecoderTrap;
+/// use textstream::{NewlinePolicy, TextReaderBuilder};
+/// # fn main() {
+/// let reader = TextReaderBuilder::new(WINDOWS_31J)
+///     .trap(DecoderTrap::Replace)
+///     .chunk_size(64 * 1024)
+///     .buffer_capacity(64 * 1024)
+///     .newline_policy(NewlinePolicy::Universal)
+///     .max_line_length(4096)
+///     .build(&b"foo\rbar"[..]);
+/// let lines: Vec<_> = reader.lines().map(|l| l.unwrap()).collect();
+/// assert_eq!(lines, ["foo", "bar"]);
+/// # }
+/// ```
+#[derive(Clone, Copy)]
+pub struct TextReaderBuilder {
+    encoding: EncodingRef,
+    trap: DecoderTrap,
+    chunk_size: usize,
+    buffer_capacity: Option<usize>,
+    newline: NewlinePolicy,
+    max_line_length: Option<usize>,
+    binary_detection: bool,
+    reset_at_newline: bool,
+    bom: BomPolicy,
+}
+
+impl TextReaderBuilder {
+    /// Creates a new `TextReaderBuilder` with `encoding`.
+    /// The other options are the same as `TextReader::new`, with `DecoderTrap::Strict`.
+    pub fn new(encoding: EncodingRef) -> TextReaderBuilder {
+        TextReaderBuilder {
+            encoding,
+            trap: DecoderTrap::Strict,
+            chunk_size: CHUNK_SIZE,
+            buffer_capacity: None,
+            newline: NewlinePolicy::default(),
+            max_line_length: None,
+            binary_detection: false,
+            reset_at_newline: false,
+            bom: BomPolicy::Keep,
+        }
+    }
+
+    /// Returns the encoding.
+    pub fn encoding(&self) -> EncodingRef { self.encoding }
+
+    /// Sets the trap for decoding errors.
+    pub fn trap(mut self, trap: DecoderTrap) -> TextReaderBuilder {
+        self.trap = trap;
+        self
+    }
+
+    /// Sets the number of bytes decoded at once. The default is 2048.
+    /// Values smaller than 8 are treated as 8, so that a partial character always fits.
+    /// At most the bytes in the buffer of the underlying reader are decoded at once.
+    pub fn chunk_size(mut self, chunk_size: usize) -> TextReaderBuilder {
+        self.chunk_size = cmp::max(chunk_size, MIN_CHUNK_SIZE);
+        self
+    }
+
+    /// Sets the capacity of the `BufReader` created by `build`. The default is the default of
+    /// `BufReader`, 8 KiB on most platforms.
+    pub fn buffer_capacity(mut self, capacity: usize) -> TextReaderBuilder {
+        self.buffer_capacity = Some(capacity);
+        self
+    }
+
+    /// Sets the line terminators. See `TextReader::set_newline_policy`.
+    pub fn newline_policy(mut self, policy: NewlinePolicy) -> TextReaderBuilder {
+        self.newline = policy;
+        self
+    }
+
+    /// Sets the maximum length of a line. See `TextReader::set_max_line_length`.
+    pub fn max_line_length(mut self, max: usize) -> TextReaderBuilder {
+        self.max_line_length = Some(max);
+        self
+    }
+
+    /// Sets whether to fail on binary data. See `TextReader::set_binary_detection`.
+    pub fn binary_detection(mut self, enabled: bool) -> TextReaderBuilder {
+        self.binary_detection = enabled;
+        self
+    }
+
+    /// Sets whether to reset the decoder at every `\n`. See `TextReader::set_reset_at_newline`.
+    pub fn reset_at_newline(mut self, enabled: bool) -> TextReaderBuilder {
+        self.reset_at_newline = enabled;
+        self
+    }
+
+    /// Sets what to do with a byte order mark. See `TextReader::set_bom_policy`.
+    pub fn bom_policy(mut self, policy: BomPolicy) -> TextReaderBuilder {
+        self.bom = policy;
+        self
+    }
+
+    /// Creates a `TextReader` reading from `reader` through a `BufReader`.
+    pub fn build<R: Read>(self, reader: R) -> TextReader<BufReader<R>> {
+        match self.buffer_capacity {
+            Some(capacity) => self.build_bufread(BufReader::with_capacity(capacity, reader)),
+            None => self.build_bufread(BufReader::new(reader)),
+        }
+    }
+
+    /// Creates a `TextReader` decoding with `decoder`, reading from `reader` through a
+    /// `BufReader`. See `TextReader::with_decoder`.
+    pub fn build_with_decoder<R: Read, D: TextDecoder + 'static>(self, reader: R, decoder: D) -> TextReader<BufReader<R>> {
+        let mut textreader = self.build(reader);
+        textreader.decoder = Box::new(decoder);
+        textreader
+    }
+
+    /// Creates a `TextReader` reading from `reader`, which is already buffered.
+    pub fn build_bufread<R: BufRead>(self, reader: R) -> TextReader<R> {
+        let mut textreader = TextReader::from_bufread(reader, self.encoding, self.trap);
+        textreader.chunk_size = self.chunk_size;
+        textreader.newline = self.newline;
+        textreader.max_line_length = self.max_line_length;
+        textreader.check_binary = self.binary_detection;
+        textreader.reset_at_newline = self.reset_at_newline;
+        textreader.set_bom_policy(self.bom);
+        textreader
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::all::WINDOWS_31J;
+
+    #[test]
+    fn build_small_chunk() {
+        let sjis_aiueo = [0x82, 0xa0, 0x82, 0xa2, 0x82, 0xa4, 0x82, 0xa6, 0x82, 0xa8];
+        let mut reader = TextReaderBuilder::new(WINDOWS_31J).chunk_size(1).build(&sjis_aiueo[..]);
+        let mut s = String::new();
+        assert!(reader.read_to_end(&mut s).is_ok());
+        assert_eq!(s, "あいうえお");
+    }
+    #[test]
+    fn build_buffer_capacity() {
+        let reader = TextReaderBuilder::new(WINDOWS_31J).buffer_capacity(3).build(&b"abcd"[..]);
+        assert_eq!(reader.get_ref().capacity(), 3);
+        assert_eq!(reader.chars().count(), 4);
+    }
+}
@@ -0,0 +1,376 @@
+//! Decompression under decoding.
+
+#[cfg(feature = "gzip")]
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Chain, Cursor, ErrorKind, Read};
+#[cfg(feature = "gzip")]
+use std::path::Path;
+
+use encoding::{DecoderTrap, EncodingRef};
+#[cfg(feature = "gzip")]
+use flate2::bufread::MultiGzDecoder;
+#[cfg(feature = "xz")]
+use xz2::bufread::XzDecoder;
+#[cfg(feature = "zstd")]
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+use crate::TextReader;
+
+/// Number of bytes looked at by `Compression::detect`, the length of the longest magic bytes.
+const MAGIC_LEN: usize = 6;
+
+/// Compression formats decompressed by `TextReader::from_compressed`.
+/// Each format is decompressed only with its cargo feature enabled.
+#[non_exhaustive]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    /// gzip, including concatenated members as written by `cat a.gz b.gz`.
+    /// Needs the `gzip` feature.
+    Gzip,
+    /// zstd, including concatenated frames. Needs the `zstd` feature.
+    Zstd,
+    /// xz, including concatenated streams. Needs the `xz` feature.
+    Xz,
+}
+
+impl Compression {
+    /// Detects the compression format by the magic bytes at the start of `bytes`.
+    /// Returns `None` for uncompressed data.
+    ///
+    /// # Examples
+    /// ```
+    /// use textstream::Compression;
+    /// # fn main() {
+    /// assert_eq!(Compression::detect(b"\x1f\x8b\x08\x00"), Some(Compression::Gzip));
+    /// assert_eq!(Compression::detect(b"\x28\xb5\x2f\xfd"), Some(Compression::Zstd));
+    /// assert_eq!(Compression::detect(b"plain text"), None);
+    /// # }
+    /// ```
+    pub fn detect(bytes: &[u8]) -> Option<Compression> {
+        if bytes.starts_with(b"\x1f\x8b") {
+            Some(Compression::Gzip)
+        }
+        else if bytes.starts_with(b"\x28\xb5\x2f\xfd") {
+            Some(Compression::Zstd)
+        }
+        else if bytes.starts_with(b"\xfd7zXZ\x00") {
+            Some(Compression::Xz)
+        }
+        else {
+            None
+        }
+    }
+
+    /// For internal use. Returns the error for a format whose cargo feature is not enabled.
+    #[allow(dead_code)]
+    fn unsupported(self) -> io::Error {
+        let (name, feature) = match self {
+            Compression::Gzip => ("gzip", "gzip"),
+            Compression::Zstd => ("zstd", "zstd"),
+            Compression::Xz => ("xz", "xz"),
+        };
+        io::Error::new(ErrorKind::Unsupported,
+            format!("{} compressed data needs the `{}` feature of textstream", name, feature))
+    }
+}
+
+/// A reader decompressing the bytes of another one.
+/// This struct is generally created through `TextReader::from_compressed`,
+/// `TextReader::from_auto_compressed` or `TextReader::open_gz`.
+pub struct Decompressor<R: BufRead> {
+    inner: Inner<Source<R>>,
+}
+
+/// The bytes read ahead by `Decompressor::detect`, followed by the rest of the reader.
+type Source<R> = Chain<Cursor<Vec<u8>>, R>;
+
+enum Inner<R: BufRead> {
+    Plain(R),
+    #[cfg(feature = "gzip")]
+    Gzip(MultiGzDecoder<R>),
+    #[cfg(feature = "zstd")]
+    Zstd(ZstdDecoder<'static, R>),
+    #[cfg(feature = "xz")]
+    Xz(XzDecoder<R>),
+}
+
+impl<R: BufRead> Decompressor<R> {
+    /// Creates a new `Decompressor` of `reader` compressed in `compression`.
+    /// Fails with `ErrorKind::Unsupported` if the cargo feature of the format is not enabled.
+    pub fn new(reader: R, compression: Compression) -> io::Result<Decompressor<R>> {
+        Decompressor::with_source(Cursor::new(Vec::new()).chain(reader), compression)
+    }
+
+    /// For internal use. Creates a new `Decompressor` of `reader` compressed in `compression`.
+    fn with_source(reader: Source<R>, compression: Compression) -> io::Result<Decompressor<R>> {
+        let inner = match compression {
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Inner::Gzip(MultiGzDecoder::new(reader)),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Inner::Zstd(ZstdDecoder::with_buffer(reader)?),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Inner::Xz(XzDecoder::new_multi_decoder(reader)),
+            #[allow(unreachable_patterns)]
+            compression => return Err(compression.unsupported()),
+        };
+        Ok(Decompressor { inner })
+    }
+
+    /// Creates a new `Decompressor` of `reader` compressed in the format detected by
+    /// `Compression::detect`, or passing the bytes through if they are not compressed.
+    /// The first bytes are read ahead until the longest magic bytes are, or the end of the
+    /// stream is reached, even if `reader` returns fewer at a time, as a pipe may.
+    pub fn detect(mut reader: R) -> io::Result<Decompressor<R>> {
+        let mut head = Vec::with_capacity(MAGIC_LEN);
+        while head.len() < MAGIC_LEN {
+            let buf = match reader.fill_buf() {
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                result => result?,
+            };
+            if buf.is_empty() {
+                break;
+            }
+            let n = buf.len().min(MAGIC_LEN - head.len());
+            head.extend_from_slice(&buf[..n]);
+            reader.consume(n);
+        }
+        let compression = Compression::detect(&head);
+        let source = Cursor::new(head).chain(reader);
+        match compression {
+            Some(compression) => Decompressor::with_source(source, compression),
+            None => Ok(Decompressor { inner: Inner::Plain(source) }),
+        }
+    }
+
+    /// Gets a reference to the compressed reader.
+    pub fn get_ref(&self) -> &R {
+        self.source().get_ref().1
+    }
+
+    /// Unwraps this `Decompressor`, returning the compressed reader.
+    /// The bytes read ahead by `detect` and not decompressed yet are lost.
+    pub fn into_inner(self) -> R {
+        let source = match self.inner {
+            Inner::Plain(reader) => reader,
+            #[cfg(feature = "gzip")]
+            Inner::Gzip(decoder) => decoder.into_inner(),
+            #[cfg(feature = "zstd")]
+            Inner::Zstd(decoder) => decoder.finish(),
+            #[cfg(feature = "xz")]
+            Inner::Xz(decoder) => decoder.into_inner(),
+        };
+        source.into_inner().1
+    }
+
+    /// For internal use. Gets a reference to the source of the decompressor.
+    fn source(&self) -> &Source<R> {
+        match self.inner {
+            Inner::Plain(ref reader) => reader,
+            #[cfg(feature = "gzip")]
+            Inner::Gzip(ref decoder) => decoder.get_ref(),
+            #[cfg(feature = "zstd")]
+            Inner::Zstd(ref decoder) => decoder.get_ref(),
+            #[cfg(feature = "xz")]
+            Inner::Xz(ref decoder) => decoder.get_ref(),
+        }
+    }
+}
+
+impl<R: BufRead> Read for Decompressor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match self.inner {
+            Inner::Plain(ref mut reader) => reader.read(buf),
+            #[cfg(feature = "gzip")]
+            Inner::Gzip(ref mut decoder) => decoder.read(buf),
+            #[cfg(feature = "zstd")]
+            Inner::Zstd(ref mut decoder) => decoder.read(buf),
+            #[cfg(feature = "xz")]
+            Inner::Xz(ref mut decoder) => decoder.read(buf),
+        }
+    }
+}
+
+impl<R: Read> TextReader<BufReader<Decompressor<BufReader<R>>>> {
+    /// Creates a new `TextReader` decoding the text decompressed from `reader`.
+    /// The offsets of the position are counted in decompressed bytes.
+    /// Fails with `ErrorKind::Unsupported` if the cargo feature of the format is not enabled.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::fs::File;
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::{Compression, TextReader};
+    /// # fn foo() -> textstream::Result<()> {
+    /// let file = File::open("app.log.zst")?;
+    /// let mut reader = TextReader::from_compressed(file, Compression::Zstd, WINDOWS_31J, DecoderTrap::Strict)?;
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s)?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn from_compressed(reader: R, compression: Compression, encoding: EncodingRef, trap: DecoderTrap)
+        -> io::Result<TextReader<BufReader<Decompressor<BufReader<R>>>>>
+    {
+        Ok(TextReader::new(Decompressor::new(BufReader::new(reader), compression)?, encoding, trap))
+    }
+
+    /// Creates a new `TextReader` decoding the text from `reader`, decompressed if it starts with
+    /// the magic bytes of a compression format. Please see `Compression::detect`.
+    /// Fails with `ErrorKind::Unsupported` if the cargo feature of the format is not enabled.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::fs::File;
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// // Either app.log or a compressed one renamed.
+    /// let file = File::open("app.log")?;
+    /// let reader = TextReader::from_auto_compressed(file, WINDOWS_31J, DecoderTrap::Replace)?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() { let _ = foo(); }
+    /// ```
+    pub fn from_auto_compressed(reader: R, encoding: EncodingRef, trap: DecoderTrap)
+        -> io::Result<TextReader<BufReader<Decompressor<BufReader<R>>>>>
+    {
+        Ok(TextReader::new(Decompressor::detect(BufReader::new(reader))?, encoding, trap))
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl TextReader<BufReader<Decompressor<BufReader<File>>>> {
+    /// Opens a gzip compressed file in read-only mode, and creates a new `TextReader` decoding
+    /// the text decompressed from it.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use std::{env, fs};
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use flate2::write::GzEncoder;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let path = env::temp_dir().join("textstream-doc-open-gz.log.gz");
+    /// let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    /// encoder.write_all(b"\x82\xa0\n")?;
+    /// fs::write(&path, encoder.finish()?)?;
+    /// let reader = TextReader::open_gz(&path, WINDOWS_31J, DecoderTrap::Replace)?;
+    /// for line in reader.lines() {
+    ///     assert_eq!(line?, "あ");
+    /// }
+    /// # fs::remove_file(&path)?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn open_gz<P: AsRef<Path>>(path: P, encoding: EncodingRef, trap: DecoderTrap)
+        -> io::Result<TextReader<BufReader<Decompressor<BufReader<File>>>>>
+    {
+        TextReader::from_compressed(File::open(path)?, Compression::Gzip, encoding, trap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::all::WINDOWS_31J;
+
+    fn read_all<R: BufRead>(mut reader: TextReader<R>) -> String {
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        s
+    }
+    #[cfg(feature = "gzip")]
+    fn gzip(bytes: &[u8]) -> Vec<u8> {
+        use std::io::Write;
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(bytes).unwrap();
+        encoder.finish().unwrap()
+    }
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn open_gz_test() {
+        use std::{env, fs, process};
+        let path = env::temp_dir().join(format!("textstream-gz-{}.txt.gz", process::id()));
+        let mut gz = gzip(b"\x82\xa0\n");
+        gz.extend_from_slice(&gzip(b"\x82\xa2\n"));
+        fs::write(&path, gz).unwrap();
+        let reader = TextReader::open_gz(&path, WINDOWS_31J, DecoderTrap::Strict).unwrap();
+        let lines: Vec<String> = reader.lines().map(|l| l.unwrap()).collect();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(lines, ["あ", "い"]);
+    }
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn from_auto_compressed_test() {
+        for bytes in [gzip(b"\x82\xa0\n"), b"\x82\xa0\n".to_vec()] {
+            let reader = TextReader::from_auto_compressed(&bytes[..], WINDOWS_31J, DecoderTrap::Strict).unwrap();
+            assert_eq!(read_all(reader), "あ\n");
+        }
+        assert!(TextReader::from_auto_compressed(&b""[..], WINDOWS_31J, DecoderTrap::Strict).is_ok());
+    }
+    #[cfg(feature = "gzip")]
+    #[test]
+    fn from_compressed_error() {
+        let mut reader = TextReader::from_compressed(&b"plain"[..], Compression::Gzip, WINDOWS_31J, DecoderTrap::Strict).unwrap();
+        assert!(matches!(reader.read_to_end(&mut String::new()), Err(crate::Error::IOError(_))));
+    }
+    #[cfg(feature = "zstd")]
+    #[test]
+    fn zstd_test() {
+        let mut bytes = zstd::encode_all(&b"\x82\xa0\n"[..], 0).unwrap();
+        bytes.extend(zstd::encode_all(&b"\x82\xa2\n"[..], 0).unwrap());
+        let reader = TextReader::from_auto_compressed(&bytes[..], WINDOWS_31J, DecoderTrap::Strict).unwrap();
+        assert_eq!(read_all(reader), "あ\nい\n");
+    }
+    #[cfg(feature = "xz")]
+    #[test]
+    fn xz_test() {
+        use std::io::Write;
+        let mut bytes = Vec::new();
+        for text in [&b"\x82\xa0\n"[..], b"\x82\xa2\n"] {
+            let mut encoder = xz2::write::XzEncoder::new(Vec::new(), 6);
+            encoder.write_all(text).unwrap();
+            bytes.extend(encoder.finish().unwrap());
+        }
+        let reader = TextReader::from_auto_compressed(&bytes[..], WINDOWS_31J, DecoderTrap::Strict).unwrap();
+        assert_eq!(read_all(reader), "あ\nい\n");
+    }
+    /// Returns the bytes a few at a time, as a pipe may.
+    struct Trickle<'a>(&'a [u8]);
+    impl Read for Trickle<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(2);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+    #[test]
+    fn detect_short_reads() {
+        let plain = TextReader::from_auto_compressed(Trickle(b"a\n\x82\xa0"), WINDOWS_31J, DecoderTrap::Strict).unwrap();
+        assert_eq!(read_all(plain), "a\nあ");
+        let xz = TextReader::from_auto_compressed(Trickle(b"\xfd7zXZ\x00"), WINDOWS_31J, DecoderTrap::Strict);
+        assert!(xz.is_err() || cfg!(feature = "xz"));
+        #[cfg(feature = "gzip")]
+        {
+            let bytes = gzip(b"\x82\xa0\n");
+            let reader = TextReader::from_auto_compressed(Trickle(&bytes), WINDOWS_31J, DecoderTrap::Strict).unwrap();
+            assert_eq!(read_all(reader), "あ\n");
+        }
+    }
+    #[test]
+    fn unsupported() {
+        for (bytes, enabled) in [(&b"\x28\xb5\x2f\xfd"[..], cfg!(feature = "zstd")), (b"\xfd7zXZ\x00", cfg!(feature = "xz"))] {
+            if !enabled {
+                let err = TextReader::from_auto_compressed(bytes, WINDOWS_31J, DecoderTrap::Strict).unwrap_err();
+                assert_eq!(err.kind(), ErrorKind::Unsupported);
+            }
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! One-shot decoding and encoding of whole texts.
+
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+use encoding::{DecoderTrap, EncoderTrap, EncodingRef};
+
+use crate::{Error, Result, TextReader};
+
+/// Decodes `bytes` in `encoding` into a `String` at once.
+///
+/// Unlike `Encoding::decode`, an error has the position of the invalid bytes, and the decoder
+/// of `TextReader` is used, such as the one resolving the byte order of "utf-16".
+///
+/// # Examples
+/// ```
+/// use encoding::all::WINDOWS_31J;
+/// use encoding::DecoderTrap;
+/// use textstream::decode_bytes;
+/// # fn foo() -> textstream::Result<()> {
+/// assert_eq!(decode_bytes(b"\x82\xa0\x82\xa2", WINDOWS_31J, DecoderTrap::Strict)?, "あい");
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub fn decode_bytes(bytes: &[u8], encoding: EncodingRef, trap: DecoderTrap) -> Result<String> {
+    read_all(bytes, bytes.len(), encoding, trap)
+}
+
+/// Reads the file at `path` in `encoding` into a `String` at once, like `fs::read_to_string`.
+///
+/// # Examples
+/// ```no_run
+/// use encoding::all::WINDOWS_31J;
+/// use encoding::DecoderTrap;
+/// use textstream::decode_file;
+/// # fn foo() -> textstream::Result<()> {
+/// let text = decode_file("shiftjis.txt", WINDOWS_31J, DecoderTrap::Replace)?;
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub fn decode_file<P: AsRef<Path>>(path: P, encoding: EncodingRef, trap: DecoderTrap) -> Result<String> {
+    let file = File::open(path)?;
+    let len = file.metadata().map_or(0, |metadata| metadata.len() as usize);
+    read_all(file, len, encoding, trap)
+}
+
+/// Encodes `s` in `encoding` into bytes at once, like `Encoding::encode` failing with `Error`.
+///
+/// # Examples
+/// ```
+/// use encoding::all::WINDOWS_31J;
+/// use encoding::EncoderTrap;
+/// use textstream::encode_str;
+/// # fn foo() -> textstream::Result<()> {
+/// assert_eq!(encode_str("あい", WINDOWS_31J, EncoderTrap::Strict)?, b"\x82\xa0\x82\xa2");
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub fn encode_str(s: &str, encoding: EncodingRef, trap: EncoderTrap) -> Result<Vec<u8>> {
+    encoding.encode(s, trap).map_err(Error::from)
+}
+
+/// Writes `text` in `encoding` to the file at `path` at once, like `fs::write`.
+/// The file is created, or truncated if it exists. It is left untouched if `text` can't be
+/// encoded.
+///
+/// # Examples
+/// ```no_run
+/// use encoding::all::WINDOWS_31J;
+/// use encoding::EncoderTrap;
+/// use textstream::encode_to_file;
+/// # fn foo() -> textstream::Result<()> {
+/// encode_to_file("shiftjis.txt", "こんにちは\r\n", WINDOWS_31J, EncoderTrap::Strict)?;
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub fn encode_to_file<P: AsRef<Path>>(path: P, text: &str, encoding: EncodingRef, trap: EncoderTrap) -> Result<()> {
+    let bytes = encode_str(text, encoding, trap)?;
+    Ok(fs::write(path, bytes)?)
+}
+
+/// For internal use. Reads `reader` to the end, reserving `len` bytes for the text.
+fn read_all<R: Read>(reader: R, len: usize, encoding: EncodingRef, trap: DecoderTrap) -> Result<String> {
+    let mut s = String::with_capacity(len);
+    TextReader::new(reader, encoding, trap).read_to_end(&mut s)?;
+    Ok(s)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+    use encoding::all::{UTF_16LE, UTF_8, WINDOWS_31J};
+    use encoding::all::ASCII;
+
+    #[test]
+    fn decode_bytes_test() {
+        assert_eq!(decode_bytes(b"a\x00\n\x00", UTF_16LE, DecoderTrap::Strict).unwrap(), "a\n");
+        assert_eq!(decode_bytes(b"a\nb\xff", UTF_8, DecoderTrap::Replace).unwrap(), "a\nb\u{fffd}");
+        match decode_bytes(b"a\nb\xff", UTF_8, DecoderTrap::Strict) {
+            Err(Error::DecodeError { position, .. }) => assert_eq!(position.line, 2),
+            result => panic!("unexpected {:?}", result),
+        }
+        assert_eq!(decode_bytes(b"", UTF_8, DecoderTrap::Strict).unwrap(), "");
+    }
+    #[test]
+    fn decode_file_test() {
+        let path = env::temp_dir().join(format!("textstream-decode-{}.txt", process::id()));
+        fs::write(&path, b"\x82\xa0\r\n").unwrap();
+        assert_eq!(decode_file(&path, WINDOWS_31J, DecoderTrap::Strict).unwrap(), "あ\r\n");
+        fs::remove_file(&path).unwrap();
+        assert!(decode_file(&path, UTF_8, DecoderTrap::Strict).is_err());
+    }
+    #[test]
+    fn encode_str_test() {
+        assert_eq!(encode_str("aあ", WINDOWS_31J, EncoderTrap::Strict).unwrap(), b"a\x82\xa0");
+        assert_eq!(encode_str("aあ", ASCII, EncoderTrap::Replace).unwrap(), b"a?");
+        assert!(encode_str("aあ", ASCII, EncoderTrap::Strict).is_err());
+        assert_eq!(encode_str("a\u{1F600}", ASCII, crate::HEX_NCR_ESCAPE).unwrap(), b"a&#x1F600;");
+    }
+    #[test]
+    fn encode_to_file_test() {
+        let path = env::temp_dir().join(format!("textstream-encode-{}.txt", process::id()));
+        encode_to_file(&path, "あ\n", UTF_16LE, EncoderTrap::Strict).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), b"\x42\x30\n\x00");
+        assert_eq!(decode_file(&path, UTF_16LE, DecoderTrap::Strict).unwrap(), "あ\n");
+        assert!(encode_to_file(&path, "\u{1F600}", ASCII, EncoderTrap::Strict).is_err());
+        assert_eq!(fs::read(&path).unwrap(), b"\x42\x30\n\x00");
+        fs::remove_file(&path).unwrap();
+    }
+}
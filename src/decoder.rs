@@ -0,0 +1,545 @@
+//! Decoders which can be sent to another thread.
+
+use std::str;
+
+use encoding::all;
+use encoding::{CodecError, DecoderTrap, EncodingRef, RawDecoder, StringWriter};
+use memchr::memchr;
+
+/// A decoder plugged into `TextReader` by `TextReader::with_decoder`, for codecs which are not
+/// in the `encoding` crate. It is implemented for every `RawDecoder` which is `Send`.
+///
+/// The semantics are those of `RawDecoder`. `feed` is called with the bytes of the stream in
+/// order, and returns the number of bytes processed, and an error whose `upto` is the end of the
+/// invalid sequence, if any. An incomplete sequence at the end of `input` is not counted as
+/// processed, but held by the decoder until it is completed by the following bytes; an invalid
+/// sequence starting with the bytes held may end at an `upto` of 0 or less. After an error, the
+/// decoder holds nothing, and the bytes from `upto` on are fed again. A decoder reporting an
+/// empty invalid sequence, or holding more than 16 bytes, fails as making no progress.
+/// `finish` is called at the end of the stream only, so that shift states are kept between
+/// chunks. It fails if the decoder holds an incomplete sequence, and resets the decoder to the
+/// initial state.
+pub trait TextDecoder: Send {
+    /// Returns whether the decoder decodes ASCII bytes to themselves.
+    fn is_ascii_compatible(&self) -> bool { false }
+
+    /// Decodes `input` into `output`.
+    fn feed(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>);
+
+    /// Finishes decoding, and resets the decoder.
+    fn finish(&mut self, output: &mut dyn StringWriter) -> Option<CodecError>;
+
+    /// Resets the decoder to the initial state, discarding what is left by `finish`.
+    fn reset(&mut self) {
+        self.finish(&mut String::new());
+    }
+
+    /// Handles the invalid sequence `input` with `trap`, returning whether to continue.
+    /// By default, the function of `DecoderTrap::Call` is given a placeholder `RawDecoder`,
+    /// which decodes nothing.
+    fn trap(&mut self, trap: DecoderTrap, input: &[u8], output: &mut dyn StringWriter) -> bool {
+        trap.trap(&mut NoDecoder, input, output)
+    }
+}
+
+/// The `RawDecoder` given to `DecoderTrap::Call` by the decoders which are not one.
+struct NoDecoder;
+
+impl RawDecoder for NoDecoder {
+    fn from_self(&self) -> Box<dyn RawDecoder> { Box::new(NoDecoder) }
+
+    fn is_ascii_compatible(&self) -> bool { false }
+
+    fn raw_feed(&mut self, _input: &[u8], _output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+        (0, None)
+    }
+
+    fn raw_finish(&mut self, _output: &mut dyn StringWriter) -> Option<CodecError> { None }
+}
+
+impl<D: RawDecoder + Send> TextDecoder for D {
+    fn is_ascii_compatible(&self) -> bool {
+        RawDecoder::is_ascii_compatible(self)
+    }
+
+    fn feed(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+        self.raw_feed(input, output)
+    }
+
+    fn finish(&mut self, output: &mut dyn StringWriter) -> Option<CodecError> {
+        self.raw_finish(output)
+    }
+
+    fn trap(&mut self, trap: DecoderTrap, input: &[u8], output: &mut dyn StringWriter) -> bool {
+        trap.trap(self, input, output)
+    }
+}
+
+/// Creates a decoder for `encoding`.
+///
+/// UTF-8 is only validated, by `Utf8Decoder`.
+/// Encodings of the `encoding` crate in the families enabled by cargo features are identified
+/// by name and decoded by their own decoders. Other encodings, including those of disabled
+/// families, are decoded by `ReplayDecoder`.
+pub(crate) fn new_decoder(encoding: EncodingRef) -> Box<dyn TextDecoder> {
+    if encoding.name() == "utf-8" {
+        return Box::new(Utf8Decoder { pending: Vec::new() });
+    }
+    match builtin_encoding(encoding.name()) {
+        Some(builtin) => Box::new(BuiltinDecoder(builtin.raw_decoder())),
+        None => Box::new(ReplayDecoder { encoding, pending: Vec::new() }),
+    }
+}
+
+/// For internal use. Returns the encoding of the `encoding` crate named `name`, if its family
+/// is enabled. The statics of disabled families are not referred to, so that their tables are
+/// not linked in.
+pub(crate) fn builtin_encoding(name: &str) -> Option<EncodingRef> {
+    let builtin: EncodingRef = match name {
+        "error" => all::ERROR,
+        "ascii" => all::ASCII,
+        "utf-8" => all::UTF_8,
+        "utf-16le" => all::UTF_16LE,
+        "utf-16be" => all::UTF_16BE,
+        "pua-mapped-binary" => all::whatwg::X_USER_DEFINED,
+        "encoder-only-utf-8" => all::whatwg::REPLACEMENT,
+        #[cfg(feature = "singlebyte")]
+        "ibm866" => all::IBM866,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-1" => all::ISO_8859_1,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-2" => all::ISO_8859_2,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-3" => all::ISO_8859_3,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-4" => all::ISO_8859_4,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-5" => all::ISO_8859_5,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-6" => all::ISO_8859_6,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-7" => all::ISO_8859_7,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-8" => all::ISO_8859_8,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-8-i" => all::whatwg::ISO_8859_8_I,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-10" => all::ISO_8859_10,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-13" => all::ISO_8859_13,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-14" => all::ISO_8859_14,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-15" => all::ISO_8859_15,
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-16" => all::ISO_8859_16,
+        #[cfg(feature = "singlebyte")]
+        "koi8-r" => all::KOI8_R,
+        #[cfg(feature = "singlebyte")]
+        "koi8-u" => all::KOI8_U,
+        #[cfg(feature = "singlebyte")]
+        "mac-roman" => all::MAC_ROMAN,
+        #[cfg(feature = "singlebyte")]
+        "mac-cyrillic" => all::MAC_CYRILLIC,
+        #[cfg(feature = "singlebyte")]
+        "windows-874" => all::WINDOWS_874,
+        #[cfg(feature = "singlebyte")]
+        "windows-1250" => all::WINDOWS_1250,
+        #[cfg(feature = "singlebyte")]
+        "windows-1251" => all::WINDOWS_1251,
+        #[cfg(feature = "singlebyte")]
+        "windows-1252" => all::WINDOWS_1252,
+        #[cfg(feature = "singlebyte")]
+        "windows-1253" => all::WINDOWS_1253,
+        #[cfg(feature = "singlebyte")]
+        "windows-1254" => all::WINDOWS_1254,
+        #[cfg(feature = "singlebyte")]
+        "windows-1255" => all::WINDOWS_1255,
+        #[cfg(feature = "singlebyte")]
+        "windows-1256" => all::WINDOWS_1256,
+        #[cfg(feature = "singlebyte")]
+        "windows-1257" => all::WINDOWS_1257,
+        #[cfg(feature = "singlebyte")]
+        "windows-1258" => all::WINDOWS_1258,
+        #[cfg(feature = "japanese")]
+        "euc-jp" => all::EUC_JP,
+        #[cfg(feature = "japanese")]
+        "windows-31j" => all::WINDOWS_31J,
+        #[cfg(feature = "japanese")]
+        "iso-2022-jp" => all::ISO_2022_JP,
+        #[cfg(feature = "korean")]
+        "windows-949" => all::WINDOWS_949,
+        #[cfg(feature = "simpchinese")]
+        "gbk" => all::GBK,
+        #[cfg(feature = "simpchinese")]
+        "gb18030" => all::GB18030,
+        #[cfg(feature = "simpchinese")]
+        "hz" => all::HZ,
+        #[cfg(feature = "tradchinese")]
+        "big5-2003" => all::BIG5_2003,
+        _ => return None,
+    };
+    Some(builtin)
+}
+
+/// Decoder of an encoding of the `encoding` crate.
+struct BuiltinDecoder(Box<dyn RawDecoder>);
+
+// SAFETY: the decoders of the `encoding` crate are plain data, without references to shared or
+// thread-local state. `new_decoder` only wraps decoders created by its own statics.
+unsafe impl Send for BuiltinDecoder {}
+
+impl RawDecoder for BuiltinDecoder {
+    fn from_self(&self) -> Box<dyn RawDecoder> {
+        Box::new(BuiltinDecoder(self.0.from_self()))
+    }
+
+    fn is_ascii_compatible(&self) -> bool {
+        self.0.is_ascii_compatible()
+    }
+
+    fn raw_feed(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+        self.0.raw_feed(input, output)
+    }
+
+    fn raw_finish(&mut self, output: &mut dyn StringWriter) -> Option<CodecError> {
+        self.0.raw_finish(output)
+    }
+}
+
+/// Decoder of UTF-8, which validates the bytes with `str::from_utf8` and copies them as they
+/// are, much faster than decoding them character by character. The errors are the same as those
+/// of the UTF-8 decoder of the `encoding` crate.
+struct Utf8Decoder {
+    /// The incomplete sequence held.
+    pending: Vec<u8>,
+}
+
+impl Utf8Decoder {
+    /// Completes the sequence held with the start of `input`.
+    /// Returns the number of bytes of `input` taken, and an error if the sequence is invalid,
+    /// or `None` if it is still incomplete.
+    fn complete(&mut self, input: &[u8], output: &mut dyn StringWriter) -> Option<(usize, Option<CodecError>)> {
+        let npending = self.pending.len();
+        let width = match self.pending[0] {
+            0xc0..=0xdf => 2,
+            0xe0..=0xef => 3,
+            _ => 4,
+        };
+        let taken = input.len().min(width - npending);
+        self.pending.extend_from_slice(&input[..taken]);
+        let result = match str::from_utf8(&self.pending) {
+            Ok(c) => {
+                output.write_str(c);
+                Some((taken, None))
+            },
+            Err(e) => e.error_len().map(|len| {
+                let upto = len as isize - npending as isize;
+                (0, Some(CodecError { upto, cause: "invalid sequence".into() }))
+            }),
+        };
+        if result.is_some() {
+            self.pending.clear();
+        }
+        result
+    }
+}
+
+impl RawDecoder for Utf8Decoder {
+    fn from_self(&self) -> Box<dyn RawDecoder> {
+        Box::new(Utf8Decoder { pending: Vec::new() })
+    }
+
+    fn is_ascii_compatible(&self) -> bool {
+        true
+    }
+
+    fn raw_feed(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+        if !self.pending.is_empty() {
+            match self.complete(input, output) {
+                Some((taken, None)) => {
+                    let (processed, err) = self.raw_feed(&input[taken..], output);
+                    let err = err.map(|e| CodecError { upto: e.upto + taken as isize, ..e });
+                    return (taken + processed, err);
+                },
+                Some(result) => return result,
+                None => return (0, None),
+            }
+        }
+        let (valid, err) = match str::from_utf8(input) {
+            Ok(valid) => (valid, None),
+            Err(e) => {
+                // SAFETY: the bytes up to `valid_up_to` are valid UTF-8.
+                let valid = unsafe { str::from_utf8_unchecked(&input[..e.valid_up_to()]) };
+                (valid, Some(e))
+            },
+        };
+        output.write_str(valid);
+        match err.map(|e| e.error_len()) {
+            None => (input.len(), None),
+            Some(Some(len)) => {
+                let upto = (valid.len() + len) as isize;
+                (valid.len(), Some(CodecError { upto, cause: "invalid sequence".into() }))
+            },
+            Some(None) => {
+                self.pending.extend_from_slice(&input[valid.len()..]);
+                (valid.len(), None)
+            },
+        }
+    }
+
+    fn raw_finish(&mut self, _output: &mut dyn StringWriter) -> Option<CodecError> {
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.pending.clear();
+        Some(CodecError { upto: 0, cause: "incomplete sequence".into() })
+    }
+}
+
+/// Decoder of an encoding which is not of the `encoding` crate, or whose family is disabled.
+/// The decoder of the encoding is created for every call, and the bytes of an incomplete
+/// sequence are fed again to the next one, so that only `Send` data is kept between calls.
+/// Shift states don't survive between calls, so a stateful encoding, such as an ISO-2022 one, is
+/// decoded wrongly if a chunk ends after a shift sequence; enable its family, or use
+/// `TextReader::with_decoder` with a `TextDecoder` for it.
+struct ReplayDecoder {
+    encoding: EncodingRef,
+    pending: Vec<u8>,
+}
+
+impl RawDecoder for ReplayDecoder {
+    fn from_self(&self) -> Box<dyn RawDecoder> {
+        Box::new(ReplayDecoder { encoding: self.encoding, pending: Vec::new() })
+    }
+
+    fn is_ascii_compatible(&self) -> bool {
+        self.encoding.raw_decoder().is_ascii_compatible()
+    }
+
+    fn raw_feed(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+        let npending = self.pending.len();
+        self.pending.extend_from_slice(input);
+        let (processed, err) = self.encoding.raw_decoder().raw_feed(&self.pending, output);
+        match err {
+            Some(mut e) => {
+                self.pending.clear();
+                e.upto -= npending as isize;
+                (processed.saturating_sub(npending), Some(e))
+            },
+            None => {
+                self.pending.drain(..processed);
+                (processed.saturating_sub(npending), None)
+            },
+        }
+    }
+
+    fn raw_finish(&mut self, output: &mut dyn StringWriter) -> Option<CodecError> {
+        let mut decoder = self.encoding.raw_decoder();
+        let (_, err) = decoder.raw_feed(&self.pending, output);
+        self.pending.clear();
+        err.or_else(|| decoder.raw_finish(output))
+    }
+}
+
+/// For internal use. Resets the decoder it wraps before every `\n`, for
+/// `TextReader::set_reset_at_newline`. A `\n` after bytes held by the decoder is fed to it as
+/// it is.
+pub(crate) struct LineReset<'a> {
+    inner: &'a mut dyn TextDecoder,
+    /// Whether the decoder holds bytes fed before.
+    held: bool,
+}
+
+impl<'a> LineReset<'a> {
+    pub(crate) fn new(inner: &'a mut dyn TextDecoder, held: bool) -> LineReset<'a> {
+        LineReset { inner, held }
+    }
+}
+
+impl TextDecoder for LineReset<'_> {
+    fn is_ascii_compatible(&self) -> bool {
+        self.inner.is_ascii_compatible()
+    }
+
+    fn feed(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+        let mut processed = 0;
+        let mut start = 0;
+        loop {
+            if !self.held && input.get(start) == Some(&b'\n') {
+                self.inner.reset();
+            }
+            let end = input.get(start + 1..).and_then(|rest| memchr(b'\n', rest)).map_or(input.len(), |n| start + 1 + n);
+            let (n, err) = self.inner.feed(&input[start..end], output);
+            // Nothing is processed until the bytes held are completed.
+            if n > 0 || !self.held {
+                processed = start + n;
+            }
+            if let Some(e) = err {
+                self.held = false;
+                return (processed, Some(CodecError { upto: e.upto + start as isize, ..e }));
+            }
+            self.held = processed < end;
+            if end == input.len() {
+                return (processed, None);
+            }
+            start = end;
+        }
+    }
+
+    fn finish(&mut self, output: &mut dyn StringWriter) -> Option<CodecError> {
+        self.inner.finish(output)
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+
+    fn trap(&mut self, trap: DecoderTrap, input: &[u8], output: &mut dyn StringWriter) -> bool {
+        self.inner.trap(trap, input, output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::all::{UTF_8, UTF_16LE};
+    use encoding::{DecoderTrap, Encoding, EncoderTrap, RawEncoder};
+
+    /// UTF-16LE under another name, to be decoded by `ReplayDecoder`.
+    struct Custom;
+    impl Encoding for Custom {
+        fn name(&self) -> &'static str { "custom" }
+        fn raw_encoder(&self) -> Box<dyn RawEncoder> { UTF_16LE.raw_encoder() }
+        fn raw_decoder(&self) -> Box<dyn RawDecoder> { UTF_16LE.raw_decoder() }
+    }
+    static CUSTOM: Custom = Custom;
+
+    #[test]
+    fn builtin_identified() {
+        let mut s = String::new();
+        let mut decoder = new_decoder(UTF_16LE);
+        let (processed, err) = decoder.feed(b"a\x00\x42", &mut s);
+        assert!(processed == 2 && err.is_none());
+        assert!(decoder.finish(&mut s).is_some());
+        assert_eq!(s, "a");
+        // Stateful, so that its own decoder is needed.
+        #[cfg(feature = "japanese")]
+        {
+            s.clear();
+            let mut decoder = new_decoder(encoding::all::ISO_2022_JP);
+            for b in b"\x1b$B$\"$$\x1b(Ba" {
+                let (_, err) = decoder.feed(&[*b], &mut s);
+                assert!(err.is_none());
+            }
+            assert!(decoder.finish(&mut s).is_none());
+            assert_eq!(s, "あいa");
+        }
+    }
+    #[test]
+    #[cfg(feature = "all-encodings")]
+    fn builtin_encoding_test() {
+        for encoding in encoding::all::encodings() {
+            assert_eq!(builtin_encoding(encoding.name()).map(|e| e.name()), Some(encoding.name()));
+        }
+        assert!(builtin_encoding("custom").is_none());
+    }
+    #[test]
+    fn utf8_decoder() {
+        let mut s = String::new();
+        let mut decoder = new_decoder(UTF_8);
+        let (processed, err) = decoder.feed(b"a\xe3\x81\x82\xe3\x81", &mut s);
+        assert!(processed == 4 && err.is_none());
+        assert!(decoder.finish(&mut s).is_some());
+        let (processed, err) = decoder.feed(b"b\xe3\x81c", &mut s);
+        assert!(processed == 1 && err.unwrap().upto == 3);
+        assert!(decoder.finish(&mut s).is_none());
+        assert_eq!(s, "aあb");
+        s.clear();
+        for (bytes, expected) in [(&b"\xe3"[..], 0), (b"\x81", 0), (b"\x82a\xf0\x9f", 2)] {
+            let (processed, err) = decoder.feed(bytes, &mut s);
+            assert!(processed == expected && err.is_none());
+        }
+        let (processed, err) = decoder.feed(b"\x98b", &mut s);
+        assert!(processed == 0 && err.unwrap().upto == 1);
+        assert_eq!(s, "あa");
+        assert_eq!(UTF_8.decode(b"\xe3\x81c\xff", DecoderTrap::Replace).unwrap(), "\u{fffd}c\u{fffd}");
+        let mut reader = crate::TextReader::new(&b"\xe3\x81c\xff"[..], UTF_8, DecoderTrap::Replace);
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "\u{fffd}c\u{fffd}");
+    }
+    #[test]
+    fn custom_text_decoder() {
+        use std::io::{Cursor, SeekFrom};
+        /// Decodes `+` and the next byte as the character after that byte.
+        struct Pairs { plus: bool }
+        impl TextDecoder for Pairs {
+            fn feed(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+                for (i, &b) in input.iter().enumerate() {
+                    match (std::mem::take(&mut self.plus), b) {
+                        (true, b) => output.write_char((b + 1) as char),
+                        (false, b'+') => self.plus = true,
+                        (false, b'!') => return (i, Some(CodecError { upto: i as isize + 1, cause: "!".into() })),
+                        (false, b) => output.write_char(b as char),
+                    }
+                }
+                (input.len().saturating_sub(self.plus as usize), None)
+            }
+            fn finish(&mut self, _output: &mut dyn StringWriter) -> Option<CodecError> {
+                std::mem::take(&mut self.plus).then(|| CodecError { upto: 0, cause: "incomplete".into() })
+            }
+        }
+        let decoder = Pairs { plus: false };
+        let mut reader = crate::TextReader::with_decoder(Cursor::new(b"a+a!b+"), UTF_8, decoder, DecoderTrap::Replace);
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "ab\u{fffd}b\u{fffd}");
+        assert_eq!(reader.position().byte, 6);
+        reader.seek(SeekFrom::Start(2)).unwrap();
+        assert_eq!(reader.read_char().unwrap(), Some('a'));
+        assert!(!reader.get_decoder().is_ascii_compatible());
+    }
+    #[test]
+    fn no_progress() {
+        /// Reports an empty invalid sequence before every byte, or holds every byte.
+        struct Stuck(bool);
+        impl TextDecoder for Stuck {
+            fn feed(&mut self, _input: &[u8], _output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+                (0, self.0.then(|| CodecError { upto: 0, cause: "stuck".into() }))
+            }
+            fn finish(&mut self, _output: &mut dyn StringWriter) -> Option<CodecError> { None }
+        }
+        for (invalid, chunk_size) in [(true, 1), (true, 2048), (false, 1), (false, 2048)] {
+            let bytes = [b'a'; 100];
+            let mut reader = crate::TextReaderBuilder::new(UTF_8).trap(DecoderTrap::Replace).chunk_size(chunk_size)
+                .build_with_decoder(&bytes[..], Stuck(invalid));
+            match reader.read_to_end(&mut String::new()) {
+                Err(crate::Error::DecodeError { cause, position }) => {
+                    assert!(cause.starts_with("the decoder makes no progress"));
+                    assert_eq!(position.byte, 0);
+                },
+                _ => panic!("expected DecodeError"),
+            }
+        }
+    }
+    #[test]
+    fn text_reader_is_send() {
+        fn assert_send<T: Send>(_: T) {}
+        assert_send(crate::TextReader::new(&b""[..], UTF_8, DecoderTrap::Strict));
+    }
+    #[test]
+    fn replay_decoder() {
+        let bytes = UTF_16LE.encode("あいう", EncoderTrap::Strict).unwrap();
+        let mut s = String::new();
+        let mut decoder = new_decoder(&CUSTOM);
+        for b in bytes.iter() {
+            let (processed, err) = decoder.feed(&[*b], &mut s);
+            assert!(processed <= 1 && err.is_none());
+        }
+        assert!(decoder.finish(&mut s).is_none());
+        assert_eq!(s, "あいう");
+        let mut reader = crate::TextReader::new(&bytes[..5], &CUSTOM, DecoderTrap::Strict);
+        assert!(reader.read_to_end(&mut String::new()).is_err());
+    }
+}
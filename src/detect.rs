@@ -0,0 +1,383 @@
+//! Charset detection.
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+
+#[cfg(feature = "detect")]
+use chardetng::EncodingDetector;
+use encoding::{DecoderTrap, EncodingRef};
+use encoding::all::UTF_8;
+#[cfg(feature = "japanese")]
+use encoding::all::{EUC_JP, ISO_2022_JP, WINDOWS_31J};
+
+use crate::html::sniff_bom;
+use crate::label::encoding_from_whatwg_label;
+use crate::{Result, TextReader};
+
+/// Number of bytes examined by the detecting constructors.
+#[cfg(feature = "japanese")]
+const DETECT_WINDOW: u64 = 64 * 1024;
+
+impl<R: Read> TextReader<BufReader<R>> {
+    /// Creates a new `TextReader` with the encoding guessed from the first 64 KiB of `reader`.
+    /// The guessed encoding is available by `encoding_name`.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let sjis = b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd\x81\x41\x90\xa2\x8a\x45\x81\x42";
+    /// let mut reader = TextReader::detect(&sjis[..], DecoderTrap::Strict)?;
+    /// assert_eq!(reader.encoding_name(), "windows-31j");
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s)?;
+    /// assert_eq!(s, "こんにちは、世界。");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    #[cfg(feature = "detect")]
+    pub fn detect(reader: R, trap: DecoderTrap) -> Result<TextReader<BufReader<R>>> {
+        TextReader::with_detected(reader, DETECT_WINDOW, trap, |window, last| {
+            let mut detector = EncodingDetector::new();
+            detector.feed(window, last);
+            encoding_from_whatwg_label(detector.guess(None, true).name())
+        })
+    }
+
+    /// Creates a new `TextReader` with the encoding guessed by `detect_japanese` from the first
+    /// 64 KiB of `reader`, or UTF-8 if it can't be guessed.
+    /// The guessed encoding is available by `encoding_name`.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let eucjp = b"\xa4\xb3\xa4\xf3\xa4\xcb\xa4\xc1\xa4\xcf";
+    /// let mut reader = TextReader::detect_japanese(&eucjp[..], DecoderTrap::Strict)?;
+    /// assert_eq!(reader.encoding_name(), "euc-jp");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    #[cfg(feature = "japanese")]
+    pub fn detect_japanese(reader: R, trap: DecoderTrap) -> Result<TextReader<BufReader<R>>> {
+        TextReader::with_detected(reader, DETECT_WINDOW, trap, |window, _| detect_japanese(window))
+    }
+
+    /// For internal use. Creates a new `TextReader` with the encoding guessed by `guess`
+    /// from the first `size` bytes of `reader`. `guess` is also told whether the bytes are all
+    /// of the stream.
+    pub(crate) fn with_detected<F>(mut reader: R, size: u64, trap: DecoderTrap, guess: F) -> Result<TextReader<BufReader<R>>>
+        where F: FnOnce(&[u8], bool) -> Option<EncodingRef>
+    {
+        let mut window = Vec::new();
+        reader.by_ref().take(size).read_to_end(&mut window)?;
+        let last = (window.len() as u64) < size;
+        let encoding = guess(&window, last).unwrap_or(UTF_8);
+        let mut textreader = TextReader::new(reader, encoding, trap);
+        textreader.binbuf = window;
+        Ok(textreader)
+    }
+}
+
+/// Returns whether `bytes`, the start of a stream in `encoding`, looks like binary data:
+/// it contains NUL, or more than 10% of other control bytes than whitespace and escape.
+/// UTF-16 is never taken for binary data, as it contains NUL bytes for ASCII characters.
+pub(crate) fn looks_binary(bytes: &[u8], encoding: EncodingRef) -> bool {
+    if encoding.name().starts_with("utf-16") {
+        return false;
+    }
+    let mut controls = 0;
+    for &b in bytes {
+        match b {
+            0 => return true,
+            b'\t' | b'\n' | b'\x0c' | b'\r' | b'\x1b' => {},
+            b if b < 0x20 || b == 0x7f => controls += 1,
+            _ => {},
+        }
+    }
+    controls * 10 > bytes.len()
+}
+
+/// Labels of the encodings considered by `detect`, in the order of preference for ties.
+/// Those of the families not enabled by cargo features are skipped.
+const CANDIDATES: &[&str] = &[
+    "utf-8", "iso-2022-jp", "shift_jis", "euc-jp", "gbk", "gb18030", "big5", "euc-kr",
+    "windows-1252", "windows-1250", "windows-1251", "koi8-r", "ibm866", "windows-1253",
+    "windows-1254", "windows-1255", "windows-1256", "windows-1257", "windows-1258", "windows-874",
+    "utf-16le", "utf-16be",
+];
+
+/// Returns the candidate encodings of `bytes`, the start of a text, with their confidence from 0
+/// to 1, the most likely first. Applications can present the choices to users, or apply their
+/// own thresholds, before creating a `TextReader`.
+///
+/// A byte order mark makes its encoding the only candidate, with a confidence of 1. Otherwise,
+/// every encoding family enabled by cargo features is considered, and UTF-16 if `bytes` contains
+/// NUL. The confidence is the share of letters among the non-ASCII characters decoded, with
+/// invalid sequences, control characters and half-width katakana counted against it. Text in
+/// ASCII only is equally likely in every ASCII compatible encoding. With the `detect` feature,
+/// the guess of [chardetng](https://github.com/hsivonen/chardetng) is put first, which tells
+/// apart encodings of similar letters, such as the Cyrillic ones, unless `bytes` contains NUL.
+///
+/// # Examples
+/// ```
+/// use encoding::Encoding;
+/// use textstream::detect;
+/// # fn main() {
+/// let candidates = detect("naïve café".as_bytes());
+/// assert_eq!(candidates[0].0.name(), "utf-8");
+/// assert_eq!(candidates[0].1, 1.0);
+/// let candidates = detect(b"\xff\xfea\0");
+/// assert_eq!(candidates.len(), 1);
+/// assert_eq!(candidates[0].0.name(), "utf-16le");
+/// # }
+/// ```
+pub fn detect(bytes: &[u8]) -> Vec<(EncodingRef, f32)> {
+    if let Some((encoding, _)) = sniff_bom(bytes) {
+        return vec![(encoding, 1.0)];
+    }
+    let has_nul = bytes.contains(&0);
+    let mut candidates: Vec<(EncodingRef, f32)> = CANDIDATES.iter()
+        .filter(|label| has_nul || !label.starts_with("utf-16"))
+        .filter_map(|label| encoding_from_whatwg_label(label))
+        .map(|encoding| (encoding, plausibility(encoding, bytes)))
+        .collect();
+    // Stable, so that ties are left in the order of preference.
+    candidates.sort_by(|a, b| b.1.total_cmp(&a.1));
+    // chardetng doesn't know UTF-16.
+    #[cfg(feature = "detect")]
+    if !has_nul {
+        let mut detector = EncodingDetector::new();
+        detector.feed(bytes, false);
+        let guess = detector.guess(None, true).name();
+        if let Some(encoding) = encoding_from_whatwg_label(guess) {
+            let top = candidates.first().map_or(1.0, |c| c.1);
+            if let Some(i) = candidates.iter().position(|c| c.0.name() == encoding.name()) {
+                candidates.remove(i);
+            }
+            let confidence = plausibility(encoding, bytes).max(top);
+            candidates.insert(0, (encoding, confidence));
+        }
+    }
+    candidates
+}
+
+/// Returns the most likely encoding of the file at `path`, with its confidence, by `detect`
+/// from the first `sample_bytes` bytes, such as 64 KiB, without reading the rest. For a quick
+/// triage of large directories of files in unknown encodings.
+///
+/// # Examples
+/// ```
+/// use encoding::Encoding;
+/// use textstream::detect_file;
+/// # fn foo() -> std::io::Result<()> {
+/// let (encoding, confidence) = detect_file("shiftjis.txt", 64 * 1024)?;
+/// if confidence > 0.9 {
+///     println!("{}", encoding.name());
+/// }
+/// # Ok(())
+/// # }
+/// # fn main() { let _ = foo(); }
+/// ```
+pub fn detect_file<P: AsRef<Path>>(path: P, sample_bytes: u64) -> io::Result<(EncodingRef, f32)> {
+    let mut sample = Vec::new();
+    File::open(path)?.take(sample_bytes).read_to_end(&mut sample)?;
+    // UTF-8 is always a candidate.
+    Ok(detect(&sample)[0])
+}
+
+/// For internal use. Returns how plausible `bytes` is as text in `encoding`, from 0 to 1.
+/// An incomplete sequence at the end is ignored, as `bytes` may be a prefix.
+fn plausibility(encoding: EncodingRef, bytes: &[u8]) -> f32 {
+    let mut decoder = encoding.raw_decoder();
+    let mut s = String::new();
+    let mut bad = 0.0;
+    let mut rest = bytes;
+    while let (processed, Some(e)) = decoder.raw_feed(rest, &mut s) {
+        bad += 2.0;
+        let next = (e.upto.max(processed as isize + 1) as usize).min(rest.len());
+        rest = &rest[next..];
+    }
+    let mut good = 0.0;
+    for c in s.chars() {
+        match c {
+            '\t' | '\n' | '\x0c' | '\r' => {},
+            c if c.is_control() => bad += 1.0,
+            c if c.is_ascii() => {},
+            // CJK punctuation and full-width forms.
+            '\u{3000}'..='\u{303f}' | '\u{ff01}'..='\u{ff60}' => {},
+            // Half-width katakana are rare, but any byte from 0xa1 to 0xdf is one in Shift_JIS.
+            '\u{ff61}'..='\u{ff9f}' => bad += 0.5,
+            c if c.is_alphabetic() => good += 1.0,
+            '\u{e000}'..='\u{f8ff}' => bad += 1.0,
+            _ => bad += 0.5,
+        }
+    }
+    if good + bad == 0.0 { 1.0 } else { good / (good + bad) }
+}
+
+/// Guesses which of Shift_JIS, EUC-JP, ISO-2022-JP and UTF-8 `bytes` is encoded in.
+/// `bytes` may be a prefix of the text, cut in the middle of a character.
+/// Returns `None` if `bytes` is ASCII only or none of them is plausible.
+///
+/// # Examples
+/// ```
+/// use encoding::Encoding;
+/// use textstream::detect_japanese;
+/// # fn main() {
+/// let sjis = b"\x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd";
+/// assert_eq!(detect_japanese(sjis).unwrap().name(), "windows-31j");
+/// assert_eq!(detect_japanese("こんにちは".as_bytes()).unwrap().name(), "utf-8");
+/// assert!(detect_japanese(b"hello").is_none());
+/// # }
+/// ```
+#[cfg(feature = "japanese")]
+pub fn detect_japanese(bytes: &[u8]) -> Option<EncodingRef> {
+    if bytes.iter().all(|&b| b < 0x80) {
+        let escaped = [&b"\x1b$B"[..], b"\x1b$@", b"\x1b(J", b"\x1b(I"].iter()
+            .any(|seq| bytes.windows(seq.len()).any(|w| w == *seq));
+        return if escaped { Some(ISO_2022_JP) } else { None };
+    }
+    match ::std::str::from_utf8(bytes) {
+        Ok(_) => return Some(UTF_8),
+        Err(e) if e.error_len().is_none() => return Some(UTF_8),
+        Err(_) => {}
+    }
+    match (score_sjis(bytes), score_eucjp(bytes)) {
+        (Some(sjis), Some(eucjp)) if eucjp > sjis => Some(EUC_JP),
+        (Some(_), _) => Some(WINDOWS_31J),
+        (None, Some(_)) => Some(EUC_JP),
+        (None, None) => None,
+    }
+}
+
+/// Returns the number of kana in `bytes` as Shift_JIS, or `None` if `bytes` is not Shift_JIS.
+#[cfg(feature = "japanese")]
+fn score_sjis(bytes: &[u8]) -> Option<usize> {
+    let mut score = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            0x00..=0x7f | 0xa1..=0xdf => i += 1,
+            lead @ 0x81..=0x9f | lead @ 0xe0..=0xfc => {
+                match bytes.get(i + 1).cloned() {
+                    Some(0x9f..=0xf1) if lead == 0x82 => score += 1,
+                    Some(0x40..=0x7e) | Some(0x80..=0x96) if lead == 0x83 => score += 1,
+                    Some(0x40..=0x7e) | Some(0x80..=0xfc) | None => {},
+                    Some(_) => return None,
+                }
+                i += 2;
+            },
+            _ => return None,
+        }
+    }
+    Some(score)
+}
+
+/// Returns the number of kana in `bytes` as EUC-JP, or `None` if `bytes` is not EUC-JP.
+#[cfg(feature = "japanese")]
+fn score_eucjp(bytes: &[u8]) -> Option<usize> {
+    let mut score = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let len = match bytes[i] {
+            0x00..=0x7f => 1,
+            0x8e | 0xa1..=0xfe => 2,
+            0x8f => 3,
+            _ => return None,
+        };
+        let end = bytes.len().min(i + len);
+        if bytes[i + 1..end].iter().any(|&b| b < 0xa1 || b == 0xff) {
+            return None;
+        }
+        if len == 2 && end == i + 2 && (bytes[i] == 0xa4 || bytes[i] == 0xa5) {
+            score += 1;
+        }
+        i += len;
+    }
+    Some(score)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::{EncoderTrap, Encoding};
+
+    #[cfg(feature = "detect")]
+    #[test]
+    fn detect_euc_jp() {
+        let text = "吾輩は猫である。名前はまだ無い。どこで生れたかとんと見当がつかぬ。\n".repeat(2000);
+        let bytes = EUC_JP.encode(&text, EncoderTrap::Strict).unwrap();
+        let mut reader = TextReader::detect(&bytes[..], DecoderTrap::Strict).unwrap();
+        assert_eq!(reader.encoding_name(), "euc-jp");
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, text);
+    }
+    #[cfg(feature = "japanese")]
+    #[test]
+    fn detect_japanese_test() {
+        let text = "吾輩は猫である。名前はまだ無い。ｱｲｳ";
+        for encoding in [WINDOWS_31J as EncodingRef, EUC_JP, ISO_2022_JP, UTF_8].iter() {
+            let bytes = encoding.encode(text, EncoderTrap::Strict).unwrap();
+            assert_eq!(detect_japanese(&bytes).unwrap().name(), encoding.name());
+            assert_eq!(detect_japanese(&bytes[..bytes.len() - 1]).unwrap().name(), encoding.name());
+        }
+        assert!(detect_japanese(b"\x80\x80\xff").is_none());
+    }
+    #[test]
+    fn detect_candidates() {
+        let text = "吾輩は猫である。名前はまだ無い。どこで生れたかとんと見当がつかぬ。";
+        #[allow(unused_mut)]
+        let mut encodings = vec![UTF_8 as EncodingRef];
+        #[cfg(feature = "japanese")]
+        encodings.extend([WINDOWS_31J as EncodingRef, EUC_JP, ISO_2022_JP]);
+        for encoding in encodings {
+            let bytes = encoding.encode(text, EncoderTrap::Strict).unwrap();
+            let candidates = detect(&bytes[..bytes.len() - 1]);
+            assert_eq!(candidates[0].0.name(), encoding.name());
+            assert_eq!(candidates[0].1, 1.0);
+        }
+        let candidates = detect(b"plain ascii");
+        assert_eq!(candidates[0].0.name(), "utf-8");
+        assert!(candidates.iter().all(|c| !c.0.name().starts_with("utf-16")));
+        let candidates = detect(b"a\0b\0");
+        assert_eq!(candidates[0].0.name(), "utf-16le");
+        assert_eq!(detect(b"\xef\xbb\xbf\x82\xa0").len(), 1);
+    }
+    #[test]
+    fn detect_file_test() {
+        use std::{env, fs, process};
+        let text = "吾輩は猫である。\n".repeat(10000);
+        let path = env::temp_dir().join(format!("textstream-detect-{}.txt", process::id()));
+        #[cfg(not(feature = "japanese"))]
+        let encoding = UTF_8;
+        #[cfg(feature = "japanese")]
+        let encoding = EUC_JP;
+        fs::write(&path, encoding.encode(&text, EncoderTrap::Strict).unwrap()).unwrap();
+        // The sample ends in the middle of a character.
+        let (detected, confidence) = detect_file(&path, 1001).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(detected.name(), encoding.name());
+        assert_eq!(confidence, 1.0);
+        assert!(detect_file(&path, 1001).is_err());
+    }
+    #[cfg(feature = "japanese")]
+    #[test]
+    fn detect_japanese_reader() {
+        let text = "こんにちは\n".repeat(20000);
+        let bytes = WINDOWS_31J.encode(&text, EncoderTrap::Strict).unwrap();
+        let mut reader = TextReader::detect_japanese(&bytes[..], DecoderTrap::Strict).unwrap();
+        assert_eq!(reader.encoding_name(), "windows-31j");
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, text);
+        let reader = TextReader::detect_japanese(&b"abc"[..], DecoderTrap::Strict).unwrap();
+        assert_eq!(reader.encoding_name(), "utf-8");
+    }
+}
@@ -0,0 +1,177 @@
+//! Parallel processing of lines.
+
+use std::collections::HashMap;
+use std::io::BufRead;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, sync_channel, Receiver, SyncSender};
+use std::thread;
+
+use crate::{Error, Lines, Result, TextReader};
+
+impl<R: BufRead> TextReader<R> {
+    /// Returns an iterator which decodes lines on the current thread, calls `f` for each line on
+    /// a pool of `workers` threads, and yields the results in the order of the lines.
+    /// Lines are passed to `f` without the line terminator, like `lines()`.
+    ///
+    /// If `f` panics, the panic is resumed on the thread iterating over the results.
+    ///
+    /// # Panics
+    /// Panics if `workers` is 0.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let reader = TextReader::new(&b"a\nbb\nccc\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let lens: Vec<usize> = reader.dispatch_lines(4, |line| line.len()).map(|r| r.unwrap()).collect();
+    /// assert_eq!(lens, [1, 2, 3]);
+    /// # }
+    /// ```
+    pub fn dispatch_lines<T, F>(self, workers: usize, f: F) -> DispatchLines<R, T>
+        where T: Send + 'static, F: Fn(String) -> T + Send + Sync + 'static
+    {
+        assert!(workers > 0, "workers must be positive");
+        let (job_sender, job_receiver) = sync_channel::<(usize, String)>(workers);
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+        let (result_sender, result_receiver) = channel();
+        let f = Arc::new(f);
+        for _ in 0..workers {
+            let job_receiver = job_receiver.clone();
+            let result_sender = result_sender.clone();
+            let f = f.clone();
+            thread::spawn(move || {
+                loop {
+                    let job = match job_receiver.lock() {
+                        Ok(receiver) => receiver.recv(),
+                        Err(poisoned) => poisoned.into_inner().recv(),
+                    };
+                    let (index, line) = match job {
+                        Ok(job) => job,
+                        Err(_) => break,
+                    };
+                    let result = panic::catch_unwind(AssertUnwindSafe(|| f(line)));
+                    if result_sender.send((index, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        DispatchLines {
+            lines: self.lines(),
+            job_sender: Some(job_sender),
+            result_receiver,
+            max_in_flight: workers * 2,
+            in_flight: 0,
+            sent: 0,
+            yielded: 0,
+            done: HashMap::new(),
+            error: None,
+        }
+    }
+}
+
+/// An iterator over the results of `dispatch_lines()` on a `TextReader`.
+/// Please see the documentation of `dispatch_lines()` for more details.
+pub struct DispatchLines<R: BufRead, T> {
+    lines: Lines<R>,
+    job_sender: Option<SyncSender<(usize, String)>>,
+    result_receiver: Receiver<(usize, thread::Result<T>)>,
+    max_in_flight: usize,
+    in_flight: usize,
+    sent: usize,
+    yielded: usize,
+    done: HashMap<usize, T>,
+    error: Option<(usize, Error)>,
+}
+
+impl<R: BufRead, T> DispatchLines<R, T> {
+    /// For internal use. Sends lines to the workers until enough lines are in flight.
+    fn fill(&mut self) {
+        while self.in_flight < self.max_in_flight {
+            let sender = match self.job_sender {
+                Some(ref sender) => sender,
+                None => return,
+            };
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    sender.send((self.sent, line)).expect("worker threads exited");
+                    self.sent += 1;
+                    self.in_flight += 1;
+                },
+                Some(Err(e)) => {
+                    self.error = Some((self.sent, e));
+                    self.job_sender = None;
+                },
+                None => {
+                    self.job_sender = None;
+                },
+            }
+        }
+    }
+}
+
+impl<R: BufRead, T> Iterator for DispatchLines<R, T> {
+    type Item = Result<T>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(t) = self.done.remove(&self.yielded) {
+                self.yielded += 1;
+                return Some(Ok(t));
+            }
+            if self.error.as_ref().map(|e| e.0) == Some(self.yielded) {
+                return self.error.take().map(|e| Err(e.1));
+            }
+            self.fill();
+            if self.in_flight == 0 {
+                return None;
+            }
+            let (index, result) = self.result_receiver.recv().expect("worker threads exited");
+            self.in_flight -= 1;
+            match result {
+                Ok(t) => { self.done.insert(index, t); },
+                Err(payload) => panic::resume_unwind(payload),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use encoding::DecoderTrap;
+    use encoding::all::WINDOWS_31J;
+
+    #[test]
+    fn dispatch_lines_ordered() {
+        let mut v = vec![];
+        for i in 0..200 {
+            v.extend_from_slice(format!("{}\n", i).as_bytes());
+        }
+        let reader = TextReader::new(&v[..], WINDOWS_31J, DecoderTrap::Strict);
+        let res: Vec<usize> = reader.dispatch_lines(4, |line| {
+            let n: usize = line.parse().unwrap();
+            thread::sleep(Duration::from_micros((n % 7) as u64 * 100));
+            n
+        }).map(|r| r.unwrap()).collect();
+        assert_eq!(res, (0..200).collect::<Vec<_>>());
+    }
+    #[test]
+    fn dispatch_lines_error() {
+        let reader = TextReader::new(&b"a\nb\n\xff\nc\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut res = reader.dispatch_lines(2, |line| line);
+        assert_eq!(res.next().unwrap().unwrap(), "a");
+        assert_eq!(res.next().unwrap().unwrap(), "b");
+        assert!(res.next().unwrap().is_err());
+        assert!(res.next().is_none());
+    }
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn dispatch_lines_panic() {
+        let reader = TextReader::new(&b"a\nb\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+        for _ in reader.dispatch_lines(2, |line| if line == "b" { panic!("boom") }) {}
+    }
+}
@@ -0,0 +1,82 @@
+//! Lossless decoding of invalid bytes.
+
+use encoding::{DecoderTrap, RawDecoder, StringWriter};
+
+/// The first of the 256 code points to which `SURROGATE_ESCAPE` maps bytes, in the
+/// Supplementary Private Use Area-B. Lone surrogates, to which Python maps them, are not valid
+/// in `str`.
+const ESCAPE_BASE: u32 = 0x10ff00;
+
+/// A trap decoding every byte of an invalid sequence to a code point reserved for it, like
+/// `errors="surrogateescape"` of Python. `EncodeWriter::set_unescape` writes the bytes back,
+/// so that text with invalid bytes passes through a filter byte for byte.
+///
+/// The reserved code points are U+10FF00 to U+10FFFF, in the Supplementary Private Use Area-B.
+/// Text which already has them can't be told from escaped bytes.
+///
+/// # Examples
+/// ```
+/// use std::io::Write;
+/// use encoding::all::WINDOWS_31J;
+/// use encoding::EncoderTrap;
+/// use textstream::{EncodeWriter, TextReader, SURROGATE_ESCAPE};
+/// # fn foo() -> textstream::Result<()> {
+/// let bytes = b"\x82\xa0\xff\n\x82";
+/// let mut reader = TextReader::new(&bytes[..], WINDOWS_31J, SURROGATE_ESCAPE);
+/// let mut s = String::new();
+/// reader.read_to_end(&mut s)?;
+/// assert_eq!(s, "あ\u{10ffff}\n\u{10ff82}");
+/// let mut writer = EncodeWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+/// writer.set_unescape(true);
+/// writer.write_all(s.as_bytes())?;
+/// assert_eq!(writer.finish()?, bytes);
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub const SURROGATE_ESCAPE: DecoderTrap = DecoderTrap::Call(escape_bytes);
+
+/// Returns the code point to which `SURROGATE_ESCAPE` maps `byte`.
+pub fn escape_byte(byte: u8) -> char {
+    char::from_u32(ESCAPE_BASE + byte as u32).unwrap()
+}
+
+/// Returns the byte which `SURROGATE_ESCAPE` maps to `c`, if `c` is a reserved code point.
+pub fn unescape_char(c: char) -> Option<u8> {
+    (c as u32).checked_sub(ESCAPE_BASE).map(|b| b as u8)
+}
+
+fn escape_bytes(_decoder: &mut dyn RawDecoder, input: &[u8], output: &mut dyn StringWriter) -> bool {
+    for &b in input {
+        output.write_char(escape_byte(b));
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use encoding::EncoderTrap;
+    use encoding::all::{UTF_16LE, UTF_8};
+    use crate::{EncodeWriter, TextReader};
+
+    fn round_trip(bytes: &[u8], encoding: encoding::EncodingRef) {
+        let mut reader = TextReader::new(bytes, encoding, SURROGATE_ESCAPE);
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        let mut writer = EncodeWriter::new(Vec::new(), encoding, EncoderTrap::Strict);
+        writer.set_unescape(true);
+        writer.write_all(s.as_bytes()).unwrap();
+        assert_eq!(writer.finish().unwrap(), bytes);
+    }
+    #[test]
+    fn surrogate_escape() {
+        for b in 0..=255 {
+            assert_eq!(unescape_char(escape_byte(b)), Some(b));
+        }
+        assert_eq!(unescape_char('\u{10feff}'), None);
+        round_trip(b"a\xe3\x81\x82\xe3\x81b\xff\xe3", UTF_8);
+        round_trip(b"a\x00\x00\xd8b\x00\x00\xdc\x00", UTF_16LE);
+    }
+}
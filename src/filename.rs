@@ -0,0 +1,102 @@
+//! File names in legacy encodings.
+
+#[cfg(unix)]
+use std::ffi::{OsStr, OsString};
+#[cfg(unix)]
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+
+use encoding::{DecoderTrap, EncoderTrap, EncodingRef};
+
+use crate::{unescape_char, Error, Result};
+
+/// Decodes a file name in `encoding`, such as the name of an entry of a zip or tar archive
+/// made on an old system, which are often in Shift_JIS or CP437.
+///
+/// With `SURROGATE_ESCAPE`, invalid bytes are kept for `encode_file_name` to write them back,
+/// so that the file can be found again by its name.
+///
+/// # Examples
+/// ```
+/// use encoding::all::WINDOWS_31J;
+/// use encoding::EncoderTrap;
+/// use textstream::{decode_file_name, encode_file_name, SURROGATE_ESCAPE};
+/// # fn foo() -> textstream::Result<()> {
+/// let name = decode_file_name(b"\x95\\\x8e\xa6\xff.txt", WINDOWS_31J, SURROGATE_ESCAPE)?;
+/// assert!(name.starts_with("表示"));
+/// assert_eq!(encode_file_name(&name, WINDOWS_31J, EncoderTrap::Strict)?, b"\x95\\\x8e\xa6\xff.txt");
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub fn decode_file_name(name: &[u8], encoding: EncodingRef, trap: DecoderTrap) -> Result<String> {
+    encoding.decode(name, trap).map_err(Error::from)
+}
+
+/// Encodes a file name in `encoding`. The code points reserved by `SURROGATE_ESCAPE` are
+/// written as the bytes which they escape.
+pub fn encode_file_name(name: &str, encoding: EncodingRef, trap: EncoderTrap) -> Result<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(name.len());
+    let mut rest = name;
+    while let Some((i, c, b)) = rest.char_indices().find_map(|(i, c)| unescape_char(c).map(|b| (i, c, b))) {
+        bytes.extend(encoding.encode(&rest[..i], trap)?);
+        bytes.push(b);
+        rest = &rest[i + c.len_utf8()..];
+    }
+    bytes.extend(encoding.encode(rest, trap)?);
+    Ok(bytes)
+}
+
+/// Decodes the bytes of a file name of the system in `encoding`, such as the names listed by
+/// `fs::read_dir` on a mount shared with a legacy system.
+/// Please see `decode_file_name`.
+///
+/// # Examples
+/// ```no_run
+/// use std::fs;
+/// use encoding::all::WINDOWS_31J;
+/// use textstream::{decode_os_str, SURROGATE_ESCAPE};
+/// # fn foo() -> textstream::Result<()> {
+/// for entry in fs::read_dir("/mnt/legacy")? {
+///     println!("{}", decode_os_str(&entry?.file_name(), WINDOWS_31J, SURROGATE_ESCAPE)?);
+/// }
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+#[cfg(unix)]
+pub fn decode_os_str(name: &OsStr, encoding: EncodingRef, trap: DecoderTrap) -> Result<String> {
+    decode_file_name(name.as_bytes(), encoding, trap)
+}
+
+/// Encodes a file name in `encoding` into a file name of the system.
+/// Please see `encode_file_name`.
+#[cfg(unix)]
+pub fn encode_os_string(name: &str, encoding: EncodingRef, trap: EncoderTrap) -> Result<OsString> {
+    encode_file_name(name, encoding, trap).map(OsString::from_vec)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::all::{ASCII, UTF_8};
+    use crate::SURROGATE_ESCAPE;
+
+    #[test]
+    fn file_name() {
+        assert_eq!(decode_file_name(b"a\xffb", UTF_8, DecoderTrap::Replace).unwrap(), "a\u{fffd}b");
+        assert!(decode_file_name(b"a\xffb", UTF_8, DecoderTrap::Strict).is_err());
+        let name = decode_file_name(b"\xff\xe3\x81\x82\xe3\x81", UTF_8, SURROGATE_ESCAPE).unwrap();
+        assert_eq!(name, "\u{10ffff}あ\u{10ffe3}\u{10ff81}");
+        assert_eq!(encode_file_name(&name, UTF_8, EncoderTrap::Strict).unwrap(), b"\xff\xe3\x81\x82\xe3\x81");
+        assert_eq!(encode_file_name("aあ", ASCII, EncoderTrap::Replace).unwrap(), b"a?");
+        assert!(encode_file_name("aあ", ASCII, EncoderTrap::Strict).is_err());
+    }
+    #[cfg(unix)]
+    #[test]
+    fn os_str() {
+        let name = OsStr::from_bytes(b"a\xffb");
+        let decoded = decode_os_str(name, UTF_8, SURROGATE_ESCAPE).unwrap();
+        assert_eq!(decoded, "a\u{10ffff}b");
+        assert_eq!(encode_os_string(&decoded, UTF_8, EncoderTrap::Strict).unwrap(), name);
+    }
+}
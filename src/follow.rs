@@ -0,0 +1,236 @@
+//! Following files growing at the end.
+
+use std::fmt;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufReader, ErrorKind, Seek, SeekFrom};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use crate::{is_unicode_newline, Error, NewlinePolicy, Result, TextReader};
+
+impl TextReader<BufReader<File>> {
+    /// Returns an iterator over the lines of this file, like `lines()`, which waits for the file
+    /// to grow instead of ending, like `tail -f`. The size of the file is polled every
+    /// `interval`. A line is yielded once its line terminator has been written.
+    ///
+    /// The iterator never ends, except by an error. Lines are read from the current position;
+    /// seek to the end first to skip the existing lines. The end of the file is not taken for
+    /// the end of the stream: a character split across two writes is decoded once it is
+    /// completed, rather than handled by the trap, and the shift state of a stateful encoding is
+    /// kept.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::io::SeekFrom;
+    /// use std::time::Duration;
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::open("app.log", WINDOWS_31J, DecoderTrap::Replace)?;
+    /// reader.seek(SeekFrom::End(0))?;
+    /// for line in reader.follow(Duration::from_millis(500)).path("app.log") {
+    ///     let line = line?;
+    ///     if line.contains("ERROR") {
+    ///         println!("{}", line);
+    ///     }
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn follow(mut self, interval: Duration) -> Follow {
+        self.growing = true;
+        Follow {
+            textreader: self,
+            interval,
+            path: None,
+            partial: String::new(),
+        }
+    }
+}
+
+/// An iterator over the lines of a file growing at the end.
+/// This struct is generally created by calling `follow()` on a `TextReader`.
+/// Please see the documentation of `follow()` for more details.
+pub struct Follow {
+    textreader: TextReader<BufReader<File>>,
+    interval: Duration,
+    path: Option<PathBuf>,
+    partial: String,
+}
+
+impl Follow {
+    /// Watches `path`, the path of the file, for truncation and rotation while waiting.
+    ///
+    /// If the file is truncated, it is read again from the start. If another file is created at
+    /// `path`, as log rotation does, the new file is read from the start, after the last line of
+    /// the old one even without a line terminator. Rotation is detected on Unix only.
+    pub fn path<P: AsRef<Path>>(mut self, path: P) -> Follow {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Unwraps this `Follow`, returning the underlying `TextReader`.
+    /// A line read without the line terminator yet is lost. The end of the file is the end of
+    /// the stream again.
+    pub fn into_inner(mut self) -> TextReader<BufReader<File>> {
+        self.textreader.growing = false;
+        self.textreader
+    }
+
+    /// For internal use. Returns whether `self.partial` ends with a line terminator.
+    /// A `\r` at the end of the file may be followed by `\n`, so that it ends a line only if
+    /// more text follows.
+    fn terminated(&mut self) -> Result<bool> {
+        match self.partial.chars().next_back() {
+            Some('\n') => Ok(true),
+            Some('\r') if self.textreader.newline != NewlinePolicy::Lf => {
+                Ok(self.textreader.peek_char()?.is_some())
+            },
+            Some(c) => Ok(self.textreader.newline == NewlinePolicy::Unicode && is_unicode_newline(c)),
+            None => Ok(false),
+        }
+    }
+
+    /// For internal use. Waits for the file to grow, and checks for truncation and rotation.
+    /// Returns whether another file is read.
+    fn wait(&mut self) -> io::Result<bool> {
+        thread::sleep(self.interval);
+        let path = match self.path {
+            Some(ref path) => path,
+            None => return Ok(false),
+        };
+        let metadata = match fs::metadata(path) {
+            Ok(metadata) => metadata,
+            // Rotated away, and not created again yet.
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if is_other_file(&metadata, &self.textreader.get_ref().get_ref().metadata()?) {
+            *self.textreader.get_mut() = BufReader::new(File::open(path)?);
+            self.textreader.seek(SeekFrom::Start(0))?;
+            return Ok(true);
+        }
+        if metadata.len() < self.textreader.get_mut().stream_position()? {
+            self.textreader.seek(SeekFrom::Start(0))?;
+            self.partial.clear();
+        }
+        Ok(false)
+    }
+}
+
+impl Iterator for Follow {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.textreader.read_line(&mut self.partial) {
+                Ok(0) | Err(Error::IncompleteSequence(_)) => {},
+                Ok(_) => match self.terminated() {
+                    Ok(true) => {
+                        let mut line = mem::take(&mut self.partial);
+                        self.textreader.strip_newline(&mut line);
+                        return Some(Ok(line));
+                    },
+                    Ok(false) => {},
+                    Err(e) => return Some(Err(e)),
+                },
+                Err(e) => return Some(Err(e)),
+            }
+            match self.wait() {
+                Ok(true) if !self.partial.is_empty() => return Some(Ok(mem::take(&mut self.partial))),
+                Ok(_) => {},
+                Err(e) => return Some(Err(Error::IOError(e))),
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Follow {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Follow")
+            .field("textreader", &self.textreader)
+            .field("interval", &self.interval)
+            .field("path", &self.path)
+            .field("partial", &self.partial)
+            .finish()
+    }
+}
+
+#[cfg(unix)]
+fn is_other_file(a: &Metadata, b: &Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() != b.dev() || a.ino() != b.ino()
+}
+
+#[cfg(not(unix))]
+fn is_other_file(_: &Metadata, _: &Metadata) -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+    use std::io::Write;
+    use std::fs::OpenOptions;
+    use encoding::DecoderTrap;
+    use encoding::all::WINDOWS_31J;
+
+    fn append(path: &Path, bytes: &[u8]) {
+        OpenOptions::new().append(true).open(path).unwrap().write_all(bytes).unwrap();
+    }
+    #[test]
+    fn follow_test() {
+        let path = env::temp_dir().join(format!("textstream-follow-{}.txt", process::id()));
+        fs::write(&path, b"a\nb").unwrap();
+        let reader = TextReader::open(&path, WINDOWS_31J, DecoderTrap::Strict).unwrap();
+        let mut lines = reader.follow(Duration::from_millis(10)).path(&path);
+        assert_eq!(lines.next().unwrap().unwrap(), "a");
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                append(&path, b"c\x82");
+                thread::sleep(Duration::from_millis(50));
+                append(&path, b"\xa0\r\nd\n");
+            })
+        };
+        assert_eq!(lines.next().unwrap().unwrap(), "bcあ");
+        assert_eq!(lines.next().unwrap().unwrap(), "d");
+        writer.join().unwrap();
+        fs::write(&path, b"e\n").unwrap();
+        assert_eq!(lines.next().unwrap().unwrap(), "e");
+        #[cfg(unix)]
+        {
+            append(&path, b"f");
+            let rotated = path.with_extension("txt.1");
+            fs::rename(&path, &rotated).unwrap();
+            fs::write(&path, b"g\n").unwrap();
+            assert_eq!(lines.next().unwrap().unwrap(), "f");
+            assert_eq!(lines.next().unwrap().unwrap(), "g");
+            fs::remove_file(&rotated).unwrap();
+        }
+        fs::remove_file(&path).unwrap();
+    }
+    #[test]
+    fn follow_replace() {
+        let path = env::temp_dir().join(format!("textstream-follow-replace-{}.txt", process::id()));
+        fs::write(&path, b"a\xff\nc\x82").unwrap();
+        let reader = TextReader::open(&path, WINDOWS_31J, DecoderTrap::Replace).unwrap();
+        let mut lines = reader.follow(Duration::from_millis(10));
+        assert_eq!(lines.next().unwrap().unwrap(), "a\u{fffd}");
+        let writer = {
+            let path = path.clone();
+            thread::spawn(move || {
+                thread::sleep(Duration::from_millis(50));
+                append(&path, b"\xa0\n");
+            })
+        };
+        assert_eq!(lines.next().unwrap().unwrap(), "cあ");
+        writer.join().unwrap();
+        fs::remove_file(&path).unwrap();
+    }
+}
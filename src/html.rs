@@ -0,0 +1,299 @@
+//! Encoding declarations of HTML documents.
+
+use std::io::{BufReader, Read};
+
+use encoding::{DecoderTrap, EncodingRef};
+use encoding::all::{UTF_8, WINDOWS_1252};
+
+use crate::{Result, TextReader};
+use crate::label::encoding_from_whatwg_label;
+
+/// Number of bytes prescanned, as recommended by the WHATWG.
+const PRESCAN_SIZE: u64 = 1024;
+
+impl<R: Read> TextReader<BufReader<R>> {
+    /// Creates a new `TextReader` for an HTML document, with the encoding of its byte order
+    /// mark, or declared by `<meta charset>` or `<meta http-equiv>` in the first 1024 bytes, or
+    /// `default`. The byte order mark is not returned as text.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_1252;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let page = b"<html><head><meta charset=\"utf-8\"><title>caf\xc3\xa9</title>";
+    /// let reader = TextReader::from_html(&page[..], WINDOWS_1252, DecoderTrap::Replace)?;
+    /// assert_eq!(reader.encoding_name(), "utf-8");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn from_html(reader: R, default: EncodingRef, trap: DecoderTrap) -> Result<TextReader<BufReader<R>>> {
+        let mut bomlen = 0;
+        let mut textreader = TextReader::with_detected(reader, PRESCAN_SIZE, trap, |window, _| {
+            match sniff_bom(window) {
+                Some((encoding, len)) => {
+                    bomlen = len;
+                    Some(encoding)
+                },
+                None => Some(prescan(window).unwrap_or(default)),
+            }
+        })?;
+        textreader.binbuf.drain(..bomlen);
+        textreader.consumed = bomlen as u64;
+        Ok(textreader)
+    }
+}
+
+/// Returns the encoding and the length of the byte order mark at the start of `bytes`.
+pub(crate) fn sniff_bom(bytes: &[u8]) -> Option<(EncodingRef, usize)> {
+    if bytes.starts_with(b"\xef\xbb\xbf") {
+        Some((UTF_8, 3))
+    }
+    else if bytes.starts_with(b"\xfe\xff") {
+        encoding_from_whatwg_label("utf-16be").map(|e| (e, 2))
+    }
+    else if bytes.starts_with(b"\xff\xfe") {
+        encoding_from_whatwg_label("utf-16le").map(|e| (e, 2))
+    }
+    else {
+        None
+    }
+}
+
+/// Prescans the start of an HTML document for an encoding declared by `<meta charset>` or
+/// `<meta http-equiv="Content-Type">`, following the WHATWG algorithm.
+/// Returns `None` if there is no declaration with a label known to `textstream::label`.
+///
+/// As the algorithm requires, UTF-16 declarations are taken as UTF-8, and `x-user-defined` as
+/// windows-1252. Byte order marks are not looked for.
+///
+/// # Examples
+/// ```
+/// use encoding::Encoding;
+/// use textstream::html::prescan;
+/// # fn main() {
+/// let page = b"<!-- <meta charset=latin1> --><META HTTP-EQUIV='Content-Type' CONTENT='text/html; charset=UTF-8'>";
+/// assert_eq!(prescan(page).unwrap().name(), "utf-8");
+/// assert!(prescan(b"<p>no declaration</p>").is_none());
+/// # }
+/// ```
+pub fn prescan(bytes: &[u8]) -> Option<EncodingRef> {
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let rest = &bytes[pos..];
+        if rest.starts_with(b"<!--") {
+            match find(&rest[2..], b"-->") {
+                Some(i) => pos += 2 + i + 3,
+                None => return None,
+            }
+        }
+        else if rest.len() >= 6 && rest[..5].eq_ignore_ascii_case(b"<meta")
+            && (is_space(rest[5]) || rest[5] == b'/')
+        {
+            pos += 5;
+            if let Some(encoding) = meta_charset(bytes, &mut pos) {
+                return Some(encoding);
+            }
+        }
+        else if rest[0] == b'<' && rest.len() >= 2 && (rest[1].is_ascii_alphabetic()
+            || (rest[1] == b'/' && rest.len() >= 3 && rest[2].is_ascii_alphabetic()))
+        {
+            pos += 1;
+            while pos < bytes.len() && !is_space(bytes[pos]) && bytes[pos] != b'>' {
+                pos += 1;
+            }
+            while get_attribute(bytes, &mut pos).is_some() {}
+        }
+        else if rest.starts_with(b"<!") || rest.starts_with(b"</") || rest.starts_with(b"<?") {
+            match rest.iter().position(|&b| b == b'>') {
+                Some(i) => pos += i + 1,
+                None => return None,
+            }
+        }
+        else {
+            pos += 1;
+        }
+    }
+    None
+}
+
+/// Processes the attributes of a `meta` tag from `pos`, returning the declared encoding.
+fn meta_charset(bytes: &[u8], pos: &mut usize) -> Option<EncodingRef> {
+    let mut names: Vec<Vec<u8>> = Vec::new();
+    let mut got_pragma = false;
+    let mut need_pragma = None;
+    let mut charset = None;
+    while let Some((name, value)) = get_attribute(bytes, pos) {
+        if names.contains(&name) {
+            continue;
+        }
+        match &name[..] {
+            b"http-equiv" => got_pragma |= value == b"content-type",
+            b"content" if charset.is_none() => {
+                if let Some(label) = charset_from_content(&value) {
+                    charset = Some(encoding_from_whatwg_label(&String::from_utf8_lossy(label)));
+                    need_pragma = Some(true);
+                }
+            },
+            b"charset" if charset.is_none() => {
+                charset = Some(encoding_from_whatwg_label(&String::from_utf8_lossy(&value)));
+                need_pragma = Some(false);
+            },
+            _ => {},
+        }
+        names.push(name);
+    }
+    match (need_pragma, charset) {
+        (Some(true), _) if !got_pragma => None,
+        (Some(_), Some(Some(encoding))) => match encoding.name() {
+            "utf-16le" | "utf-16be" => Some(UTF_8),
+            "pua-mapped-binary" => Some(WINDOWS_1252),
+            _ => Some(encoding),
+        },
+        _ => None,
+    }
+}
+
+/// Gets an attribute from `pos` with the lowercased name and value, or `None` at the end of
+/// the tag or of the input.
+fn get_attribute(bytes: &[u8], pos: &mut usize) -> Option<(Vec<u8>, Vec<u8>)> {
+    let byte = |pos: usize| bytes.get(pos).cloned();
+    while byte(*pos).is_some_and(|b| is_space(b) || b == b'/') {
+        *pos += 1;
+    }
+    let mut name = Vec::new();
+    let mut value = Vec::new();
+    loop {
+        match byte(*pos)? {
+            b'=' if !name.is_empty() => {
+                *pos += 1;
+                break;
+            },
+            b if is_space(b) => {
+                while byte(*pos).is_some_and(is_space) {
+                    *pos += 1;
+                }
+                if byte(*pos)? != b'=' {
+                    return Some((name, value));
+                }
+                *pos += 1;
+                break;
+            },
+            b'/' | b'>' if !name.is_empty() => return Some((name, value)),
+            b'>' => return None,
+            b => name.push(b.to_ascii_lowercase()),
+        }
+        *pos += 1;
+    }
+    while byte(*pos).is_some_and(is_space) {
+        *pos += 1;
+    }
+    match byte(*pos)? {
+        quote @ b'"' | quote @ b'\'' => {
+            loop {
+                *pos += 1;
+                match byte(*pos)? {
+                    b if b == quote => {
+                        *pos += 1;
+                        return Some((name, value));
+                    },
+                    b => value.push(b.to_ascii_lowercase()),
+                }
+            }
+        },
+        b'>' => return Some((name, value)),
+        _ => {},
+    }
+    loop {
+        match byte(*pos)? {
+            b if is_space(b) || b == b'>' => return Some((name, value)),
+            b => value.push(b.to_ascii_lowercase()),
+        }
+        *pos += 1;
+    }
+}
+
+/// Extracts the encoding label from the value of a `content` attribute, which is lowercased.
+fn charset_from_content(content: &[u8]) -> Option<&[u8]> {
+    let mut pos = 0;
+    loop {
+        pos += find(&content[pos..], b"charset")? + 7;
+        while content.get(pos).is_some_and(|&b| is_space(b)) {
+            pos += 1;
+        }
+        if content.get(pos) == Some(&b'=') {
+            break;
+        }
+    }
+    pos += 1;
+    while content.get(pos).is_some_and(|&b| is_space(b)) {
+        pos += 1;
+    }
+    let rest = &content[pos..];
+    match rest.first() {
+        Some(&quote) if quote == b'"' || quote == b'\'' => {
+            let len = rest[1..].iter().position(|&b| b == quote)?;
+            Some(&rest[1..1 + len])
+        },
+        Some(_) => {
+            let len = rest.iter().position(|&b| is_space(b) || b == b';').unwrap_or(rest.len());
+            Some(&rest[..len])
+        },
+        None => None,
+    }
+}
+
+fn is_space(b: u8) -> bool {
+    b == b'\t' || b == b'\n' || b == b'\x0c' || b == b'\r' || b == b' '
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::all::ISO_8859_2;
+
+    fn prescan_name(bytes: &[u8]) -> Option<&'static str> {
+        prescan(bytes).map(|e| e.name())
+    }
+    #[test]
+    fn prescan_test() {
+        assert_eq!(prescan_name(b"<meta charset=utf-8>"), Some("utf-8"));
+        assert_eq!(prescan_name(b"<meta charset = \"UTF-16LE\">"), Some("utf-8"));
+        assert_eq!(prescan_name(b"<meta/charset='utf-16be'/>"), Some("utf-8"));
+        assert_eq!(prescan_name(b"<meta content=\"text/html; charset=utf-8\" http-equiv=content-type>"), Some("utf-8"));
+        assert_eq!(prescan_name(b"<meta content=\"text/html; charset=utf-8\">"), None);
+        assert_eq!(prescan_name(b"<meta content=\"text/html; charset=utf-8\" http-equiv=content-type charset=bogus>"), Some("utf-8"));
+        #[cfg(feature = "japanese")]
+        assert_eq!(prescan_name(b"<meta content=\"text/html; charset=euc-jp\" http-equiv=content-type charset=utf-8>"), Some("euc-jp"));
+        assert_eq!(prescan_name(b"<meta charset=bogus><meta charset=utf-8>"), Some("utf-8"));
+        assert_eq!(prescan_name(b"<div title='<meta charset=latin2>'><meta charset=utf-8>"), Some("utf-8"));
+        assert_eq!(prescan_name(b"<!-- <meta charset=latin2> -->"), None);
+        assert_eq!(prescan_name(b"<metal charset=utf-8>"), None);
+        assert_eq!(prescan_name(b"<meta charset=x-user-defined>"), Some("windows-1252"));
+    }
+    #[test]
+    fn charset_from_content_test() {
+        assert_eq!(charset_from_content(b"text/html; charset=utf-8"), Some(&b"utf-8"[..]));
+        assert_eq!(charset_from_content(b"text/html;charset = 'utf-8' ; foo"), Some(&b"utf-8"[..]));
+        assert_eq!(charset_from_content(b"charsetcharset=a;b"), Some(&b"a"[..]));
+        assert_eq!(charset_from_content(b"charset=\"utf-8"), None);
+        assert_eq!(charset_from_content(b"text/html"), None);
+    }
+    #[test]
+    fn from_html_test() {
+        let page = b"\xef\xbb\xbf<meta charset=windows-1252>caf\xc3\xa9";
+        let mut reader = TextReader::from_html(&page[..], ISO_8859_2, DecoderTrap::Strict).unwrap();
+        assert_eq!(reader.encoding_name(), "utf-8");
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "<meta charset=windows-1252>café");
+        let reader = TextReader::from_html(&b"<p>"[..], ISO_8859_2, DecoderTrap::Strict).unwrap();
+        assert_eq!(reader.encoding_name(), "iso-8859-2");
+    }
+}
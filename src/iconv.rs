@@ -0,0 +1,156 @@
+//! Decoding with the iconv of the system.
+
+use std::ffi::CString;
+use std::io::{self, ErrorKind};
+use std::{fmt, mem, ptr, str};
+
+use encoding::{CodecError, StringWriter};
+
+use crate::TextDecoder;
+
+/// Number of bytes of UTF-8 converted at once.
+const OUTPUT_SIZE: usize = 1024;
+
+/// A decoder converting to UTF-8 with `iconv(3)` of the system, for the encodings not in the
+/// `encoding` crate, such as EBCDIC code pages. The names of the encodings are those of the
+/// system, listed by `iconv -l`.
+///
+/// # Examples
+/// ```
+/// use encoding::all::ISO_8859_1;
+/// use encoding::DecoderTrap;
+/// use textstream::{IconvDecoder, TextReader};
+/// # fn foo() -> textstream::Result<()> {
+/// let decoder = IconvDecoder::new("IBM037")?;
+/// // EBCDIC is a single byte encoding, like ISO-8859-1.
+/// let mut reader = TextReader::with_decoder(&b"\xc8\x85\x93\x93\x96"[..], ISO_8859_1, decoder, DecoderTrap::Strict);
+/// let mut s = String::new();
+/// reader.read_to_end(&mut s)?;
+/// assert_eq!(s, "Hello");
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub struct IconvDecoder {
+    cd: libc::iconv_t,
+    name: String,
+    /// The incomplete sequence held, which iconv leaves unconverted.
+    pending: Vec<u8>,
+}
+
+// SAFETY: a conversion descriptor may be used from any thread, by one thread at a time, which
+// `&mut self` ensures.
+unsafe impl Send for IconvDecoder {}
+
+impl IconvDecoder {
+    /// Creates a decoder of the encoding `name`.
+    /// Fails with `InvalidInput` if the system doesn't support the encoding.
+    pub fn new(name: &str) -> io::Result<IconvDecoder> {
+        let invalid = || io::Error::new(ErrorKind::InvalidInput, format!("unknown iconv encoding {}", name));
+        let from = CString::new(name).map_err(|_| invalid())?;
+        // SAFETY: both arguments are NUL terminated strings.
+        let cd = unsafe { libc::iconv_open(b"UTF-8\0".as_ptr() as *const libc::c_char, from.as_ptr()) };
+        if cd as isize == -1 {
+            return Err(invalid());
+        }
+        Ok(IconvDecoder { cd, name: name.to_string(), pending: Vec::new() })
+    }
+
+    /// Gets the name of the encoding.
+    pub fn name(&self) -> &str { &self.name }
+
+    /// Converts `input`, holding an incomplete sequence at the end.
+    fn convert(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+        let mut buf = [0u8; OUTPUT_SIZE];
+        let mut inptr = input.as_ptr() as *mut libc::c_char;
+        let mut inleft = input.len();
+        loop {
+            let mut outptr = buf.as_mut_ptr() as *mut libc::c_char;
+            let mut outleft = buf.len();
+            // SAFETY: the pointers and the lengths are those of `input` and `buf`, which iconv
+            // advances together. The input is not written to.
+            let ret = unsafe { libc::iconv(self.cd, &mut inptr, &mut inleft, &mut outptr, &mut outleft) };
+            let errno = io::Error::last_os_error().raw_os_error();
+            let written = buf.len() - outleft;
+            // SAFETY: iconv converts to UTF-8 whole characters only.
+            output.write_str(unsafe { str::from_utf8_unchecked(&buf[..written]) });
+            let processed = input.len() - inleft;
+            if ret != usize::MAX {
+                return (processed, None);
+            }
+            match errno {
+                Some(libc::E2BIG) => {},
+                Some(libc::EINVAL) => {
+                    self.pending.extend_from_slice(&input[processed..]);
+                    return (processed, None);
+                },
+                _ => {
+                    let upto = processed as isize + 1;
+                    return (processed, Some(CodecError { upto, cause: "invalid sequence".into() }));
+                },
+            }
+        }
+    }
+}
+
+impl TextDecoder for IconvDecoder {
+    fn feed(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+        if self.pending.is_empty() {
+            return self.convert(input, output);
+        }
+        // The sequence held is converted again with the input.
+        let npending = self.pending.len();
+        let mut bytes = mem::take(&mut self.pending);
+        bytes.extend_from_slice(input);
+        let (processed, err) = self.convert(&bytes, output);
+        let err = err.map(|e| CodecError { upto: e.upto - npending as isize, ..e });
+        (processed.saturating_sub(npending), err)
+    }
+
+    fn finish(&mut self, _output: &mut dyn StringWriter) -> Option<CodecError> {
+        // SAFETY: null pointers reset the conversion state.
+        unsafe {
+            libc::iconv(self.cd, ptr::null_mut(), ptr::null_mut(), ptr::null_mut(), ptr::null_mut());
+        }
+        if self.pending.is_empty() {
+            return None;
+        }
+        self.pending.clear();
+        Some(CodecError { upto: 0, cause: "incomplete sequence".into() })
+    }
+}
+
+impl Drop for IconvDecoder {
+    fn drop(&mut self) {
+        // SAFETY: the descriptor is opened by `new`, and closed only here.
+        unsafe {
+            libc::iconv_close(self.cd);
+        }
+    }
+}
+
+impl fmt::Debug for IconvDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IconvDecoder").field("name", &self.name).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::DecoderTrap;
+    use encoding::all::WINDOWS_31J;
+    use crate::TextReader;
+
+    #[test]
+    fn iconv_decoder() {
+        let decoder = IconvDecoder::new("SHIFT_JIS").unwrap();
+        let bytes = b"\x82\xa0\n\x82\xa2\xff\x82";
+        let mut reader = TextReader::with_decoder(&bytes[..], WINDOWS_31J, decoder, DecoderTrap::Replace);
+        reader.chunk_size = 3;
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "あ\nい\u{fffd}\u{fffd}");
+        assert!(IconvDecoder::new("no-such-encoding").is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Decoding with the converters of ICU.
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::io::{self, ErrorKind};
+use std::{fmt, ptr};
+
+use encoding::{CodecError, StringWriter};
+
+use crate::TextDecoder;
+
+/// Number of UTF-16 code units converted at once.
+const OUTPUT_SIZE: usize = 1024;
+
+#[repr(C)]
+struct UConverter {
+    _private: [u8; 0],
+}
+
+type UErrorCode = c_int;
+type UChar = u16;
+type UBool = i8;
+type UConverterToUCallback = unsafe extern "C" fn(*const c_void, *mut c_void, *const c_char, i32, c_int, *mut UErrorCode);
+
+const U_ZERO_ERROR: UErrorCode = 0;
+const U_BUFFER_OVERFLOW_ERROR: UErrorCode = 15;
+
+extern "C" {
+    #[link_name = concat!("ucnv_open", env!("TEXTSTREAM_ICU_SUFFIX"))]
+    fn ucnv_open(name: *const c_char, err: *mut UErrorCode) -> *mut UConverter;
+    #[link_name = concat!("ucnv_close", env!("TEXTSTREAM_ICU_SUFFIX"))]
+    fn ucnv_close(cnv: *mut UConverter);
+    #[link_name = concat!("ucnv_getName", env!("TEXTSTREAM_ICU_SUFFIX"))]
+    fn ucnv_get_name(cnv: *const UConverter, err: *mut UErrorCode) -> *const c_char;
+    #[link_name = concat!("ucnv_setToUCallBack", env!("TEXTSTREAM_ICU_SUFFIX"))]
+    fn ucnv_set_to_u_callback(cnv: *mut UConverter, action: UConverterToUCallback, context: *const c_void,
+        old_action: *mut Option<UConverterToUCallback>, old_context: *mut *const c_void, err: *mut UErrorCode);
+    #[link_name = concat!("UCNV_TO_U_CALLBACK_STOP", env!("TEXTSTREAM_ICU_SUFFIX"))]
+    fn ucnv_to_u_callback_stop(context: *const c_void, args: *mut c_void, code_units: *const c_char, length: i32,
+        reason: c_int, err: *mut UErrorCode);
+    #[link_name = concat!("ucnv_toUnicode", env!("TEXTSTREAM_ICU_SUFFIX"))]
+    fn ucnv_to_unicode(cnv: *mut UConverter, target: *mut *mut UChar, target_limit: *const UChar,
+        source: *mut *const c_char, source_limit: *const c_char, offsets: *mut i32, flush: UBool, err: *mut UErrorCode);
+    #[link_name = concat!("ucnv_toUCountPending", env!("TEXTSTREAM_ICU_SUFFIX"))]
+    fn ucnv_to_u_count_pending(cnv: *const UConverter, err: *mut UErrorCode) -> i32;
+    #[link_name = concat!("ucnv_getInvalidChars", env!("TEXTSTREAM_ICU_SUFFIX"))]
+    fn ucnv_get_invalid_chars(cnv: *const UConverter, chars: *mut c_char, len: *mut i8, err: *mut UErrorCode);
+    #[link_name = concat!("ucnv_resetToUnicode", env!("TEXTSTREAM_ICU_SUFFIX"))]
+    fn ucnv_reset_to_unicode(cnv: *mut UConverter);
+}
+
+/// A decoder with a converter of ICU, for its conversion tables, which cover many vendor
+/// variants of the encodings. The names of the converters are those of `ucnv_open`, such as
+/// `ibm-943_P15A-2003` or `ibm-37`, or aliases such as `Shift_JIS`.
+/// Invalid sequences are reported as errors, to be handled by the trap of the `TextReader`,
+/// instead of being substituted by ICU.
+///
+/// The feature `icu` links to the ICU of the system, whose version is found by `pkg-config`,
+/// or given by the environment variable `TEXTSTREAM_ICU_VERSION`.
+///
+/// # Examples
+/// ```
+/// use encoding::all::WINDOWS_31J;
+/// use encoding::DecoderTrap;
+/// use textstream::{IcuDecoder, NewlinePolicy, TextReaderBuilder};
+/// # fn foo() -> textstream::Result<()> {
+/// let decoder = IcuDecoder::new("ibm-943_P15A-2003")?;
+/// let reader = TextReaderBuilder::new(WINDOWS_31J)
+///     .trap(DecoderTrap::Replace)
+///     .newline_policy(NewlinePolicy::Universal)
+///     .build_with_decoder(&b"\x82\xa0\r\n\x82\xa2"[..], decoder);
+/// let lines = reader.lines().collect::<textstream::Result<Vec<_>>>()?;
+/// assert_eq!(lines, ["あ", "い"]);
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub struct IcuDecoder {
+    cnv: *mut UConverter,
+    incomplete: bool,
+}
+
+// SAFETY: a converter may be used from any thread, by one thread at a time, which `&mut self`
+// ensures.
+unsafe impl Send for IcuDecoder {}
+
+impl IcuDecoder {
+    /// Creates a decoder with the converter `name`.
+    /// Fails with `InvalidInput` if ICU doesn't have the converter.
+    pub fn new(name: &str) -> io::Result<IcuDecoder> {
+        let invalid = || io::Error::new(ErrorKind::InvalidInput, format!("unknown ICU converter {}", name));
+        let name = CString::new(name).map_err(|_| invalid())?;
+        let mut err = U_ZERO_ERROR;
+        // SAFETY: `name` is a NUL terminated string.
+        let cnv = unsafe { ucnv_open(name.as_ptr(), &mut err) };
+        if cnv.is_null() || err > U_ZERO_ERROR {
+            return Err(invalid());
+        }
+        let decoder = IcuDecoder { cnv, incomplete: false };
+        // SAFETY: the converter is open, and the callback is the one of ICU.
+        unsafe {
+            ucnv_set_to_u_callback(cnv, ucnv_to_u_callback_stop, ptr::null(), ptr::null_mut(), ptr::null_mut(), &mut err);
+        }
+        if err > U_ZERO_ERROR {
+            return Err(io::Error::new(ErrorKind::Other, format!("ICU error {}", err)));
+        }
+        Ok(decoder)
+    }
+
+    /// Gets the canonical name of the converter.
+    pub fn name(&self) -> &str {
+        let mut err = U_ZERO_ERROR;
+        // SAFETY: the name is a static NUL terminated string of ICU.
+        let name = unsafe { CStr::from_ptr(ucnv_get_name(self.cnv, &mut err)) };
+        name.to_str().unwrap_or_default()
+    }
+}
+
+impl TextDecoder for IcuDecoder {
+    fn feed(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+        self.incomplete = false;
+        let mut units = Vec::with_capacity(input.len());
+        let mut buf = [0 as UChar; OUTPUT_SIZE];
+        let mut source = input.as_ptr() as *const c_char;
+        // SAFETY: one past the end of `input`.
+        let source_limit = unsafe { source.add(input.len()) };
+        let mut err = loop {
+            let mut err = U_ZERO_ERROR;
+            let mut target = buf.as_mut_ptr();
+            // SAFETY: the pointers are those of `input` and `buf`, which ICU advances.
+            unsafe {
+                ucnv_to_unicode(self.cnv, &mut target, buf.as_ptr().add(buf.len()), &mut source, source_limit,
+                    ptr::null_mut(), 0, &mut err);
+            }
+            let written = (target as usize - buf.as_ptr() as usize) / 2;
+            units.extend_from_slice(&buf[..written]);
+            if err != U_BUFFER_OVERFLOW_ERROR {
+                break err;
+            }
+        };
+        let consumed = source as usize - input.as_ptr() as usize;
+        // Converted after the loop, as a surrogate pair may be split between two buffers.
+        output.write_str(&String::from_utf16_lossy(&units));
+        if err > U_ZERO_ERROR {
+            let mut invalid = [0 as c_char; 32];
+            let mut len = invalid.len() as i8;
+            err = U_ZERO_ERROR;
+            // SAFETY: `len` is the size of `invalid`.
+            unsafe { ucnv_get_invalid_chars(self.cnv, invalid.as_mut_ptr(), &mut len, &mut err) };
+            let len = if err > U_ZERO_ERROR { 0 } else { len as usize };
+            let processed = consumed.saturating_sub(len);
+            return (processed, Some(CodecError { upto: consumed as isize, cause: "invalid sequence".into() }));
+        }
+        let mut err = U_ZERO_ERROR;
+        // SAFETY: the converter is open.
+        let pending = unsafe { ucnv_to_u_count_pending(self.cnv, &mut err) };
+        let pending = if err > U_ZERO_ERROR { 0 } else { pending.max(0) as usize };
+        self.incomplete = pending > 0;
+        (consumed - pending.min(consumed), None)
+    }
+
+    fn finish(&mut self, _output: &mut dyn StringWriter) -> Option<CodecError> {
+        // SAFETY: the converter is open.
+        unsafe { ucnv_reset_to_unicode(self.cnv) };
+        if !self.incomplete {
+            return None;
+        }
+        self.incomplete = false;
+        Some(CodecError { upto: 0, cause: "incomplete sequence".into() })
+    }
+}
+
+impl Drop for IcuDecoder {
+    fn drop(&mut self) {
+        // SAFETY: the converter is opened by `new`, and closed only here.
+        unsafe { ucnv_close(self.cnv) };
+    }
+}
+
+impl fmt::Debug for IcuDecoder {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("IcuDecoder").field("name", &self.name()).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::DecoderTrap;
+    use encoding::all::{ISO_8859_1, WINDOWS_31J};
+    use crate::{TextReader, TextReaderBuilder};
+
+    #[test]
+    fn icu_decoder() {
+        let decoder = IcuDecoder::new("Shift_JIS").unwrap();
+        let bytes = b"\x82\xa0\n\x82\xa2\xff\x82";
+        let mut reader = TextReaderBuilder::new(WINDOWS_31J).trap(DecoderTrap::Replace).chunk_size(3)
+            .build_with_decoder(&bytes[..], decoder);
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "あ\nい\u{fffd}\u{fffd}");
+        assert!(IcuDecoder::new("no-such-converter").is_err());
+    }
+    #[test]
+    fn icu_ebcdic() {
+        let decoder = IcuDecoder::new("ibm-37").unwrap();
+        assert_eq!(decoder.name(), "ibm-37_P100-1995");
+        let mut reader = TextReader::with_decoder(&b"\xc8\x85\x93\x93\x96"[..], ISO_8859_1, decoder, DecoderTrap::Strict);
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "Hello");
+    }
+}
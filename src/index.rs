@@ -0,0 +1,168 @@
+//! Random access to lines.
+
+use std::io::{self, BufRead, ErrorKind, Seek, SeekFrom};
+
+use memchr::memchr2_iter;
+
+use crate::{EncodingInfo, Error, NewlinePolicy, Result, TextReader};
+
+/// The byte offsets of the lines of a stream, built by `TextReader::build_line_index`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LineIndex {
+    offsets: Vec<u64>,
+}
+
+impl LineIndex {
+    /// Returns the number of lines.
+    pub fn len(&self) -> usize { self.offsets.len() }
+
+    /// Returns whether the stream has no lines.
+    pub fn is_empty(&self) -> bool { self.offsets.is_empty() }
+
+    /// Returns the byte offset of the start of line `n`, counted from 0.
+    pub fn offset(&self, n: usize) -> Option<u64> { self.offsets.get(n).cloned() }
+
+    /// Returns the line, counted from 0, which contains the byte at `offset`.
+    pub fn line_of(&self, offset: u64) -> Option<usize> {
+        match self.offsets.binary_search(&offset) {
+            Ok(n) => Some(n),
+            Err(0) => None,
+            Err(n) => Some(n - 1),
+        }
+    }
+}
+
+impl<R: BufRead + Seek> TextReader<R> {
+    /// Scans the underlying reader from the start, and returns the byte offsets of its lines.
+    /// The reader is left at the start. Lines can then be read from any of them by `seek_line`,
+    /// without decoding the lines before it.
+    ///
+    /// Lines are split at `\n` bytes, and at `\r` bytes unless the newline policy is
+    /// `NewlinePolicy::Lf`. The other Unicode line terminators are not recognized.
+    ///
+    /// Fails with `InvalidInput` unless the `EncodingInfo` of the encoding is
+    /// `ascii_compatible` and not `stateful`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Cursor;
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let data = Cursor::new(b"a\n\x82\xa0\nc\n");
+    /// let mut reader = TextReader::from_bufread(data, WINDOWS_31J, DecoderTrap::Strict);
+    /// let index = reader.build_line_index()?;
+    /// assert_eq!(index.len(), 3);
+    /// reader.seek_line(&index, 1)?;
+    /// let mut s = String::new();
+    /// reader.read_line(&mut s)?;
+    /// assert_eq!(s, "あ\n");
+    /// assert_eq!(reader.position().line, 3);
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn build_line_index(&mut self) -> Result<LineIndex> {
+        EncodingInfo::require_line_splittable(self.encoding, "index lines")?;
+        self.seek(SeekFrom::Start(0))?;
+        let universal = self.newline != NewlinePolicy::Lf;
+        let mut offsets = vec![0];
+        let mut pos = 0;
+        let mut pending_cr = false;
+        loop {
+            let buf = match self.reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e.into()),
+            };
+            if buf.is_empty() {
+                break;
+            }
+            // A `\r` at the end of the previous buffer.
+            if pending_cr && buf[0] != b'\n' {
+                offsets.push(pos);
+            }
+            pending_cr = false;
+            for i in memchr2_iter(b'\n', b'\r', buf) {
+                let next = pos + i as u64 + 1;
+                match (buf[i], buf.get(i + 1)) {
+                    (b'\n', _) => offsets.push(next),
+                    (_, _) if !universal => {},
+                    (_, Some(&b'\n')) => {},
+                    (_, Some(_)) => offsets.push(next),
+                    (_, None) => pending_cr = true,
+                }
+            }
+            let len = buf.len();
+            self.reader.consume(len);
+            pos += len as u64;
+        }
+        if pending_cr {
+            offsets.push(pos);
+        }
+        // The end of the stream starts no line.
+        if offsets.last() == Some(&pos) {
+            offsets.pop();
+        }
+        self.seek(SeekFrom::Start(0))?;
+        Ok(LineIndex { offsets })
+    }
+
+    /// Seeks to the start of line `n` of `index`, counted from 0, built for this reader.
+    /// The line of the position is set to the line.
+    /// Returns the byte offset, or fails with `InvalidInput` if there is no line `n`.
+    pub fn seek_line(&mut self, index: &LineIndex, n: usize) -> Result<u64> {
+        let offset = index.offset(n).ok_or_else(|| {
+            Error::IOError(io::Error::new(ErrorKind::InvalidInput,
+                format!("line {} out of {} lines", n, index.len())))
+        })?;
+        self.seek(SeekFrom::Start(offset))?;
+        self.line = n as u64;
+        self.decoded_lines.line = n as u64;
+        Ok(offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufReader, Cursor};
+    use encoding::DecoderTrap;
+    use encoding::all::{UTF_16LE, WINDOWS_31J};
+
+    fn offsets(bytes: &[u8], policy: NewlinePolicy, capacity: usize) -> Vec<u64> {
+        let bufreader = BufReader::with_capacity(capacity, Cursor::new(bytes));
+        let mut reader = TextReader::from_bufread(bufreader, WINDOWS_31J, DecoderTrap::Strict);
+        reader.set_newline_policy(policy);
+        reader.build_line_index().unwrap().offsets
+    }
+    #[test]
+    fn build_line_index_test() {
+        let data = b"a\r\n\x82\xa0\rb\n\nc";
+        assert_eq!(offsets(data, NewlinePolicy::Lf, 8192), [0, 3, 8, 9]);
+        for capacity in 1..5 {
+            assert_eq!(offsets(data, NewlinePolicy::Universal, capacity), [0, 3, 6, 8, 9]);
+        }
+        assert_eq!(offsets(b"a\r", NewlinePolicy::Universal, 1), [0]);
+        assert_eq!(offsets(b"a\r", NewlinePolicy::Lf, 1), [0]);
+        assert!(offsets(b"", NewlinePolicy::Lf, 1).is_empty());
+        let mut reader = TextReader::from_bufread(Cursor::new(b"a\x00"), UTF_16LE, DecoderTrap::Strict);
+        assert!(reader.build_line_index().is_err());
+    }
+    #[test]
+    fn seek_line_test() {
+        let data = b"a\r\n\x82\xa0\rb\n\nc";
+        let mut reader = TextReader::from_bufread(Cursor::new(data), WINDOWS_31J, DecoderTrap::Strict);
+        reader.set_newline_policy(NewlinePolicy::Universal);
+        let index = reader.build_line_index().unwrap();
+        assert_eq!(index.line_of(7), Some(2));
+        assert_eq!(index.line_of(9), Some(4));
+        assert_eq!(reader.seek_line(&index, 2).unwrap(), 6);
+        let mut s = String::new();
+        reader.read_line(&mut s).unwrap();
+        assert_eq!(s, "b\n");
+        assert_eq!(reader.position().line, 4);
+        assert!(reader.seek_line(&index, 5).is_err());
+    }
+}
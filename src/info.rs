@@ -0,0 +1,112 @@
+//! Capabilities of encodings.
+
+use std::io::{self, ErrorKind};
+
+use encoding::Encoding;
+
+use crate::label::encoding_from_whatwg_label;
+use crate::{Error, Result};
+
+/// Capabilities of an encoding, for adapting strategies to the encoding in generic tools.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodingInfo {
+    /// Name of the encoding, same as `Encoding::name`.
+    pub name: &'static str,
+    /// Whether ASCII characters are encoded as the same single bytes, and the bytes of
+    /// control characters (including `\n` and `\r`) never appear as a part of other characters.
+    /// If true, newlines can be searched for in the raw bytes.
+    pub ascii_compatible: bool,
+    /// Whether the meaning of bytes depends on preceding escape or shift sequences.
+    pub stateful: bool,
+    /// Whether every character is encoded in the same number of bytes.
+    pub fixed_width: bool,
+    /// Maximum number of bytes per character, including escape or shift sequences.
+    pub max_bytes_per_char: usize,
+    /// Byte order mark, if the encoding has one.
+    pub bom: Option<&'static [u8]>,
+}
+
+impl EncodingInfo {
+    /// Returns the capabilities of the encoding with given label, if the label is known to
+    /// `textstream::label`.
+    ///
+    /// # Examples
+    /// ```
+    /// use textstream::EncodingInfo;
+    /// let info = EncodingInfo::for_label("utf-16le").unwrap();
+    /// assert!(!info.ascii_compatible);
+    /// assert_eq!(info.bom, Some(&b"\xff\xfe"[..]));
+    /// ```
+    pub fn for_label(label: &str) -> Option<EncodingInfo> {
+        encoding_from_whatwg_label(label).and_then(|e| EncodingInfo::for_encoding(e))
+    }
+
+    /// Returns the capabilities of `encoding`, if it is one of the encodings of the `encoding`
+    /// crate.
+    pub fn for_encoding(encoding: &dyn Encoding) -> Option<EncodingInfo> {
+        let name = encoding.name();
+        let (ascii_compatible, stateful, max_bytes_per_char, bom): (bool, bool, usize, Option<&'static [u8]>) = match name {
+            "utf-8" => (true, false, 4, Some(b"\xef\xbb\xbf")),
+            "utf-16le" => (false, false, 4, Some(b"\xff\xfe")),
+            "utf-16be" => (false, false, 4, Some(b"\xfe\xff")),
+            "windows-31j" | "gbk" | "big5-2003" | "windows-949" => (true, false, 2, None),
+            "euc-jp" => (true, false, 3, None),
+            "gb18030" => (true, false, 4, None),
+            "iso-2022-jp" => (false, true, 5, None),
+            "hz" => (false, true, 4, None),
+            "ascii" | "ibm866" | "mac-roman" | "mac-cyrillic" | "pua-mapped-binary" => (true, false, 1, None),
+            _ if name.starts_with("iso-8859-") || name.starts_with("windows-")
+                || name.starts_with("koi8-") => (true, false, 1, None),
+            _ => return None,
+        };
+        Some(EncodingInfo {
+            name,
+            ascii_compatible,
+            stateful,
+            fixed_width: max_bytes_per_char == 1,
+            max_bytes_per_char,
+            bom,
+        })
+    }
+
+    /// For internal use. Fails with `InvalidInput` unless `encoding` is ASCII compatible and
+    /// not stateful, so that every `\n` byte ends a line and a decoder can start after it.
+    /// `what` names the operation in the error message.
+    pub(crate) fn require_line_splittable(encoding: &dyn Encoding, what: &str) -> Result<()> {
+        match EncodingInfo::for_encoding(encoding) {
+            Some(info) if info.ascii_compatible && !info.stateful => Ok(()),
+            _ => Err(Error::IOError(io::Error::new(ErrorKind::InvalidInput,
+                format!("cannot {} in {}", what, encoding.name())))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::all;
+
+    #[test]
+    fn for_label_test() {
+        let info = EncodingInfo::for_label("utf-8").unwrap();
+        assert_eq!(info.name, "utf-8");
+        assert!(info.ascii_compatible);
+        assert!(!info.fixed_width);
+        assert!(EncodingInfo::for_label("bazinga").is_none());
+    }
+    #[test]
+    fn for_encoding_test() {
+        for encoding in all::encodings() {
+            match encoding.name() {
+                "error" | "encoder-only-utf-8" => assert!(EncodingInfo::for_encoding(*encoding).is_none()),
+                _ => assert!(EncodingInfo::for_encoding(*encoding).is_some(), "{}", encoding.name()),
+            }
+        }
+        let info = EncodingInfo::for_encoding(all::ISO_2022_JP).unwrap();
+        assert!(info.stateful);
+        assert!(!info.ascii_compatible);
+        let info = EncodingInfo::for_encoding(all::WINDOWS_1252).unwrap();
+        assert!(info.fixed_width);
+        assert_eq!(info.max_bytes_per_char, 1);
+    }
+}
@@ -0,0 +1,324 @@
+//! Encoding lookup by label.
+//!
+//! Unlike `encoding::label`, this module only knows the encoding families enabled by cargo
+//! features, so that the tables of the other families are not linked into the binary.
+//! UTF-8 and UTF-16 are always available.
+
+use std::{error, fmt, result};
+use std::io::{BufReader, Read};
+
+use encoding::all;
+use encoding::{DecoderTrap, EncodingRef};
+
+use crate::{Error, Result, TextReader};
+
+/// Returns an encoding from given label, defined in the WHATWG Encoding standard, if any.
+/// Returns `None` for labels of encoding families not enabled by cargo features.
+///
+/// The generic "utf-16" label is UTF-16LE, as the standard defines. The readers created with a
+/// label by `TextReader` resolve its byte order from the stream instead.
+pub fn encoding_from_whatwg_label(label: &str) -> Option<EncodingRef> {
+    let label = trim_label(label).to_ascii_lowercase();
+    match label.as_str() {
+        "unicode-1-1-utf-8" | "utf-8" | "utf8" =>
+            Some(all::UTF_8 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "866" | "cp866" | "csibm866" | "ibm866" =>
+            Some(all::IBM866 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "csisolatin2" | "iso-8859-2" | "iso-ir-101" | "iso8859-2" | "iso88592" | "iso_8859-2" |
+        "iso_8859-2:1987" | "l2" | "latin2" =>
+            Some(all::ISO_8859_2 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "csisolatin3" | "iso-8859-3" | "iso-ir-109" | "iso8859-3" | "iso88593" | "iso_8859-3" |
+        "iso_8859-3:1988" | "l3" | "latin3" =>
+            Some(all::ISO_8859_3 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "csisolatin4" | "iso-8859-4" | "iso-ir-110" | "iso8859-4" | "iso88594" | "iso_8859-4" |
+        "iso_8859-4:1988" | "l4" | "latin4" =>
+            Some(all::ISO_8859_4 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "csisolatincyrillic" | "cyrillic" | "iso-8859-5" | "iso-ir-144" | "iso8859-5" | "iso88595" |
+        "iso_8859-5" | "iso_8859-5:1988" =>
+            Some(all::ISO_8859_5 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "arabic" | "asmo-708" | "csiso88596e" | "csiso88596i" | "csisolatinarabic" | "ecma-114" |
+        "iso-8859-6" | "iso-8859-6-e" | "iso-8859-6-i" | "iso-ir-127" | "iso8859-6" | "iso88596" |
+        "iso_8859-6" | "iso_8859-6:1987" =>
+            Some(all::ISO_8859_6 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "csisolatingreek" | "ecma-118" | "elot_928" | "greek" | "greek8" | "iso-8859-7" |
+        "iso-ir-126" | "iso8859-7" | "iso88597" | "iso_8859-7" | "iso_8859-7:1987" |
+        "sun_eu_greek" =>
+            Some(all::ISO_8859_7 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "csiso88598e" | "csisolatinhebrew" | "hebrew" | "iso-8859-8" | "iso-8859-8-e" |
+        "iso-ir-138" | "iso8859-8" | "iso88598" | "iso_8859-8" | "iso_8859-8:1988" | "visual" =>
+            Some(all::ISO_8859_8 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "csiso88598i" | "iso-8859-8-i" | "logical" =>
+            Some(all::whatwg::ISO_8859_8_I as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "csisolatin6" | "iso-8859-10" | "iso-ir-157" | "iso8859-10" | "iso885910" | "l6" |
+        "latin6" =>
+            Some(all::ISO_8859_10 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-13" | "iso8859-13" | "iso885913" =>
+            Some(all::ISO_8859_13 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-14" | "iso8859-14" | "iso885914" =>
+            Some(all::ISO_8859_14 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "csisolatin9" | "iso-8859-15" | "iso8859-15" | "iso885915" | "iso_8859-15" | "l9" =>
+            Some(all::ISO_8859_15 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "iso-8859-16" =>
+            Some(all::ISO_8859_16 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "cskoi8r" | "koi" | "koi8" | "koi8-r" | "koi8_r" =>
+            Some(all::KOI8_R as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "koi8-u" =>
+            Some(all::KOI8_U as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "csmacintosh" | "mac" | "macintosh" | "x-mac-roman" =>
+            Some(all::MAC_ROMAN as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "dos-874" | "iso-8859-11" | "iso8859-11" | "iso885911" | "tis-620" | "windows-874" =>
+            Some(all::WINDOWS_874 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "cp1250" | "windows-1250" | "x-cp1250" =>
+            Some(all::WINDOWS_1250 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "cp1251" | "windows-1251" | "x-cp1251" =>
+            Some(all::WINDOWS_1251 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "ansi_x3.4-1968" | "ascii" | "cp1252" | "cp819" | "csisolatin1" | "ibm819" | "iso-8859-1" |
+        "iso-ir-100" | "iso8859-1" | "iso88591" | "iso_8859-1" | "iso_8859-1:1987" | "l1" |
+        "latin1" | "us-ascii" | "windows-1252" | "x-cp1252" =>
+            Some(all::WINDOWS_1252 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "cp1253" | "windows-1253" | "x-cp1253" =>
+            Some(all::WINDOWS_1253 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "cp1254" | "csisolatin5" | "iso-8859-9" | "iso-ir-148" | "iso8859-9" | "iso88599" |
+        "iso_8859-9" | "iso_8859-9:1989" | "l5" | "latin5" | "windows-1254" | "x-cp1254" =>
+            Some(all::WINDOWS_1254 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "cp1255" | "windows-1255" | "x-cp1255" =>
+            Some(all::WINDOWS_1255 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "cp1256" | "windows-1256" | "x-cp1256" =>
+            Some(all::WINDOWS_1256 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "cp1257" | "windows-1257" | "x-cp1257" =>
+            Some(all::WINDOWS_1257 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "cp1258" | "windows-1258" | "x-cp1258" =>
+            Some(all::WINDOWS_1258 as EncodingRef),
+        #[cfg(feature = "singlebyte")]
+        "x-mac-cyrillic" | "x-mac-ukrainian" =>
+            Some(all::MAC_CYRILLIC as EncodingRef),
+        #[cfg(feature = "simpchinese")]
+        "chinese" | "csgb2312" | "csiso58gb231280" | "gb2312" | "gb_2312" | "gb_2312-80" | "gbk" |
+        "iso-ir-58" | "x-gbk" =>
+            Some(all::GBK as EncodingRef),
+        #[cfg(feature = "simpchinese")]
+        "gb18030" =>
+            Some(all::GB18030 as EncodingRef),
+        #[cfg(feature = "tradchinese")]
+        "big5" | "big5-hkscs" | "cn-big5" | "csbig5" | "x-x-big5" =>
+            Some(all::BIG5_2003 as EncodingRef),
+        #[cfg(feature = "japanese")]
+        "cseucpkdfmtjapanese" | "euc-jp" | "x-euc-jp" =>
+            Some(all::EUC_JP as EncodingRef),
+        #[cfg(feature = "japanese")]
+        "csiso2022jp" | "iso-2022-jp" =>
+            Some(all::ISO_2022_JP as EncodingRef),
+        #[cfg(feature = "japanese")]
+        "csshiftjis" | "ms_kanji" | "shift-jis" | "shift_jis" | "sjis" | "windows-31j" | "x-sjis" =>
+            Some(all::WINDOWS_31J as EncodingRef),
+        #[cfg(feature = "korean")]
+        "cseuckr" | "csksc56011987" | "euc-kr" | "iso-ir-149" | "korean" | "ks_c_5601-1987" |
+        "ks_c_5601-1989" | "ksc5601" | "ksc_5601" | "windows-949" =>
+            Some(all::WINDOWS_949 as EncodingRef),
+        "csiso2022kr" | "hz-gb-2312" | "iso-2022-kr" | "iso-2022-cn" | "iso-2022-cn-ext" =>
+            Some(all::whatwg::REPLACEMENT as EncodingRef),
+        "utf-16be" =>
+            Some(all::UTF_16BE as EncodingRef),
+        "utf-16" | "utf-16le" =>
+            Some(all::UTF_16LE as EncodingRef),
+        "x-user-defined" =>
+            Some(all::whatwg::X_USER_DEFINED as EncodingRef),
+        _ => None
+    }
+}
+
+/// For internal use. Removes the whitespace around `label`, as the WHATWG Encoding standard does.
+fn trim_label(label: &str) -> &str {
+    label.trim_matches(&[' ', '\n', '\r', '\t', '\x0C'][..])
+}
+
+/// For internal use. Returns whether `label` is the generic "utf-16" label, whose byte order is
+/// resolved from the stream.
+pub(crate) fn is_generic_utf16(label: &str) -> bool {
+    trim_label(label).eq_ignore_ascii_case("utf-16")
+}
+
+/// Error returned when an encoding label is unknown to `textstream::label`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct UnknownEncodingError {
+    label: String,
+}
+
+impl UnknownEncodingError {
+    /// Returns the unknown label.
+    pub fn label(&self) -> &str { &self.label }
+}
+
+impl fmt::Display for UnknownEncodingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unknown encoding label: {:?}", self.label)
+    }
+}
+
+impl error::Error for UnknownEncodingError {}
+
+impl From<UnknownEncodingError> for Error {
+    fn from(err: UnknownEncodingError) -> Error {
+        Error::UnknownEncoding(err.label)
+    }
+}
+
+/// Returns the value of the `charset` parameter of a MIME type such as a `Content-Type` header,
+/// if any.
+fn mime_charset(mime: &str) -> Option<String> {
+    for param in mime.split(';').skip(1) {
+        let (name, value) = match param.split_once('=') {
+            Some(pair) => pair,
+            None => continue,
+        };
+        if !name.trim().eq_ignore_ascii_case("charset") {
+            continue;
+        }
+        let value = value.trim();
+        return match value.strip_prefix('"') {
+            Some(quoted) => {
+                let mut unquoted = String::new();
+                let mut chars = quoted.chars();
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => unquoted.extend(chars.next()),
+                        _ => unquoted.push(c),
+                    }
+                }
+                Some(unquoted)
+            },
+            None => Some(value.to_string()),
+        };
+    }
+    None
+}
+
+impl<R: Read> TextReader<BufReader<R>> {
+    /// Creates a new `TextReader` with the encoding of given label, defined in the WHATWG
+    /// Encoding standard.
+    ///
+    /// For the generic "utf-16" label, the byte order is resolved at the first read from the
+    /// byte order mark, which is kept as text unless stripped by `set_bom_policy`. Without one,
+    /// the byte order is guessed from the NUL bytes of ASCII characters, and is little endian,
+    /// as written by Windows tools, if it can't be.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// let reader = TextReader::with_label(&b""[..], "utf8", DecoderTrap::Strict).unwrap();
+    /// assert_eq!(reader.encoding_name(), "utf-8");
+    /// let err = TextReader::with_label(&b""[..], "bazinga", DecoderTrap::Strict).err().unwrap();
+    /// assert_eq!(err.label(), "bazinga");
+    /// ```
+    pub fn with_label(reader: R, label: &str, trap: DecoderTrap) -> result::Result<TextReader<BufReader<R>>, UnknownEncodingError> {
+        match encoding_from_whatwg_label(label) {
+            Some(encoding) => {
+                let mut textreader = TextReader::new(reader, encoding, trap);
+                textreader.utf16_unresolved = is_generic_utf16(label);
+                Ok(textreader)
+            },
+            None => Err(UnknownEncodingError { label: label.to_string() }),
+        }
+    }
+
+    /// Creates a new `TextReader` with the encoding of the `charset` parameter of a MIME type,
+    /// such as the `Content-Type` header of an HTTP response.
+    /// Returns `Error::UnknownEncoding` if the parameter is missing or its label is unknown.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::from_mime(&b"\xff\xfea\0"[..], "text/plain; charset=\"UTF-16LE\"", DecoderTrap::Strict)?;
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s)?;
+    /// assert_eq!(s, "\u{feff}a");
+    /// assert!(TextReader::from_mime(&b""[..], "text/plain", DecoderTrap::Strict).is_err());
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn from_mime(reader: R, mime: &str, trap: DecoderTrap) -> Result<TextReader<BufReader<R>>> {
+        let label = mime_charset(mime).unwrap_or_default();
+        Ok(TextReader::with_label(reader, &label, trap)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn utf_always_available() {
+        assert_eq!(encoding_from_whatwg_label("utf-8").unwrap().name(), "utf-8");
+        assert_eq!(encoding_from_whatwg_label(" UTF8\t").unwrap().name(), "utf-8");
+        assert_eq!(encoding_from_whatwg_label("utf-16le").unwrap().name(), "utf-16le");
+        assert!(encoding_from_whatwg_label("bazinga").is_none());
+    }
+    #[test]
+    fn mime_charset_test() {
+        assert_eq!(mime_charset("text/html; charset=Shift_JIS").unwrap(), "Shift_JIS");
+        assert_eq!(mime_charset("text/plain;format=flowed; CHARSET = \"utf-8\" ").unwrap(), "utf-8");
+        assert_eq!(mime_charset("text/plain; charset=\"a\\\"b\"; x=y").unwrap(), "a\"b");
+        assert!(mime_charset("text/plain").is_none());
+        assert!(mime_charset("text/plain; charsets=utf-8").is_none());
+    }
+    #[test]
+    fn from_mime_unknown() {
+        match TextReader::from_mime(&b""[..], "text/plain; charset=bazinga", DecoderTrap::Strict) {
+            Err(Error::UnknownEncoding(label)) => assert_eq!(label, "bazinga"),
+            _ => panic!("expected UnknownEncoding"),
+        }
+    }
+    #[cfg(feature = "japanese")]
+    #[test]
+    fn japanese_labels() {
+        assert_eq!(encoding_from_whatwg_label("sjis").unwrap().name(), "windows-31j");
+        assert_eq!(encoding_from_whatwg_label("EUC-JP").unwrap().name(), "euc-jp");
+    }
+    #[cfg(not(feature = "japanese"))]
+    #[test]
+    fn japanese_labels_disabled() {
+        assert!(encoding_from_whatwg_label("sjis").is_none());
+    }
+    #[cfg(feature = "all-encodings")]
+    #[test]
+    fn agrees_with_encoding_crate() {
+        for encoding in all::encodings() {
+            if let Some(name) = encoding.whatwg_name() {
+                assert_eq!(encoding_from_whatwg_label(name).map(|e| e.name()),
+                           ::encoding::label::encoding_from_whatwg_label(name).map(|e| e.name()));
+            }
+        }
+    }
+}
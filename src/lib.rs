@@ -2,24 +2,165 @@
 
 #![warn(missing_docs)]
 
-extern crate encoding;
-extern crate memchr;
-
-use std::{io, result};
+use std::{error, fmt, io, result};
 use std::borrow::Cow;
-use std::io::{BufReader, ErrorKind, Read};
+use std::fs::File;
+use std::mem;
+use std::ops::ControlFlow;
+use std::io::{BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom};
 use std::iter::Iterator;
+use std::path::Path;
+
+use encoding::{DecoderTrap, EncoderTrap, EncodingRef};
+use memchr::{memchr, memchr2, memchr_iter};
 
-use encoding::{DecoderTrap, Encoding, RawDecoder};
-use memchr::memchr;
+#[cfg(feature = "bench")]
+pub mod bench;
+mod bom;
+mod builder;
+#[cfg(any(feature = "gzip", feature = "zstd", feature = "xz"))]
+mod compress;
+mod convert;
+mod decoder;
+mod detect;
+mod dispatch;
+mod escape;
+mod filename;
+mod follow;
+pub mod html;
+#[cfg(all(feature = "iconv", unix))]
+mod iconv;
+#[cfg(feature = "icu")]
+mod icu;
+mod index;
+mod info;
+pub mod label;
+mod locale;
+mod lossy;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod pipeline;
+pub mod prelude;
+mod process;
+mod report;
+mod resync;
+mod rlines;
+mod section;
+mod shared;
+mod stream;
+mod timeout;
+mod transcode;
+mod trap;
+pub mod windows;
+mod writer;
+
+pub use bom::BomPolicy;
+pub use builder::TextReaderBuilder;
+#[cfg(any(feature = "gzip", feature = "zstd", feature = "xz"))]
+pub use compress::{Compression, Decompressor};
+pub use convert::{decode_bytes, decode_file, encode_str, encode_to_file};
+pub use decoder::TextDecoder;
+pub use detect::{detect, detect_file};
+#[cfg(feature = "japanese")]
+pub use detect::detect_japanese;
+pub use dispatch::DispatchLines;
+pub use escape::{escape_byte, unescape_char, SURROGATE_ESCAPE};
+pub use filename::{decode_file_name, encode_file_name};
+#[cfg(unix)]
+pub use filename::{decode_os_str, encode_os_string};
+pub use follow::Follow;
+#[cfg(all(feature = "iconv", unix))]
+pub use iconv::IconvDecoder;
+#[cfg(feature = "icu")]
+pub use icu::IcuDecoder;
+pub use index::LineIndex;
+pub use info::EncodingInfo;
+pub use label::UnknownEncodingError;
+pub use locale::{encoding_from_locale, native_encoding};
+pub use lossy::LinesLossy;
+#[cfg(feature = "rayon")]
+pub use parallel::ParLines;
+pub use pipeline::PipelinedLines;
+pub use process::{decode_output, CommandExt, DecodedOutput, TextChild};
+pub use report::DecodeReport;
+pub use resync::Resync;
+pub use rlines::RLines;
+pub use section::{Schema, Section, SectionLength, SectionReader, SectionText};
+pub use shared::SharedTextReader;
+pub use stream::TextStream;
+pub use timeout::TimeoutReader;
+use decoder::LineReset;
+use trap::{LineCounter, Trap, TrapHandler};
+pub use transcode::{transcode, transcode_file, transcode_with_progress, TranscodeOptions, TranscodeStats};
+pub use trap::{InvalidSequence, Replacement};
+pub use writer::{BufferMode, EncodeWriter, LineTerminator, TextWriter, HEX_NCR_ESCAPE};
 
 /// Error for reader.
 #[derive(Debug)]
+#[non_exhaustive]
 pub enum Error {
     /// IO Error.
     IOError(io::Error),
     /// Encoding error.
-    CodecError(Cow<'static, str>)
+    CodecError(Cow<'static, str>),
+    /// Decoding error, with the position of the invalid bytes.
+    DecodeError {
+        /// Description of the error.
+        cause: Cow<'static, str>,
+        /// Offset of the invalid bytes, and the line and the column after the text decoded
+        /// before them.
+        position: Position,
+    },
+    /// The stream ends in the middle of a multibyte sequence, which starts at the position.
+    /// Only with `DecoderTrap::Strict`; the other traps handle the sequence as an invalid one.
+    IncompleteSequence(Position),
+    /// A line is longer than the maximum line length.
+    LineTooLong,
+    /// The stream looks like binary data rather than text.
+    /// See `TextReader::set_binary_detection`.
+    BinaryData,
+    /// The encoding label is missing, or unknown to `textstream::label`.
+    UnknownEncoding(String),
+    /// The stream doesn't start with a byte order mark, required by `BomPolicy::Require`.
+    MissingBom,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::IOError(e) => e.fmt(f),
+            Error::CodecError(cause) => f.write_str(cause),
+            Error::DecodeError { cause, position } => write!(f, "{} at {}", cause, position),
+            Error::IncompleteSequence(position) => write!(f, "incomplete sequence at {}", position),
+            Error::LineTooLong => f.write_str("line too long"),
+            Error::BinaryData => f.write_str("binary data"),
+            Error::UnknownEncoding(label) => write!(f, "unknown encoding: {}", label),
+            Error::MissingBom => f.write_str("missing byte order mark"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::IOError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Codec errors are converted to errors of kind `InvalidData`, and unknown encodings to
+/// `InvalidInput`.
+impl From<Error> for io::Error {
+    fn from(err: Error) -> io::Error {
+        match err {
+            Error::IOError(e) => e,
+            Error::UnknownEncoding(_) => io::Error::new(ErrorKind::InvalidInput, err.to_string()),
+            _ => io::Error::new(ErrorKind::InvalidData, err.to_string()),
+        }
+    }
 }
 
 impl From<io::Error> for Error {
@@ -43,26 +184,124 @@ impl From<encoding::CodecError> for Error {
 /// Result for reader.
 pub type Result<T> = result::Result<T, Error>;
 
+/// Line terminators recognized by `read_line` and `lines`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum NewlinePolicy {
+    /// `\n` (including `\r\n`).
+    #[default]
+    Lf,
+    /// `\r\n`, `\n` and `\r`, like Python's universal newlines.
+    Universal,
+    /// `\r\n`, `\n`, `\r`, NEL (U+0085), LINE SEPARATOR (U+2028) and
+    /// PARAGRAPH SEPARATOR (U+2029).
+    Unicode,
+}
+
+/// Position in the stream, after the text returned so far. Please see `TextReader::position`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    /// Offset in bytes from the start of the underlying reader.
+    pub byte: u64,
+    /// Line number, starting at 1.
+    pub line: u64,
+    /// Column in characters, starting at 1.
+    pub column: u64,
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {} (byte {})", self.line, self.column, self.byte)
+    }
+}
+
+/// Returns whether `c` is a line terminator only under `NewlinePolicy::Unicode`.
+fn is_unicode_newline(c: char) -> bool {
+    c == '\u{85}' || c == '\u{2028}' || c == '\u{2029}'
+}
+
 const CHUNK_SIZE: usize = 2048;
-const ERR_INCOMPLETE_SEQ: &'static str = "incomplete sequence";
+/// Minimum number of bytes decoded at once.
+const MIN_CHUNK_SIZE: usize = 8;
+/// Maximum length of an incomplete sequence held by a decoder. A decoder holding more bytes is
+/// taken for one making no progress.
+const MAX_SEQUENCE_LEN: usize = 16;
 
-/// The `TextReader` struct is wrapper for `BufReader` to decode text codecs.
-pub struct TextReader<R: Read> {
-    bufreader: BufReader<R>,
-    decoder: Box<RawDecoder>,
+/// For internal use. Decodes `input` into `s`, of which the decoder holds `input[..queued]`, an
+/// incomplete sequence fed before. The decoder is not finished, so that the state of stateful
+/// encodings is kept between chunks.
+/// Returns the number of bytes processed, whether they are all of `input`, rather than the
+/// decoder holding an incomplete sequence at the end, or the cause of an error at the end of the
+/// processed bytes, and the offset of an invalid sequence handled by the trap.
+///
+/// A decoder making no progress fails, rather than reading looping forever: one reporting an
+/// empty invalid sequence, which the trap would handle again and again, or one holding more
+/// bytes than any sequence, which would be read to the end without decoding.
+fn decode(decoder: &mut dyn TextDecoder, trap: Trap, input: &[u8], queued: usize, s: &mut String)
+    -> (usize, result::Result<bool, Cow<'static, str>>, Option<usize>)
+{
+    let decoded = s.len();
+    s.reserve(input.len() - queued);
+    let (offset, err) = decoder.feed(&input[queued..], s);
+    // A sequence completed by the input starts with the bytes held.
+    let offset = if offset == 0 { 0 } else { queued + offset };
+    if let Some(e) = err {
+        // The invalid sequence may end in the bytes held, which are fed again after it.
+        let upto = queued as isize + e.upto;
+        assert!(upto >= offset as isize);
+        let upto = upto as usize;
+        if upto == offset {
+            return (offset, Err("the decoder makes no progress on an empty invalid sequence".into()), None);
+        }
+        if !trap.apply(decoder, input, offset, upto, s, decoded) {
+            return (offset, Err(e.cause), None);
+        }
+        // The decoder holds nothing after an error. The rest is decoded by the next call.
+        return (upto, Ok(true), Some(offset));
+    }
+    if input.len() - offset > MAX_SEQUENCE_LEN {
+        return (offset, Err("the decoder makes no progress, holding too many bytes".into()), None);
+    }
+    (offset, Ok(offset == input.len()), None)
+}
+
+/// The `TextReader` struct is wrapper for `BufRead` to decode text codecs.
+/// The text is decoded directly from the buffer of the underlying reader.
+pub struct TextReader<R: BufRead> {
+    reader: R,
+    decoder: Box<dyn TextDecoder>,
+    encoding: EncodingRef,
     trap: DecoderTrap,
     textbuf: String,
+    textpos: usize,
     textbuf_completeseq: bool,
     binbuf: Vec<u8>,
+    queued: usize,
+    chunk_size: usize,
+    newline: NewlinePolicy,
+    max_line_length: Option<usize>,
+    consumed: u64,
+    line: u64,
+    column: u64,
+    after_cr: bool,
+    linebuf: String,
+    check_binary: bool,
+    reset_at_newline: bool,
+    bom: BomPolicy,
+    bom_pending: bool,
+    utf16_unresolved: bool,
+    report: DecodeReport,
+    trap_handler: Option<TrapHandler>,
+    decoded_lines: LineCounter,
+    /// Whether the stream may grow after its end, as followed by `Follow`, so that the decoder
+    /// is neither finished nor trapped there.
+    growing: bool,
 }
 
-impl<R: Read> TextReader<R> {
-    /// Creates a new `TextReader` with `codec`.
+impl<R: Read> TextReader<BufReader<R>> {
+    /// Creates a new `TextReader` with `codec`, reading through a `BufReader`.
     ///
     /// # Examples
     /// ```
-    /// extern crate textstream;
-    /// extern crate encoding;
     /// use std::fs::File;
     /// use encoding::label::encoding_from_whatwg_label;
     /// use encoding::{DecoderTrap, Encoding};
@@ -74,16 +313,41 @@ impl<R: Read> TextReader<R> {
     /// # }
     /// # fn main() { foo(); }
     /// ```
-    pub fn new(bufreader: R, encoding: &Encoding, trap: DecoderTrap) -> TextReader<R> {
-        TextReader::from_bufreader(BufReader::new(bufreader), encoding, trap)
+    pub fn new(reader: R, encoding: EncodingRef, trap: DecoderTrap) -> TextReader<BufReader<R>> {
+        TextReader::from_bufread(BufReader::new(reader), encoding, trap)
+    }
+
+    /// Creates a new `TextReader` with `codec`, reading through a `BufReader` of `capacity`
+    /// bytes, which are decoded at once. Large capacities suit batch jobs on large files, and
+    /// small ones suit memory constrained environments.
+    /// See `TextReaderBuilder` for more settings.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let reader = TextReader::with_capacity(256 * 1024, &b"\x82\xa0"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// assert_eq!(reader.get_ref().capacity(), 256 * 1024);
+    /// # }
+    /// ```
+    pub fn with_capacity(capacity: usize, reader: R, encoding: EncodingRef, trap: DecoderTrap) -> TextReader<BufReader<R>> {
+        TextReaderBuilder::new(encoding).trap(trap).chunk_size(capacity).buffer_capacity(capacity).build(reader)
+    }
+
+    /// Creates a new `TextReader` from BufReader. Same as `from_bufread`.
+    pub fn from_bufreader(bufreader: BufReader<R>, encoding: EncodingRef, trap: DecoderTrap) -> TextReader<BufReader<R>> {
+        TextReader::from_bufread(bufreader, encoding, trap)
     }
+}
 
-    /// Creates a new `TextReader` from BufReader.
+impl<R: BufRead> TextReader<R> {
+    /// Creates a new `TextReader` from a reader which is already buffered, such as `BufReader`,
+    /// `Cursor`, a byte slice or `StdinLock`, without another buffer.
     ///
     /// # Examples
     /// ```
-    /// extern crate textstream;
-    /// extern crate encoding;
     /// use std::fs::File;
     /// use std::io::BufReader;
     /// use encoding::label::encoding_from_whatwg_label;
@@ -91,101 +355,540 @@ impl<R: Read> TextReader<R> {
     /// use textstream::TextReader;
     /// # fn foo() -> std::io::Result<()> {
     /// let mut f = BufReader::new(File::open("shiftjis.txt")?);
-    /// let mut reader = TextReader::new(f, encoding_from_whatwg_label("shiftjis").unwrap(), DecoderTrap::Strict);
+    /// let mut reader = TextReader::from_bufread(f, encoding_from_whatwg_label("shiftjis").unwrap(), DecoderTrap::Strict);
     /// # Ok(())
     /// # }
     /// # fn main() { foo(); }
     /// ```
-    pub fn from_bufreader(bufreader: BufReader<R>, encoding: &Encoding, trap: DecoderTrap) -> TextReader<R> {
+    pub fn from_bufread(reader: R, encoding: EncodingRef, trap: DecoderTrap) -> TextReader<R> {
         TextReader {
-            bufreader: bufreader,
-            decoder: encoding.raw_decoder(),
-            trap: trap,
+            reader,
+            decoder: decoder::new_decoder(encoding),
+            encoding,
+            trap,
             textbuf: String::new(),
+            textpos: 0,
             textbuf_completeseq: true,
-            binbuf: Vec::with_capacity(CHUNK_SIZE),
+            binbuf: Vec::new(),
+            queued: 0,
+            chunk_size: CHUNK_SIZE,
+            newline: NewlinePolicy::default(),
+            max_line_length: None,
+            consumed: 0,
+            line: 0,
+            column: 0,
+            after_cr: false,
+            linebuf: String::new(),
+            check_binary: false,
+            reset_at_newline: false,
+            bom: BomPolicy::Keep,
+            bom_pending: false,
+            utf16_unresolved: false,
+            report: DecodeReport::default(),
+            trap_handler: None,
+            decoded_lines: LineCounter::default(),
+            growing: false,
         }
     }
 
-    /// Gets a reference to the underlying text reader.
+    /// Creates a new `TextReader` decoding with `decoder`, a codec which is not in the `encoding`
+    /// crate, such as a vendor specific variant of Shift_JIS.
+    /// `encoding` is the closest encoding with the same byte width of characters, which the rest
+    /// of the reader relies on: the byte offsets of the position are counted by encoding the
+    /// buffered text with it, and the partitions of `par_lines` are decoded with it.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::ISO_8859_1;
+    /// use encoding::{CodecError, DecoderTrap, StringWriter};
+    /// use textstream::{TextDecoder, TextReader};
+    ///
+    /// /// JIS X 0201 Roman, ASCII with a yen sign and an overline.
+    /// struct JisRoman;
+    /// impl TextDecoder for JisRoman {
+    ///     fn feed(&mut self, input: &[u8], output: &mut dyn StringWriter) -> (usize, Option<CodecError>) {
+    ///         for (i, &b) in input.iter().enumerate() {
+    ///             match b {
+    ///                 b'\\' => output.write_char('¥'),
+    ///                 b'~' => output.write_char('‾'),
+    ///                 0..=0x7f => output.write_char(b as char),
+    ///                 _ => return (i, Some(CodecError { upto: i as isize + 1, cause: "not JIS X 0201".into() })),
+    ///             }
+    ///         }
+    ///         (input.len(), None)
+    ///     }
+    ///     fn finish(&mut self, _output: &mut dyn StringWriter) -> Option<CodecError> { None }
+    /// }
+    ///
+    /// # fn main() {
+    /// let mut reader = TextReader::with_decoder(&b"\\100\xff"[..], ISO_8859_1, JisRoman, DecoderTrap::Replace);
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s).unwrap();
+    /// assert_eq!(s, "¥100\u{fffd}");
+    /// # }
+    /// ```
+    pub fn with_decoder<D: TextDecoder + 'static>(reader: R, encoding: EncodingRef, decoder: D, trap: DecoderTrap) -> TextReader<R> {
+        let mut textreader = TextReader::from_bufread(reader, encoding, trap);
+        textreader.decoder = Box::new(decoder);
+        textreader
+    }
+
+    /// Sets the line terminators recognized by `read_line` and `lines`.
+    /// The default is `NewlinePolicy::Lf`.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::{NewlinePolicy, TextReader};
+    /// # fn main() {
+    /// let mut reader = TextReader::new(&b"foo\rbar\r"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// reader.set_newline_policy(NewlinePolicy::Universal);
+    /// let lines: Vec<_> = reader.lines().map(|l| l.unwrap()).collect();
+    /// assert_eq!(lines, ["foo", "bar"]);
+    /// # }
+    /// ```
+    pub fn set_newline_policy(&mut self, policy: NewlinePolicy) {
+        self.newline = policy;
+    }
+
+    /// Gets the line terminators recognized by `read_line` and `lines`.
+    pub fn newline_policy(&self) -> NewlinePolicy { self.newline }
+
+    /// Sets the maximum length of a line in bytes of decoded text, including the line terminator.
+    /// `read_line` fails with `Error::LineTooLong` for longer lines. The default is `None`.
+    pub fn set_max_line_length(&mut self, max: Option<usize>) {
+        self.max_line_length = max;
+    }
+
+    /// Gets the maximum length of a line.
+    pub fn max_line_length(&self) -> Option<usize> { self.max_line_length }
+
+    /// Sets whether to check if the stream looks like binary data, such as images, rather than
+    /// text. If enabled, the first chunk read is examined, and reading fails with
+    /// `Error::BinaryData` if it contains NUL, or many other control characters.
+    /// The default is `false`. This must be set before reading.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::EUC_JP;
+    /// use encoding::DecoderTrap;
+    /// use textstream::{Error, TextReader};
+    /// # fn main() {
+    /// let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    /// let mut reader = TextReader::new(&png[..], EUC_JP, DecoderTrap::Replace);
+    /// reader.set_binary_detection(true);
+    /// assert!(matches!(reader.read_to_end(&mut String::new()), Err(Error::BinaryData)));
+    /// # }
+    /// ```
+    pub fn set_binary_detection(&mut self, enabled: bool) {
+        self.check_binary = enabled;
+    }
+
+    /// Sets whether to reset the decoder to the initial state at every `\n`, for stateful
+    /// encodings such as ISO-2022-JP, where every line is expected to start in ASCII, as in
+    /// mail. Lines of producers which shift at every line without shifting back are decoded
+    /// without the state carried over from the line before. The default is `false`.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::ISO_2022_JP;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// // Half-width katakana, not shifted back before the line terminator.
+    /// let mut reader = TextReader::new(&b"\x1b(I\x31\nabc"[..], ISO_2022_JP, DecoderTrap::Strict);
+    /// reader.set_reset_at_newline(true);
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s).unwrap();
+    /// assert_eq!(s, "\u{ff71}\nabc");
+    /// # }
+    /// ```
+    pub fn set_reset_at_newline(&mut self, enabled: bool) {
+        self.reset_at_newline = enabled;
+    }
+
+    /// Gets whether the decoder is reset at every `\n`.
+    pub fn reset_at_newline(&self) -> bool { self.reset_at_newline }
+
+    /// Gets a reference to the underlying reader.
     /// It is inadvisable to directly read from the underlying reader.
-    pub fn get_bufreader(&self) -> &BufReader<R> { &self.bufreader }
+    pub fn get_ref(&self) -> &R { &self.reader }
 
-    /// Gets a mutable reference to the underlying text reader.
+    /// Gets a mutable reference to the underlying reader.
     /// It is inadvisable to directly read from the underlying reader.
-    pub fn get_bufreader_mut(&mut self) -> &BufReader<R> { &mut self.bufreader }
+    pub fn get_mut(&mut self) -> &mut R { &mut self.reader }
 
     /// Unwraps this `TextReader`, returning the underlying reader.
-    /// Note that any leftover data in the internal chunk is lost.
-    pub fn into_bufreader(self) -> BufReader<R> { self.bufreader }
+    /// Note that any leftover data in the internal chunk is lost. Use `into_parts` to keep it.
+    pub fn into_inner(self) -> R { self.reader }
+
+    /// Unwraps this `TextReader`, returning the underlying reader, the bytes read from it but
+    /// not decoded yet, and the text decoded but not returned yet. The text comes before the
+    /// bytes, which come before the rest of the reader.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Read;
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::new(&b"\x82\xa0\nbody\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let mut header = String::new();
+    /// reader.read_line(&mut header)?;
+    /// let (mut rest, bytes, mut body) = reader.into_parts();
+    /// assert!(bytes.is_empty());
+    /// rest.read_to_string(&mut body)?;
+    /// assert_eq!(body, "body\n");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn into_parts(mut self) -> (R, Vec<u8>, String) {
+        let text = self.textbuf.split_off(self.textpos);
+        (self.reader, self.binbuf, text)
+    }
+
+    /// Gets a reference to the underlying reader.
+    #[deprecated(since = "0.2.0", note = "use `get_ref`")]
+    pub fn get_bufreader(&self) -> &R { self.get_ref() }
+
+    /// Gets a mutable reference to the underlying reader.
+    #[deprecated(since = "0.2.0", note = "use `get_mut`")]
+    pub fn get_bufreader_mut(&mut self) -> &mut R { self.get_mut() }
+
+    /// Unwraps this `TextReader`, returning the underlying reader.
+    #[deprecated(since = "0.2.0", note = "use `into_inner`")]
+    pub fn into_bufreader(self) -> R { self.into_inner() }
 
     /// Gets a reference to the underlying decoder.
-    pub fn get_decoder(&self) -> &RawDecoder { self.decoder.as_ref() }
+    pub fn get_decoder(&self) -> &dyn TextDecoder { self.decoder.as_ref() }
 
     /// Gets a mutable reference to the underlying decoder.
-    pub fn get_decoder_mut(&mut self) -> &mut RawDecoder { self.decoder.as_mut() }
+    pub fn get_decoder_mut(&mut self) -> &mut dyn TextDecoder { self.decoder.as_mut() }
 
     /// Unwraps this `TextReader`, returning the underlying decoder.
-    pub fn into_decoder(self) -> Box<RawDecoder> { self.decoder }
+    pub fn into_decoder(self) -> Box<dyn TextDecoder> { self.decoder }
+
+    /// Gets the name of the encoding, same as `Encoding::name`.
+    pub fn encoding_name(&self) -> &'static str { self.encoding.name() }
+
+    /// Switches to `encoding` from the current position, for streams which declare their
+    /// encoding partway through, such as mail with a `charset` header, or HTML with a late
+    /// `<meta charset>`. The bytes which have not been decoded, including an incomplete
+    /// sequence held by the current decoder, are decoded with a new decoder of `encoding`.
+    ///
+    /// The text decoded ahead of the position into the buffer of this reader is encoded back
+    /// with the current encoding to be decoded again. Fails with `Error::CodecError`, keeping
+    /// the current encoding, if it cannot be, such as for replaced invalid sequences. Reading the
+    /// start with an encoding which decodes every byte, such as `WINDOWS_1252`, avoids this.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::{WINDOWS_1252, WINDOWS_31J};
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mail = b"Content-Type: text/plain; charset=shift_jis\n\n\x82\xa0";
+    /// let mut reader = TextReader::new(&mail[..], WINDOWS_1252, DecoderTrap::Strict);
+    /// let mut header = String::new();
+    /// reader.read_line(&mut header)?;
+    /// assert!(header.ends_with("charset=shift_jis\n"));
+    /// reader.switch_encoding(WINDOWS_31J)?;
+    /// let mut body = String::new();
+    /// reader.read_to_end(&mut body)?;
+    /// assert_eq!(body, "\nあ");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn switch_encoding(&mut self, encoding: EncodingRef) -> Result<()> {
+        let mut binbuf = self.encoding.encode(&self.textbuf[self.textpos..], EncoderTrap::Strict)
+            .map_err(|_| Error::CodecError(format!("cannot encode the text read ahead back in {}", self.encoding.name()).into()))?;
+        self.consumed = self.consumed.saturating_sub(binbuf.len() as u64);
+        binbuf.extend_from_slice(&self.binbuf);
+        self.binbuf = binbuf;
+        self.queued = 0;
+        self.textbuf.clear();
+        self.textpos = 0;
+        self.textbuf_completeseq = true;
+        self.decoder = decoder::new_decoder(encoding);
+        self.encoding = encoding;
+        self.decoded_lines = LineCounter::default();
+        self.decoded_lines.line = self.line;
+        Ok(())
+    }
 
     /// For internal use. If sequence is incomplete, return false.
     fn _read(&mut self, s: &mut String) -> Result<bool> {
-        if self.textbuf.len() > 0 {
-            s.push_str(self.textbuf.as_ref());
+        if self.textpos < self.textbuf.len() {
+            s.push_str(&self.textbuf[self.textpos..]);
             let complete = self.textbuf_completeseq;
             self.textbuf.clear();
+            self.textpos = 0;
             self.textbuf_completeseq = true;
             return Ok(complete);
         }
-        if self.binbuf.len() < CHUNK_SIZE {
-            let mut binbuflen = self.binbuf.len();
-            self.binbuf.resize(CHUNK_SIZE, 0);
-            let nread = self.bufreader.read(&mut self.binbuf[binbuflen..])?;
-            binbuflen += nread;
-            self.binbuf.truncate(binbuflen);
-        }
-        s.reserve(self.binbuf.len());
-        let (offset, err) = self.decoder.raw_feed(&self.binbuf[..], s);
-        if offset > 0 {
-            if offset < self.binbuf.len() {
-                self.binbuf = self.binbuf[offset..].to_vec();
+        if self.utf16_unresolved {
+            self.resolve_utf16()?;
+        }
+        loop {
+            let strlen = s.len();
+            let (eof, result) = if self.binbuf.is_empty() {
+                self._decode_direct(s)?
             }
             else {
-                self.binbuf.clear();
+                self._decode_binbuf(s)?
+            };
+            let result = match result {
+                Ok(_) if eof && !self.growing => Ok(self.finish_decoder(s)),
+                result => result,
+            };
+            self.decoded_lines.count(&s[strlen..], self.newline);
+            if let Err(e) = self.check_bom(s, strlen, eof) {
+                // The text is returned by the next read.
+                self.textbuf.push_str(&s[strlen..]);
+                self.textbuf_completeseq = !matches!(result, Ok(false));
+                s.truncate(strlen);
+                return Err(e);
             }
-        }
-        if let Some(e) = err {
-            assert!(e.upto >= offset as isize);
-            if !self.trap.trap(&mut *self.decoder, &self.binbuf[..e.upto as usize], s) {
-                return Err(Error::from(e.cause));
+            let is_completeseq = result?;
+            if eof && !is_completeseq && !self.growing && self.trap_incomplete(s) {
+                return Ok(true);
+            }
+            // A short read may end in the middle of a character. Read further rather than
+            // returning no text, which is taken for the end of the stream.
+            if s.len() > strlen || eof {
+                return Ok(is_completeseq);
             }
-            if e.upto as usize - offset > 0 {
-                self.binbuf = self.binbuf[e.upto as usize - offset..].to_vec();
+        }
+    }
+
+    /// For internal use. Finishes the decoder at the end of the stream, the only point where it
+    /// is finished, as stateful encodings keep their state between chunks.
+    /// Returns false if an incomplete sequence is left in `binbuf`. The decoder no longer holds
+    /// it, so that it is fed again if the stream grows.
+    fn finish_decoder(&mut self, s: &mut String) -> bool {
+        let incomplete = self.decoder.finish(s).is_some();
+        self.queued = 0;
+        if !incomplete {
+            self.consumed += self.binbuf.len() as u64;
+            self.binbuf.clear();
+        }
+        !incomplete
+    }
+
+    /// For internal use. Handles the incomplete sequence left in `binbuf` at the end of the
+    /// stream with the trap, as the decoders of `Encoding::decode` do.
+    /// Returns false for `DecoderTrap::Strict`, to fail with `IncompleteSequence`.
+    fn trap_incomplete(&mut self, s: &mut String) -> bool {
+        let trap = Trap::new(self.trap, &mut self.trap_handler, self.consumed, self.decoded_lines, self.newline);
+        if !trap.apply(&mut *self.decoder, &self.binbuf, 0, self.binbuf.len(), s, s.len()) {
+            return false;
+        }
+        self.report.record(self.consumed);
+        self.consumed += self.binbuf.len() as u64;
+        self.binbuf.clear();
+        true
+    }
+
+    /// For internal use. Decodes a chunk from the buffer of the underlying reader.
+    /// An incomplete sequence at the end, which the decoder holds, is moved to `binbuf`.
+    /// Returns whether the end of the stream is reached, and the result of `decode`.
+    fn _decode_direct(&mut self, s: &mut String) -> Result<(bool, Result<bool>)> {
+        let buf = self.reader.fill_buf()?;
+        let input = &buf[..buf.len().min(self.chunk_size)];
+        if self.check_binary && !input.is_empty() {
+            if detect::looks_binary(input, self.encoding) {
+                return Err(Error::BinaryData);
             }
+            self.check_binary = false;
+        }
+        let trap = Trap::new(self.trap, &mut self.trap_handler, self.consumed, self.decoded_lines, self.newline);
+        let (processed, result, trapped) = if self.reset_at_newline {
+            decode(&mut LineReset::new(&mut *self.decoder, false), trap, input, 0, s)
         }
-        let mut is_completeseq = true;
-        if let Some(e) = self.decoder.raw_finish(s) {
-            if e.cause == ERR_INCOMPLETE_SEQ {
-                is_completeseq = false;
+        else {
+            decode(&mut *self.decoder, trap, input, 0, s)
+        };
+        if let Some(offset) = trapped {
+            self.report.record(self.consumed + offset as u64);
+        }
+        let mut consumed = processed;
+        if let Ok(false) = result {
+            self.binbuf.extend_from_slice(&input[processed..]);
+            self.queued = self.binbuf.len();
+            consumed = input.len();
+        }
+        let eof = input.is_empty();
+        self.reader.consume(consumed);
+        let result = result.map_err(|cause| self.decode_error(cause, processed));
+        self.consumed += processed as u64;
+        Ok((eof, result))
+    }
+
+    /// For internal use. Decodes `binbuf` with a few bytes appended from the underlying reader.
+    /// `binbuf` starts with the incomplete sequence held by the decoder, if any, which is
+    /// completed a byte at a time, so that the bytes after it are left in the reader, and
+    /// decoding goes back to `_decode_direct` instead of copying every chunk through `binbuf`.
+    /// Returns whether the end of the stream is reached, with every byte fed to the decoder, and
+    /// the result of `decode`.
+    fn _decode_binbuf(&mut self, s: &mut String) -> Result<(bool, Result<bool>)> {
+        let pending = self.binbuf.len();
+        let buf = self.reader.fill_buf()?;
+        // The binary check wants a whole chunk to look at.
+        let limit = if self.check_binary {
+            self.chunk_size
+        }
+        else if self.queued == pending {
+            1
+        }
+        else {
+            MIN_CHUNK_SIZE
+        };
+        let nread = buf.len().min(limit);
+        self.binbuf.extend_from_slice(&buf[..nread]);
+        if self.check_binary {
+            if detect::looks_binary(&self.binbuf, self.encoding) {
+                self.reader.consume(nread);
+                return Err(Error::BinaryData);
             }
-            else if !self.trap.trap(&mut *self.decoder, &self.binbuf[..e.upto as usize], s) {
-                assert!(e.upto >= 0);
-                if e.upto > 0 {
-                    self.binbuf = self.binbuf[e.upto as usize - offset..].to_vec();
-                }
-                return Err(Error::from(e.cause));
+            self.check_binary = false;
+        }
+        let trap = Trap::new(self.trap, &mut self.trap_handler, self.consumed, self.decoded_lines, self.newline);
+        let (processed, result, trapped) = if self.reset_at_newline {
+            decode(&mut LineReset::new(&mut *self.decoder, self.queued > 0), trap, &self.binbuf, self.queued, s)
+        }
+        else {
+            decode(&mut *self.decoder, trap, &self.binbuf, self.queued, s)
+        };
+        if let Some(offset) = trapped {
+            self.report.record(self.consumed + offset as u64);
+        }
+        if let Ok(false) = result {
+            // Every byte has been fed, and the decoder holds the rest.
+            self.reader.consume(nread);
+            self.binbuf.drain(..processed);
+            self.queued = self.binbuf.len();
+        }
+        else if processed >= pending {
+            self.reader.consume(processed - pending);
+            self.binbuf.clear();
+            self.queued = 0;
+        }
+        else {
+            self.reader.consume(nread);
+            self.binbuf.drain(..processed);
+            self.queued = 0;
+        }
+        let result = result.map_err(|cause| self.decode_error(cause, processed));
+        self.consumed += processed as u64;
+        Ok((nread == 0 && self.queued == self.binbuf.len(), result))
+    }
+
+    /// For internal use. Creates a `DecodeError` for the bytes at `offset` in the chunk being
+    /// decoded. The line and the column are set by `locate`.
+    fn decode_error(&self, cause: Cow<'static, str>, offset: usize) -> Error {
+        let position = Position { byte: self.consumed + offset as u64, line: 0, column: 0 };
+        Error::DecodeError { cause, position }
+    }
+
+    /// For internal use. Creates an `IncompleteSequence` for the bytes left in `binbuf` at the
+    /// end of the stream. The line and the column are set by `locate`.
+    fn incomplete_error(&self) -> Error {
+        Error::IncompleteSequence(Position { byte: self.consumed, line: 0, column: 0 })
+    }
+
+    /// For internal use. Sets the line and the column of a `DecodeError` or an
+    /// `IncompleteSequence` to the current ones, which are those of the error once the text
+    /// decoded before it has been returned.
+    fn locate<T>(&self, result: Result<T>) -> Result<T> {
+        let here = |position: Position| Position { line: self.line + 1, column: self.column + 1, ..position };
+        match result {
+            Err(Error::DecodeError { cause, position }) => {
+                Err(Error::DecodeError { cause, position: here(position) })
+            },
+            Err(Error::IncompleteSequence(position)) => Err(Error::IncompleteSequence(here(position))),
+            result => result,
+        }
+    }
+
+    /// For internal use. Updates the line and the column with text returned to the caller.
+    fn advance(&mut self, text: &str) {
+        if self.newline != NewlinePolicy::Lf {
+            for c in text.chars() {
+                self.advance_char(c);
             }
+            return;
+        }
+        match text.rfind('\n') {
+            Some(i) => {
+                self.line += text.bytes().filter(|&b| b == b'\n').count() as u64;
+                self.column = text[i + 1..].chars().count() as u64;
+            },
+            None => self.column += text.chars().count() as u64,
+        }
+    }
+
+    /// For internal use. Updates the line and the column with a character returned to the caller.
+    fn advance_char(&mut self, c: char) {
+        let after_cr = mem::replace(&mut self.after_cr, false);
+        let newline = match c {
+            '\n' => self.newline == NewlinePolicy::Lf || !after_cr,
+            '\r' if self.newline != NewlinePolicy::Lf => {
+                self.after_cr = true;
+                true
+            },
+            _ => self.newline == NewlinePolicy::Unicode && is_unicode_newline(c),
+        };
+        if newline {
+            self.line += 1;
+            self.column = 0;
+        }
+        else if c != '\n' {
+            self.column += 1;
+        }
+    }
+
+    /// Returns the position after the text returned so far: the offset in the underlying reader,
+    /// and the line and the column in the text.
+    ///
+    /// Lines are counted with the `NewlinePolicy`. Columns are counted in characters.
+    /// The offset is counted from the start of the reader passed to the constructor. It is exact
+    /// when no decoded text is buffered in this reader. Otherwise the length of the buffered text
+    /// is estimated by encoding it again, which may differ from the source for stateful
+    /// encodings or replaced errors.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// let mut reader = TextReader::new(&b"a\n\x82\xa0b\nc"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let mut s = String::new();
+    /// reader.read_line(&mut s).unwrap();
+    /// assert_eq!(reader.read_char().unwrap(), Some('あ'));
+    /// let pos = reader.position();
+    /// assert_eq!((pos.byte, pos.line, pos.column), (4, 2, 2));
+    /// ```
+    pub fn position(&self) -> Position {
+        let mut byte = self.consumed;
+        if self.textpos < self.textbuf.len() {
+            let buffered = self.encoding.encode(&self.textbuf[self.textpos..], EncoderTrap::Replace);
+            byte = byte.saturating_sub(buffered.map_or(0, |b| b.len() as u64));
         }
-        Ok(is_completeseq)
+        Position { byte, line: self.line + 1, column: self.column + 1 }
     }
 
     /// Read decoded text until file end, placing them into `buf`.
     /// If successful, this function will return the total number of bytes read.
     ///
+    /// If the underlying reader fails with `ErrorKind::WouldBlock`, the text read before it is
+    /// left in `buf`, and the call can be retried to append the rest.
+    ///
     /// # Examples:
     /// ```
-    /// extern crate textstream;
-    /// extern crate encoding;
     /// use std::fs::File;
     /// use std::io::BufReader;
     /// use textstream::TextReader;
@@ -193,7 +896,7 @@ impl<R: Read> TextReader<R> {
     /// use encoding::{DecoderTrap, Encoding};
     /// # fn foo() -> textstream::Result<()> {
     /// let mut f = BufReader::new(File::open("shiftjis.txt")?);
-    /// let mut reader = TextReader::new(f, encoding_from_whatwg_label("shiftjis").unwrap(), DecoderTrap::Strict);
+    /// let mut reader = TextReader::from_bufread(f, encoding_from_whatwg_label("shiftjis").unwrap(), DecoderTrap::Strict);
     /// let mut s = String::new();
     /// reader.read_to_end(&mut s)?;
     /// # Ok(())
@@ -201,6 +904,20 @@ impl<R: Read> TextReader<R> {
     /// # fn main() { foo(); }
     /// ```
     pub fn read_to_end(&mut self, buf: &mut String) -> Result<usize> {
+        let nstrlen = buf.len();
+        let result = self._read_to_end(buf);
+        self.advance(&buf[nstrlen..]);
+        self.locate(result)
+    }
+
+    /// Read decoded text until file end, placing them into `buf`, like `Read::read_to_string`.
+    /// Same as `read_to_end`.
+    pub fn read_to_string(&mut self, buf: &mut String) -> Result<usize> {
+        self.read_to_end(buf)
+    }
+
+    /// For internal use. `read_to_end` without updating the position.
+    fn _read_to_end(&mut self, buf: &mut String) -> Result<usize> {
         let nstrlen = buf.len();
         let mut lastlen = buf.len();
         loop {
@@ -213,7 +930,7 @@ impl<R: Read> TextReader<R> {
                             return Ok(lastlen - nstrlen);
                         }
                         else {
-                            return Err(Error::CodecError(Cow::from(ERR_INCOMPLETE_SEQ)));
+                            return Err(self.incomplete_error());
                         }
                     }
                     lastlen = buf.len();
@@ -222,13 +939,87 @@ impl<R: Read> TextReader<R> {
         }
     }
 
-    /// Read decoded text until file end, placing them into `buf`.
+    /// Read decoded text until file end, never failing outright.
+    /// Returns the text decoded successfully, the first error if any, and the raw bytes from
+    /// the position of the error to the end of the stream, so that as much text as possible can
+    /// be salvaged from damaged input. If reading the rest of the stream fails, the raw bytes
+    /// read before the failure are returned.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let mut reader = TextReader::new(&b"abc\xff\xfedef"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let (text, err, rest) = reader.read_to_string_partial();
+    /// assert_eq!(text, "abc");
+    /// assert!(err.is_some());
+    /// assert_eq!(rest, b"\xff\xfedef");
+    /// # }
+    /// ```
+    pub fn read_to_string_partial(&mut self) -> (String, Option<Error>, Vec<u8>) {
+        let mut s = String::new();
+        let err = self.read_to_end(&mut s).err();
+        let mut rest = mem::take(&mut self.binbuf);
+        self.queued = 0;
+        if let Some(Error::CodecError(_)) | Some(Error::DecodeError { .. }) | Some(Error::IncompleteSequence(_)) = err {
+            let _ = self.reader.read_to_end(&mut rest);
+        }
+        self.decoder.reset();
+        (s, err, rest)
+    }
+
+    /// For internal use. Returns the end of the first `delim` in `s`, or of the first line
+    /// terminator if `None`.
+    fn find_delim(&self, s: &str, delim: Option<char>, at_eof: bool) -> Option<usize> {
+        match delim {
+            Some(c) => s.find(c).map(|n| n + c.len_utf8()),
+            None => self.find_line_end(s, at_eof),
+        }
+    }
+
+    /// For internal use. Returns the end of the first line terminator in `s`.
+    /// A `\r` at the end of `s` is not a terminator unless `at_eof`,
+    /// because it may be followed by `\n`.
+    fn find_line_end(&self, s: &str, at_eof: bool) -> Option<usize> {
+        let n = match self.newline {
+            NewlinePolicy::Lf => memchr(b'\n', s.as_bytes()),
+            NewlinePolicy::Universal => memchr2(b'\r', b'\n', s.as_bytes()),
+            NewlinePolicy::Unicode => s.char_indices()
+                .find(|&(_, c)| c == '\r' || c == '\n' || is_unicode_newline(c))
+                .map(|(n, _)| n),
+        }?;
+        match s.as_bytes()[n] {
+            b'\r' => match s.as_bytes().get(n + 1) {
+                Some(&b'\n') => Some(n + 2),
+                Some(_) => Some(n + 1),
+                None if at_eof => Some(n + 1),
+                None => None,
+            },
+            b'\n' => Some(n + 1),
+            _ => Some(n + s[n..].chars().next().unwrap().len_utf8()),
+        }
+    }
+
+    /// Read decoded text until a line terminator, placing them into `buf`.
+    /// The recognized line terminators are set by `set_newline_policy`.
     /// If successful, this function will return the total number of bytes read.
     ///
+    /// If the line is longer than the maximum line length, this function fails with
+    /// `Error::LineTooLong`, leaving the text up to the maximum line length in `buf`.
+    /// The rest of the line can be read by the subsequent calls.
+    ///
+    /// If the stream ends in the middle of a multibyte sequence, this function fails with
+    /// `Error::IncompleteSequence` under `DecoderTrap::Strict`, leaving the text before it in
+    /// `buf`, so that a truncated file is not taken for a complete last line.
+    ///
+    /// If the underlying reader fails with `ErrorKind::WouldBlock` or `ErrorKind::TimedOut`, the
+    /// text read before it is kept in this reader rather than left in `buf`, and the call can be
+    /// retried once the reader is ready. Please see also `try_read_line` and `with_timeout`.
+    ///
     /// # Examples:
     /// ```
-    /// extern crate textstream;
-    /// extern crate encoding;
     /// use std::fs::File;
     /// use std::io::BufReader;
     /// use encoding::label::encoding_from_whatwg_label;
@@ -236,7 +1027,7 @@ impl<R: Read> TextReader<R> {
     /// use textstream::TextReader;
     /// # fn foo() -> textstream::Result<()> {
     /// let mut f = BufReader::new(File::open("shiftjis.txt")?);
-    /// let mut reader = TextReader::new(f, encoding_from_whatwg_label("shiftjis").unwrap(), DecoderTrap::Strict);
+    /// let mut reader = TextReader::from_bufread(f, encoding_from_whatwg_label("shiftjis").unwrap(), DecoderTrap::Strict);
     /// let mut s = String::new();
     /// reader.read_line(&mut s)?;
     /// # Ok(())
@@ -244,93 +1035,1066 @@ impl<R: Read> TextReader<R> {
     /// # fn main() { foo(); }
     /// ```
     pub fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        let nstrlen = buf.len();
+        let result = self._read_line(buf);
+        self.unread_retryable(buf, nstrlen, &result);
+        self.advance(&buf[nstrlen..]);
+        self.locate(result)
+    }
+
+    /// Reads a line like `read_line`, from a non-blocking reader. Returns `None` if the
+    /// underlying reader fails with `ErrorKind::WouldBlock` before a whole line is read, leaving
+    /// `buf` as it was; the text read so far is returned by the next call.
+    /// Returns `Some(0)` at the end of the stream.
+    ///
+    /// # Examples:
+    /// ```
+    /// use std::io::BufReader;
+    /// use std::net::TcpStream;
+    /// use encoding::all::UTF_8;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let stream = TcpStream::connect("127.0.0.1:8080")?;
+    /// stream.set_nonblocking(true)?;
+    /// let mut reader = TextReader::from_bufread(BufReader::new(stream), UTF_8, DecoderTrap::Strict);
+    /// let mut s = String::new();
+    /// match reader.try_read_line(&mut s)? {
+    ///     Some(0) => println!("closed"),
+    ///     Some(_) => println!("received {}", s),
+    ///     None => println!("no complete line yet"),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo(); }
+    /// ```
+    pub fn try_read_line(&mut self, buf: &mut String) -> Result<Option<usize>> {
+        match self.read_line(buf) {
+            Err(Error::IOError(ref e)) if e.kind() == ErrorKind::WouldBlock => Ok(None),
+            result => result.map(Some),
+        }
+    }
+
+    /// For internal use. Puts the text read before a `WouldBlock` or `TimedOut` error back into
+    /// `textbuf`, so that the line is read again as a whole by the retried call.
+    fn unread_retryable(&mut self, buf: &mut String, nstrlen: usize, result: &Result<usize>) {
+        if let Err(Error::IOError(e)) = result {
+            if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) {
+                let partial = buf.split_off(nstrlen);
+                self.unread(&partial);
+            }
+        }
+    }
+
+    /// For internal use. `read_line` without updating the position.
+    fn _read_line(&mut self, buf: &mut String) -> Result<usize> {
+        self._read_until(buf, None)
+    }
+
+    /// Read decoded text until `delim`, placing them into `buf`, like `BufRead::read_until`
+    /// in decoded characters. The delimiter is included, unless the end of the stream is
+    /// reached first. If successful, this function will return the total number of bytes read.
+    ///
+    /// The maximum line length and the handling of `ErrorKind::WouldBlock` and `ErrorKind::TimedOut`
+    /// apply as in `read_line`.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::new(&b"\x82\xa0=\x82\xa2"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let mut s = String::new();
+    /// reader.read_until_char('=', &mut s)?;
+    /// assert_eq!(s, "あ=");
+    /// s.clear();
+    /// reader.read_until_char('=', &mut s)?;
+    /// assert_eq!(s, "い");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn read_until_char(&mut self, delim: char, buf: &mut String) -> Result<usize> {
+        let nstrlen = buf.len();
+        let result = self._read_until(buf, Some(delim));
+        self.unread_retryable(buf, nstrlen, &result);
+        self.advance(&buf[nstrlen..]);
+        self.locate(result)
+    }
+
+    /// For internal use. Reads decoded text until `delim`, or a line terminator if `None`,
+    /// without updating the position. The maximum line length applies to any delimiter.
+    fn _read_until(&mut self, buf: &mut String, delim: Option<char>) -> Result<usize> {
+        // Most lines are found in the text decoded with a previous one, which is left in
+        // `textbuf` rather than copied out and in again.
+        if let Some(n) = self.find_delim(&self.textbuf[self.textpos..], delim, false) {
+            if self.max_line_length.map_or(true, |max| n <= max) {
+                buf.push_str(&self.textbuf[self.textpos..self.textpos + n]);
+                self.textpos += n;
+                return Ok(n);
+            }
+        }
         let nstrlen = buf.len();
         let mut lastlen = buf.len();
+        let mut searchfrom = buf.len();
         loop {
             let result = self._read(buf);
             let newlen = buf.len();
-            match memchr(b'\n', &buf[lastlen..].as_bytes()) {
-                Some(n) => {
-                    if lastlen + n + 1 < newlen {
-                        self.textbuf = buf[lastlen + n + 1..].to_string();
-                        self.textbuf_completeseq = match result.as_ref() {
-                            Err(&Error::CodecError(ref s)) if s == ERR_INCOMPLETE_SEQ => true,
-                            _ => false
-                        };
-                        buf.truncate(lastlen + n + 1);
+            let at_eof = newlen == lastlen && result.is_ok();
+            let found = self.find_delim(&buf[searchfrom..], delim, at_eof).map(|n| searchfrom + n);
+            let too_long = match self.max_line_length {
+                Some(max) if found.unwrap_or(newlen) - nstrlen > max => {
+                    let mut end = nstrlen + max;
+                    while !buf.is_char_boundary(end) {
+                        end -= 1;
                     }
-                    return Ok(lastlen + n + 1 - nstrlen);
+                    Some(end)
                 },
-                _ => {}
+                _ => None,
+            };
+            if let Some(end) = too_long.or(found) {
+                if end < newlen {
+                    self.textbuf.clear();
+                    self.textbuf.push_str(&buf[end..]);
+                    self.textpos = 0;
+                    self.textbuf_completeseq = !matches!(result, Ok(false));
+                    buf.truncate(end);
+                }
+                if too_long.is_some() {
+                    return Err(Error::LineTooLong);
+                }
+                return Ok(end - nstrlen);
             }
-            match result {
-                Err(e) => {
-                    match e {
-                        Error::IOError(ref ioerr) if ioerr.kind() == ErrorKind::Interrupted => {
-                            lastlen = newlen;
-                            continue;
-                        },
-                        Error::IOError(ref ioerr) if ioerr.kind() == ErrorKind::UnexpectedEof => {
-                            return Ok(newlen - nstrlen);
-                        },
-                        _ => return Err(e),
-                    }
+            // A trailing `\r` is searched again with the next chunk.
+            searchfrom = if delim.is_none() && newlen > nstrlen && buf.ends_with('\r') { newlen - 1 } else { newlen };
+            if let Err(e) = result {
+                match e {
+                    Error::IOError(ref ioerr) if ioerr.kind() == ErrorKind::Interrupted => {
+                        lastlen = newlen;
+                        continue;
+                    },
+                    Error::IOError(ref ioerr) if ioerr.kind() == ErrorKind::UnexpectedEof => {
+                        return Ok(newlen - nstrlen);
+                    },
+                    _ => return Err(e),
                 }
-                _ => {}
             }
             if lastlen == newlen {
+                // The text before an incomplete sequence is left in `buf`, as with a
+                // `DecodeError`, rather than returned as if the line were complete.
+                if matches!(result, Ok(false)) {
+                    return Err(self.incomplete_error());
+                }
                 return Ok(newlen - nstrlen);
             }
             lastlen = newlen;
         }
     }
 
-    /// Returns an iterator over the lines of this reader.
-    /// The iterator returned from this function will yield instances of
-    /// `textstream::Result<String>`. Each string will not have a newline byte (the 0xA byte) or
-    /// CRLF (0xD, 0xA bytes) at the end.
-    pub fn lines(self) -> Lines<R> {
-        Lines { textreader: self }
+    /// Reads a line like `read_line`, into a buffer of this reader rather than a `String` of
+    /// the caller. The line includes the line terminator, if any.
+    /// Returns `None` at the end of the stream. The line is valid until the next call.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::new(&b"\x82\xa0\nb"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let mut count = 0;
+    /// while let Some(line) = reader.read_line_borrowed()? {
+    ///     count += line.chars().count();
+    /// }
+    /// assert_eq!(count, 3);
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn read_line_borrowed(&mut self) -> Result<Option<&str>> {
+        let mut buf = mem::take(&mut self.linebuf);
+        buf.clear();
+        let result = self.read_line(&mut buf);
+        self.linebuf = buf;
+        match result? {
+            0 => Ok(None),
+            _ => Ok(Some(&self.linebuf)),
+        }
     }
-}
 
-/// An iterator over the lines of an `TextReader`.
-/// This struct is generally created by calling `lines()` on a `TextReader`. Please see the
-/// documentation of `lines()` for more details.
-pub struct Lines<R: Read> {
-    textreader: TextReader<R>
-}
-impl<R: Read> Iterator for Lines<R> {
-    type Item = Result<String>;
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Returns the next line like `read_line_borrowed`, without consuming it. The line is
+    /// returned again by the next read.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::new(&b"#!\x82\xa0\nbody"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let is_script = reader.peek_line()?.is_some_and(|line| line.starts_with("#!"));
+    /// assert!(is_script);
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s)?;
+    /// assert_eq!(s, "#!あ\nbody");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn peek_line(&mut self) -> Result<Option<&str>> {
+        let mut line = String::new();
+        let result = self._read_line(&mut line);
+        self.unread(&line);
+        self.locate(result)?;
+        match line.len() {
+            0 => Ok(None),
+            len => Ok(Some(&self.textbuf[self.textpos..self.textpos + len])),
+        }
+    }
+
+    /// Reads a decoded character.
+    /// Returns `None` at the end of the stream.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let mut reader = TextReader::new(&[0x82, 0xa0, 0x41][..], WINDOWS_31J, DecoderTrap::Strict);
+    /// assert_eq!(reader.read_char().unwrap(), Some('あ'));
+    /// assert_eq!(reader.read_char().unwrap(), Some('A'));
+    /// assert_eq!(reader.read_char().unwrap(), None);
+    /// # }
+    /// ```
+    pub fn read_char(&mut self) -> Result<Option<char>> {
+        if !self.fill_textbuf()? {
+            return Ok(None);
+        }
+        let c = self.textbuf[self.textpos..].chars().next().unwrap();
+        self.textpos += c.len_utf8();
+        self.advance_char(c);
+        Ok(Some(c))
+    }
+
+    /// Returns the next decoded character without consuming it.
+    /// Returns `None` at the end of the stream.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let mut reader = TextReader::new(&b"12\x82\xa0"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let mut number = String::new();
+    /// while let Some(c) = reader.peek_char().unwrap().filter(|c| c.is_ascii_digit()) {
+    ///     number.push(c);
+    ///     reader.read_char().unwrap();
+    /// }
+    /// assert_eq!(number, "12");
+    /// assert_eq!(reader.read_char().unwrap(), Some('あ'));
+    /// # }
+    /// ```
+    pub fn peek_char(&mut self) -> Result<Option<char>> {
+        if !self.fill_textbuf()? {
+            return Ok(None);
+        }
+        Ok(self.textbuf[self.textpos..].chars().next())
+    }
+
+    /// Skips `n` lines, without returning them. The line terminators are set by
+    /// `set_newline_policy`. Returns the number of lines skipped, which is less than `n` at the
+    /// end of the stream.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let csv = b"name,kana\r\n\x88\xa2,\x82\xa0\r\n";
+    /// let mut reader = TextReader::new(&csv[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// assert_eq!(reader.skip_lines(1)?, 1);
+    /// assert_eq!(reader.lines().next().unwrap()?, "阿,あ");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn skip_lines(&mut self, n: usize) -> Result<usize> {
+        for skipped in 0..n {
+            if self.read_line_borrowed()?.is_none() {
+                return Ok(skipped);
+            }
+        }
+        Ok(n)
+    }
+
+    /// Skips `n` decoded characters, without returning them. Returns the number of characters
+    /// skipped, which is less than `n` at the end of the stream.
+    pub fn skip_chars(&mut self, n: usize) -> Result<usize> {
+        self.consume_chars(n, None)
+    }
+
+    /// Reads up to `n` decoded characters, appending them to `buf`. Returns the number of
+    /// characters read, which is less than `n` only at the end of the stream.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let record = b"\x8e\x52\x93\x63\x81\x40\x91\xbe\x98\x59042";
+    /// let mut reader = TextReader::new(&record[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let mut name = String::new();
+    /// assert_eq!(reader.read_chars(5, &mut name)?, 5);
+    /// assert_eq!(name, "山田　太郎");
+    /// let mut age = String::new();
+    /// assert_eq!(reader.read_chars(5, &mut age)?, 3);
+    /// assert_eq!(age, "042");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn read_chars(&mut self, n: usize, buf: &mut String) -> Result<usize> {
+        self.consume_chars(n, Some(buf))
+    }
+
+    /// Creates an adapter which reads at most `n` decoded characters from this reader, like
+    /// `Read::take` in characters.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let reader = TextReader::new(&b"\x82\xa0\x82\xa2\x82\xa4"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let mut preview = String::new();
+    /// reader.take_chars(2).read_to_end(&mut preview)?;
+    /// assert_eq!(preview, "あい");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn take_chars(self, n: usize) -> TakeChars<R> {
+        TakeChars { textreader: self, limit: n }
+    }
+
+    /// For internal use. Consumes up to `n` decoded characters, appending them to `buf` if any.
+    fn consume_chars(&mut self, n: usize, mut buf: Option<&mut String>) -> Result<usize> {
+        let mut count = 0;
+        while count < n && self.fill_textbuf()? {
+            let textbuf = mem::take(&mut self.textbuf);
+            let rest = &textbuf[self.textpos..];
+            let end = rest.char_indices().nth(n - count).map_or(rest.len(), |(i, _)| i);
+            count += rest[..end].chars().count();
+            if let Some(ref mut buf) = buf {
+                buf.push_str(&rest[..end]);
+            }
+            self.advance(&rest[..end]);
+            self.textpos += end;
+            self.textbuf = textbuf;
+        }
+        Ok(count)
+    }
+
+    /// Pushes decoded text back, so that the next read returns `s` first.
+    /// The byte offset of `position` moves back by the encoded length of `s`, but the line and
+    /// the column do not, and count `s` again when it is read.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::new(&b"\x82\xa0\x82\xa2"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let c = reader.read_char()?.unwrap();
+    /// reader.unread(&c.to_string());
+    /// reader.unread("<");
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s)?;
+    /// assert_eq!(s, "<あい");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn unread(&mut self, s: &str) {
+        // Text just read is still in `textbuf` in most cases.
+        if self.textpos >= s.len() && &self.textbuf[self.textpos - s.len()..self.textpos] == s {
+            self.textpos -= s.len();
+            return;
+        }
+        let mut textbuf = String::with_capacity(s.len() + self.textbuf.len() - self.textpos);
+        textbuf.push_str(s);
+        textbuf.push_str(&self.textbuf[self.textpos..]);
+        self.textbuf = textbuf;
+        self.textpos = 0;
+    }
+
+    /// For internal use. Decodes text into `textbuf` if it is consumed.
+    /// Returns false at the end of the stream.
+    fn fill_textbuf(&mut self) -> Result<bool> {
+        if self.textpos == self.textbuf.len() {
+            let mut s = mem::take(&mut self.textbuf);
+            s.clear();
+            self.textpos = 0;
+            let result = loop {
+                match self._read(&mut s) {
+                    Err(Error::IOError(ref e)) if e.kind() == ErrorKind::Interrupted => {}
+                    result => break result,
+                }
+            };
+            // On a codec error, the text decoded before it is returned first.
+            // The error occurs again when the text is consumed.
+            match result {
+                Err(e) if s.is_empty() => return self.locate(Err(e)),
+                Ok(false) if s.is_empty() => return self.locate(Err(self.incomplete_error())),
+                Ok(true) if s.is_empty() => return Ok(false),
+                _ => {}
+            }
+            self.textbuf = s;
+        }
+        Ok(true)
+    }
+
+    /// Returns an iterator over the lines of this reader.
+    /// The iterator returned from this function will yield instances of
+    /// `textstream::Result<String>`. Each string will not have a newline byte (the 0xA byte) or
+    /// CRLF (0xD, 0xA bytes) at the end, nor the other line terminators recognized by the
+    /// `NewlinePolicy`.
+    pub fn lines(self) -> Lines<R> {
+        Lines { textreader: self }
+    }
+
+    /// Returns an iterator over the lines of this reader, like `lines()`, without taking
+    /// ownership of the reader. The rest of the stream can be read after the iteration.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let file = b"Title: \x82\xa0\n\nbody\nbody";
+    /// let mut reader = TextReader::new(&file[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// for line in reader.lines_mut() {
+    ///     if line?.is_empty() {
+    ///         break;
+    ///     }
+    /// }
+    /// let mut body = String::new();
+    /// reader.read_to_end(&mut body)?;
+    /// assert_eq!(body, "body\nbody");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn lines_mut(&mut self) -> LinesMut<'_, R> {
+        LinesMut { textreader: self }
+    }
+
+    /// Returns an iterator over the lines of this reader, including their line terminators,
+    /// so that the text can be written back unchanged. Only the last line may lack a
+    /// terminator.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let reader = TextReader::new(&b"a\r\n\x82\xa0\nb"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let lines: Vec<_> = reader.lines_with_terminator().map(|l| l.unwrap()).collect();
+    /// assert_eq!(lines, ["a\r\n", "あ\n", "b"]);
+    /// # }
+    /// ```
+    pub fn lines_with_terminator(self) -> LinesWithTerminator<R> {
+        LinesWithTerminator { textreader: self }
+    }
+
+    /// Calls `f` with each line of this reader, without the line terminator, like `lines()`.
+    /// The lines are read into a single buffer, which is reused rather than allocating a
+    /// `String` per line. Stops at the end of the stream or when `f` returns
+    /// `ControlFlow::Break`.
+    ///
+    /// With `NewlinePolicy::Lf`, all the lines in a decoded chunk are found in a single pass
+    /// and passed to `f` without being copied, which is much faster for short lines.
+    ///
+    /// # Examples:
+    /// ```
+    /// use std::ops::ControlFlow;
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let log = b"INFO a\nERROR \x82\xa0\nINFO b\n";
+    /// let mut reader = TextReader::new(&log[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let mut errors = 0;
+    /// reader.for_each_line(|line| {
+    ///     if line.starts_with("ERROR") {
+    ///         errors += 1;
+    ///     }
+    ///     ControlFlow::Continue(())
+    /// })?;
+    /// assert_eq!(errors, 1);
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn for_each_line<F: FnMut(&str) -> ControlFlow<()>>(&mut self, mut f: F) -> Result<()> {
         let mut s = String::new();
-        match self.textreader.read_line(&mut s) {
+        loop {
+            if self.newline == NewlinePolicy::Lf && self.for_each_buffered_line(&mut f).is_break() {
+                return Ok(());
+            }
+            s.clear();
+            if self.read_line(&mut s)? == 0 {
+                return Ok(());
+            }
+            self.strip_newline(&mut s);
+            if f(&s).is_break() {
+                return Ok(());
+            }
+        }
+    }
+
+    /// For internal use. Calls `f` on each complete line in `textbuf`, without the line
+    /// terminator, until a line longer than the maximum line length.
+    /// Only for `NewlinePolicy::Lf`.
+    fn for_each_buffered_line<F: FnMut(&str) -> ControlFlow<()>>(&mut self, f: &mut F) -> ControlFlow<()> {
+        let rest = &self.textbuf[self.textpos..];
+        let mut start = 0;
+        let mut lines = 0;
+        let mut flow = ControlFlow::Continue(());
+        for end in memchr_iter(b'\n', rest.as_bytes()) {
+            if self.max_line_length.is_some_and(|max| end + 1 - start > max) {
+                break;
+            }
+            let line = &rest[start..end];
+            start = end + 1;
+            lines += 1;
+            flow = f(line.strip_suffix('\r').unwrap_or(line));
+            if flow.is_break() {
+                break;
+            }
+        }
+        self.textpos += start;
+        if lines > 0 {
+            self.line += lines;
+            self.column = 0;
+        }
+        flow
+    }
+
+    /// Returns an iterator over the segments of this reader separated by `delim`, like
+    /// `BufRead::split` over decoded text.
+    /// The iterator returned from this function will yield instances of
+    /// `textstream::Result<String>`. Each string will not have `delim` at the end.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let reader = TextReader::new(&b"a;\x82\xa0;;b"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let records: Vec<_> = reader.split(';').map(|r| r.unwrap()).collect();
+    /// assert_eq!(records, ["a", "あ", "", "b"]);
+    /// # }
+    /// ```
+    pub fn split(self, delim: char) -> Split<R> {
+        Split { textreader: self, delim }
+    }
+
+    /// Returns an iterator over the records of this reader separated by the source byte `byte`,
+    /// like `split()`. The byte must be an ASCII control character, and the encoding ASCII
+    /// compatible (see `EncodingInfo::ascii_compatible`), so that the byte never appears as a
+    /// part of other characters. Otherwise, this function fails with an error of kind
+    /// `InvalidInput`.
+    pub fn split_byte(self, byte: u8) -> Result<Split<R>> {
+        let ascii_compatible = EncodingInfo::for_encoding(self.encoding).is_some_and(|info| info.ascii_compatible);
+        if !byte.is_ascii_control() || !ascii_compatible {
+            return Err(Error::IOError(io::Error::new(ErrorKind::InvalidInput,
+                format!("cannot split {} on byte {:#04x}", self.encoding.name(), byte))));
+        }
+        Ok(self.split(byte as char))
+    }
+
+    /// Returns an iterator over the records of this reader separated by NUL bytes, such as the
+    /// output of `find -print0`. Please see the documentation of `split_byte()` for more details.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let reader = TextReader::new(&b"./\x82\xa0.txt\0./b.txt\0"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let paths: Vec<_> = reader.split_nul()?.collect::<textstream::Result<_>>()?;
+    /// assert_eq!(paths, ["./あ.txt", "./b.txt"]);
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn split_nul(self) -> Result<Split<R>> {
+        self.split_byte(0)
+    }
+
+    /// Returns an iterator over the paragraphs of this reader, which are separated by one or
+    /// more blank lines, i.e. lines empty or only of whitespace.
+    /// The iterator returned from this function will yield instances of
+    /// `textstream::Result<String>`. The lines of each paragraph are joined by `\n`, whatever
+    /// their line terminators, without a line terminator at the end.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let text = b"\x82\xa0\r\nb\r\n\r\n  \n\nc\n";
+    /// let reader = TextReader::new(&text[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let paragraphs: Vec<_> = reader.paragraphs().map(|p| p.unwrap()).collect();
+    /// assert_eq!(paragraphs, ["あ\nb", "c"]);
+    /// # }
+    /// ```
+    pub fn paragraphs(self) -> Paragraphs<R> {
+        Paragraphs { textreader: self }
+    }
+
+    /// Returns an iterator over the whitespace-separated tokens of this reader, across line
+    /// boundaries. Lines are not read as a whole.
+    /// The iterator returned from this function will yield instances of
+    /// `textstream::Result<String>`.
+    ///
+    /// # Examples:
+    /// ```
+    /// use encoding::all::EUC_JP;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let data = b"1 \xa4\xa2\n  2\t\xa4\xa4\n";
+    /// let reader = TextReader::new(&data[..], EUC_JP, DecoderTrap::Strict);
+    /// let tokens: Vec<_> = reader.tokens().map(|t| t.unwrap()).collect();
+    /// assert_eq!(tokens, ["1", "あ", "2", "い"]);
+    /// # }
+    /// ```
+    pub fn tokens(self) -> Tokens<R> {
+        Tokens { textreader: self }
+    }
+
+    /// For internal use. Reads a line without the line terminator, or `None` at the end of the
+    /// stream.
+    fn next_line(&mut self) -> Option<Result<String>> {
+        let mut s = String::new();
+        match self.read_line(&mut s) {
             Ok(_) => {
-                if s.len() > 0 {
-                    if s.ends_with("\n") {
-                        s.pop();
-                        if s.ends_with("\r") {
-                            s.pop();
-                        }
-                    }
+                if !s.is_empty() {
+                    self.strip_newline(&mut s);
                     Some(Ok(s))
                 }
                 else {
                     None
                 }
-            },
-            Err(e) => {
-                Some(Err(e))
+            },
+            Err(e) => {
+                Some(Err(e))
+            }
+        }
+    }
+
+    /// For internal use. Removes the line terminator at the end of `s`, if any.
+    fn strip_newline(&self, s: &mut String) {
+        if s.ends_with('\n') {
+            s.pop();
+            if s.ends_with('\r') {
+                s.pop();
+            }
+        }
+        else if (self.newline != NewlinePolicy::Lf && s.ends_with('\r'))
+                || (self.newline == NewlinePolicy::Unicode && s.ends_with(is_unicode_newline)) {
+            s.pop();
+        }
+    }
+
+    /// Returns an iterator over the decoded characters of this reader.
+    /// The iterator returned from this function will yield instances of
+    /// `textstream::Result<char>`.
+    pub fn chars(self) -> Chars<R> {
+        Chars { textreader: self }
+    }
+
+    /// For internal use. Discards the buffered bytes and text, and the state of the decoder,
+    /// for reading from `offset` of the underlying reader. The line and the column are reset.
+    fn reset(&mut self, offset: u64) {
+        self.binbuf.clear();
+        self.queued = 0;
+        self.textbuf.clear();
+        self.textpos = 0;
+        self.textbuf_completeseq = true;
+        self.decoder.reset();
+        self.consumed = offset;
+        self.line = 0;
+        self.column = 0;
+        self.after_cr = false;
+        self.decoded_lines = LineCounter::default();
+        self.bom_pending = self.bom != BomPolicy::Keep && offset == 0;
+    }
+}
+
+impl TextReader<BufReader<File>> {
+    /// Opens a file in read-only mode, like `File::open`, and creates a new `TextReader` for it.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::open("shiftjis.txt", WINDOWS_31J, DecoderTrap::Strict)?;
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s)?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() { let _ = foo(); }
+    /// ```
+    pub fn open<P: AsRef<Path>>(path: P, encoding: EncodingRef, trap: DecoderTrap) -> io::Result<TextReader<BufReader<File>>> {
+        Ok(TextReader::new(File::open(path)?, encoding, trap))
+    }
+
+    /// Opens a file like `open`, with the encoding of given label, defined in the WHATWG Encoding
+    /// standard. The label is checked before opening the file.
+    pub fn open_with_label<P: AsRef<Path>>(path: P, label: &str, trap: DecoderTrap) -> Result<TextReader<BufReader<File>>> {
+        let encoding = label::encoding_from_whatwg_label(label)
+            .ok_or_else(|| Error::UnknownEncoding(label.to_string()))?;
+        let mut textreader = TextReader::open(path, encoding, trap)?;
+        textreader.utf16_unresolved = label::is_generic_utf16(label);
+        Ok(textreader)
+    }
+}
+
+impl<R: BufRead + Seek> TextReader<R> {
+    /// Seeks to an offset, in bytes, in the underlying reader.
+    /// The internal buffers are cleared and the decoder is reset.
+    /// `SeekFrom::Current` is relative to the byte offset of `position`.
+    /// The line and the column of `position` count from the new offset.
+    ///
+    /// # Examples:
+    /// ```
+    /// use std::fs::File;
+    /// use std::io::SeekFrom;
+    /// use encoding::label::encoding_from_whatwg_label;
+    /// use encoding::{DecoderTrap, Encoding};
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut f = File::open("shiftjis.txt")?;
+    /// let mut reader = TextReader::new(f, encoding_from_whatwg_label("shiftjis").unwrap(), DecoderTrap::Strict);
+    /// let mut s = String::new();
+    /// reader.read_line(&mut s)?;
+    /// reader.seek(SeekFrom::Start(0))?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo(); }
+    /// ```
+    pub fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
+        let pos = match pos {
+            SeekFrom::Current(n) => {
+                let offset = self.position().byte.checked_add_signed(n).ok_or_else(|| {
+                    io::Error::new(ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position")
+                })?;
+                SeekFrom::Start(offset)
+            },
+            _ => pos
+        };
+        let newpos = self.reader.seek(pos)?;
+        self.reset(newpos);
+        Ok(newpos)
+    }
+}
+
+/// Shows the encoding, the bytes read but not decoded yet, the characters decoded but not
+/// returned yet, and the position. The underlying reader is not shown.
+impl<R: BufRead> fmt::Debug for TextReader<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TextReader")
+            .field("encoding", &self.encoding_name())
+            .field("buffered_bytes", &self.binbuf.len())
+            .field("buffered_chars", &self.textbuf[self.textpos..].chars().count())
+            .field("position", &self.position())
+            .finish_non_exhaustive()
+    }
+}
+
+
+/// An iterator over the lines of an `TextReader`.
+/// This struct is generally created by calling `lines()` on a `TextReader`. Please see the
+/// documentation of `lines()` for more details.
+pub struct Lines<R: BufRead> {
+    textreader: TextReader<R>
+}
+impl<R: BufRead> Lines<R> {
+    /// Unwraps this `Lines`, returning the underlying `TextReader`.
+    /// Text decoded but not yielded yet is kept in the reader.
+    pub fn into_inner(self) -> TextReader<R> { self.textreader }
+}
+impl<R: BufRead> Iterator for Lines<R> {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.textreader.next_line()
+    }
+}
+impl<R: BufRead> fmt::Debug for Lines<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Lines").field("textreader", &self.textreader).finish()
+    }
+}
+
+/// An iterator over the lines of an `TextReader`, including their line terminators.
+/// This struct is generally created by calling `lines_with_terminator()` on a `TextReader`.
+/// Please see the documentation of `lines_with_terminator()` for more details.
+pub struct LinesWithTerminator<R: BufRead> {
+    textreader: TextReader<R>
+}
+impl<R: BufRead> Iterator for LinesWithTerminator<R> {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut s = String::new();
+        match self.textreader.read_line(&mut s) {
+            Ok(0) => None,
+            Ok(_) => Some(Ok(s)),
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+impl<R: BufRead> fmt::Debug for LinesWithTerminator<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LinesWithTerminator").field("textreader", &self.textreader).finish()
+    }
+}
+
+/// An iterator over the segments of an `TextReader` separated by a character.
+/// This struct is generally created by calling `split()` on a `TextReader`. Please see the
+/// documentation of `split()` for more details.
+pub struct Split<R: BufRead> {
+    textreader: TextReader<R>,
+    delim: char,
+}
+impl<R: BufRead> Iterator for Split<R> {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut s = String::new();
+        match self.textreader.read_until_char(self.delim, &mut s) {
+            Ok(0) => None,
+            Ok(_) => {
+                if s.ends_with(self.delim) {
+                    s.pop();
+                }
+                Some(Ok(s))
+            },
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+impl<R: BufRead> fmt::Debug for Split<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Split").field("textreader", &self.textreader).field("delim", &self.delim).finish()
+    }
+}
+
+/// An iterator over the paragraphs of an `TextReader`.
+/// This struct is generally created by calling `paragraphs()` on a `TextReader`. Please see the
+/// documentation of `paragraphs()` for more details.
+pub struct Paragraphs<R: BufRead> {
+    textreader: TextReader<R>
+}
+impl<R: BufRead> Iterator for Paragraphs<R> {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut paragraph: Option<String> = None;
+        while let Some(line) = self.textreader.next_line() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            if line.trim().is_empty() {
+                if paragraph.is_some() {
+                    break;
+                }
+            }
+            else if let Some(ref mut s) = paragraph {
+                s.push('\n');
+                s.push_str(&line);
+            }
+            else {
+                paragraph = Some(line);
+            }
+        }
+        paragraph.map(Ok)
+    }
+}
+impl<R: BufRead> fmt::Debug for Paragraphs<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Paragraphs").field("textreader", &self.textreader).finish()
+    }
+}
+
+/// An iterator over the whitespace-separated tokens of an `TextReader`.
+/// This struct is generally created by calling `tokens()` on a `TextReader`. Please see the
+/// documentation of `tokens()` for more details.
+pub struct Tokens<R: BufRead> {
+    textreader: TextReader<R>
+}
+impl<R: BufRead> Iterator for Tokens<R> {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut token = String::new();
+        loop {
+            match self.textreader.read_char() {
+                Ok(Some(c)) if c.is_whitespace() => {
+                    if !token.is_empty() {
+                        return Some(Ok(token));
+                    }
+                },
+                Ok(Some(c)) => token.push(c),
+                Ok(None) if token.is_empty() => return None,
+                Ok(None) => return Some(Ok(token)),
+                Err(e) => return Some(Err(e)),
             }
         }
     }
 }
+impl<R: BufRead> fmt::Debug for Tokens<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tokens").field("textreader", &self.textreader).finish()
+    }
+}
+
+/// An iterator over the lines of a borrowed `TextReader`.
+/// This struct is generally created by calling `lines_mut()` on a `TextReader`. Please see the
+/// documentation of `lines_mut()` for more details.
+pub struct LinesMut<'a, R: BufRead> {
+    textreader: &'a mut TextReader<R>
+}
+impl<'a, R: BufRead> Iterator for LinesMut<'a, R> {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.textreader.next_line()
+    }
+}
+impl<'a, R: BufRead> fmt::Debug for LinesMut<'a, R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LinesMut").field("textreader", &self.textreader).finish()
+    }
+}
+
+/// An iterator over the decoded characters of an `TextReader`.
+/// This struct is generally created by calling `chars()` on a `TextReader`. Please see the
+/// documentation of `chars()` for more details.
+pub struct Chars<R: BufRead> {
+    textreader: TextReader<R>
+}
+impl<R: BufRead> Iterator for Chars<R> {
+    type Item = Result<char>;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.textreader.read_char() {
+            Ok(Some(c)) => Some(Ok(c)),
+            Ok(None) => None,
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+impl<R: BufRead> fmt::Debug for Chars<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Chars").field("textreader", &self.textreader).finish()
+    }
+}
+
+/// Reader adapter which limits the number of decoded characters read from a `TextReader`.
+/// This struct is generally created by calling `take_chars()` on a `TextReader`. Please see the
+/// documentation of `take_chars()` for more details.
+pub struct TakeChars<R: BufRead> {
+    textreader: TextReader<R>,
+    limit: usize,
+}
+impl<R: BufRead> TakeChars<R> {
+    /// Returns the number of characters that can be read before reaching the limit.
+    pub fn limit(&self) -> usize { self.limit }
+
+    /// Sets the number of characters that can be read before reaching the limit.
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Unwraps this `TakeChars`, returning the underlying `TextReader`.
+    /// The characters beyond the limit are kept in the reader.
+    pub fn into_inner(self) -> TextReader<R> { self.textreader }
+
+    /// Reads a decoded character, or `None` at the limit or at the end of the stream.
+    pub fn read_char(&mut self) -> Result<Option<char>> {
+        if self.limit == 0 {
+            return Ok(None);
+        }
+        let c = self.textreader.read_char()?;
+        if c.is_some() {
+            self.limit -= 1;
+        }
+        Ok(c)
+    }
+
+    /// Reads decoded text up to the limit or the end of the stream, placing them into `buf`.
+    /// If successful, this function will return the total number of bytes read.
+    pub fn read_to_end(&mut self, buf: &mut String) -> Result<usize> {
+        let nstrlen = buf.len();
+        let result = self.textreader.read_chars(self.limit, buf);
+        // Characters read before an error count too.
+        self.limit -= buf[nstrlen..].chars().count();
+        result?;
+        Ok(buf.len() - nstrlen)
+    }
+
+    /// Reads decoded text until a line terminator, the limit or the end of the stream, placing
+    /// them into `buf`. Please see `TextReader::read_line` for more details.
+    pub fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        if self.limit == 0 {
+            return Ok(0);
+        }
+        let nstrlen = buf.len();
+        let result = self.textreader._read_line(buf);
+        self.textreader.unread_retryable(buf, nstrlen, &result);
+        if let Some((end, _)) = buf[nstrlen..].char_indices().nth(self.limit) {
+            self.textreader.unread(&buf[nstrlen + end..]);
+            buf.truncate(nstrlen + end);
+        }
+        self.textreader.advance(&buf[nstrlen..]);
+        self.limit -= buf[nstrlen..].chars().count();
+        self.textreader.locate(result)?;
+        Ok(buf.len() - nstrlen)
+    }
+}
+impl<R: BufRead> Iterator for TakeChars<R> {
+    type Item = Result<char>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.read_char().transpose()
+    }
+}
+impl<R: BufRead> fmt::Debug for TakeChars<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("TakeChars").field("textreader", &self.textreader).field("limit", &self.limit).finish()
+    }
+}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use encoding::all::{ASCII, WINDOWS_1252, WINDOWS_31J};
     use encoding::label::encoding_from_whatwg_label;
     use encoding::DecoderTrap;
+    use std::{env, fs, process};
+    use std::io::Cursor;
 
     #[test]
     fn read_to_end_shortstring() {
@@ -356,6 +2120,115 @@ mod tests {
         assert_eq!(s, s_answer);
     }
     #[test]
+    fn read_to_end_replace() {
+        let v = [0x82, 0xa0, 0x41, 0xff, 0x82, 0xa2];
+        let mut reader = TextReader::new(&v[..], encoding_from_whatwg_label("sjis").unwrap(), DecoderTrap::Replace);
+        let mut s = String::new();
+        assert!(reader.read_to_end(&mut s).is_ok());
+        assert_eq!(s, "あA\u{FFFD}い");
+    }
+    #[test]
+    fn read_to_end_short_reads() {
+        struct OneByte<'a>(&'a [u8]);
+        impl<'a> Read for OneByte<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                (&mut self.0).take(1).read(buf)
+            }
+        }
+        let sjis_aiueo = [0x82, 0xa0, 0x82, 0xa2, 0x82, 0xa4, 0x82, 0xa6, 0x82, 0xa8];
+        let mut reader = TextReader::new(OneByte(&sjis_aiueo), encoding_from_whatwg_label("sjis").unwrap(), DecoderTrap::Strict);
+        let mut s = String::new();
+        assert!(reader.read_to_end(&mut s).is_ok());
+        assert_eq!(s, "あいうえお");
+    }
+    #[test]
+    fn would_block() {
+        // Blocks before every chunk, which are split in the middle of a line and a character.
+        struct NonBlocking<'a> { chunks: Vec<&'a [u8]>, blocked: bool }
+        impl<'a> Read for NonBlocking<'a> {
+            fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+                if self.chunks.is_empty() {
+                    return Ok(0);
+                }
+                self.blocked = !self.blocked;
+                if self.blocked {
+                    return Err(io::Error::from(ErrorKind::WouldBlock));
+                }
+                self.chunks.remove(0).read(buf)
+            }
+        }
+        let chunks = || vec![&b"a\r"[..], b"\n\x82", b"\xa0b\n", b"c"];
+        let mut reader = TextReader::new(NonBlocking { chunks: chunks(), blocked: false }, WINDOWS_31J, DecoderTrap::Strict);
+        reader.set_newline_policy(NewlinePolicy::Universal);
+        let mut lines = Vec::new();
+        let mut blocked = 0;
+        let mut s = String::new();
+        loop {
+            match reader.try_read_line(&mut s).unwrap() {
+                Some(0) => break,
+                Some(_) => lines.push(mem::take(&mut s)),
+                None => blocked += 1,
+            }
+        }
+        assert_eq!(lines, ["a\r\n", "あb\n", "c"]);
+        assert_eq!(blocked, 4);
+        let pos = reader.position();
+        assert_eq!((pos.byte, pos.line, pos.column), (8, 3, 2));
+        let mut reader = TextReader::new(NonBlocking { chunks: chunks(), blocked: false }, WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        loop {
+            match reader.read_to_end(&mut s) {
+                Ok(_) => break,
+                Err(Error::IOError(e)) => assert_eq!(e.kind(), ErrorKind::WouldBlock),
+                Err(e) => panic!("{}", e),
+            }
+        }
+        assert_eq!(s, "a\r\nあb\nc");
+    }
+    #[test]
+    fn switch_encoding_test() {
+        // The body is decoded ahead with the header.
+        let mut reader = TextReader::new(&b"charset=sjis\n\x82\xa0\n"[..], WINDOWS_1252, DecoderTrap::Strict);
+        let mut s = String::new();
+        reader.read_line(&mut s).unwrap();
+        reader.switch_encoding(WINDOWS_31J).unwrap();
+        assert_eq!(reader.encoding_name(), "windows-31j");
+        reader.read_line(&mut s).unwrap();
+        assert_eq!(s, "charset=sjis\nあ\n");
+        let pos = reader.position();
+        assert_eq!((pos.byte, pos.line, pos.column), (16, 3, 1));
+        // The incomplete sequence held by the decoder is kept.
+        let mut reader = TextReaderBuilder::new(WINDOWS_31J).chunk_size(3).build(&b"ab\x82\xa0"[..]);
+        assert_eq!(reader.read_char().unwrap(), Some('a'));
+        reader.switch_encoding(WINDOWS_31J).unwrap();
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "bあ");
+        let mut reader = TextReader::new(&b"a\xffb"[..], ASCII, DecoderTrap::Replace);
+        assert_eq!(reader.read_char().unwrap(), Some('a'));
+        assert!(matches!(reader.switch_encoding(WINDOWS_1252), Err(Error::CodecError(_))));
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "\u{fffd}b");
+    }
+    #[test]
+    fn read_to_string_partial_test() {
+        let mut v = vec![0x41u8; CHUNK_SIZE * 2];
+        v.extend_from_slice(&[0x82, 0xa0, 0x82]);
+        let mut reader = TextReader::new(&v[..], encoding_from_whatwg_label("sjis").unwrap(), DecoderTrap::Strict);
+        let (s, err, rest) = reader.read_to_string_partial();
+        assert_eq!(s.len(), CHUNK_SIZE * 2 + 3);
+        assert!(s.ends_with('あ'));
+        assert!(err.is_some());
+        assert_eq!(rest, [0x82]);
+        let mut reader = TextReader::new(&b"abc"[..], encoding_from_whatwg_label("sjis").unwrap(), DecoderTrap::Strict);
+        let (s, err, rest) = reader.read_to_string_partial();
+        assert_eq!(s, "abc");
+        assert!(err.is_none());
+        assert!(rest.is_empty());
+    }
+    #[test]
+    #[allow(clippy::match_like_matches_macro)]
     fn read_line_shortstring() {
         let sjis_aiueo = [0x82, 0xa0, 0x82, 0xa2, 0x82, 0xa4, 0x82, 0xa6, 0x82, 0xa8];
         let mut v = vec![];
@@ -375,6 +2248,7 @@ mod tests {
         assert_eq!(s, "あいうえお");
     }
     #[test]
+    #[allow(clippy::needless_borrow)]
     fn read_line_then_read_to_end_shortstring() {
         let sjis_aiueo = [0x82, 0xa0, 0x82, 0xa2, 0x82, 0xa4, 0x82, 0xa6, 0x82, 0xa8];
         let mut v = vec![];
@@ -390,6 +2264,576 @@ mod tests {
         assert_eq!(s, "abcdあいうえお");
     }
     #[test]
+    fn read_line_universal() {
+        let mut reader = TextReader::new(&b"a\rb\r\nc\nd\r"[..], encoding_from_whatwg_label("sjis").unwrap(), DecoderTrap::Strict);
+        reader.set_newline_policy(NewlinePolicy::Universal);
+        let mut s = String::new();
+        for line in ["a\r", "b\r\n", "c\n", "d\r", ""].iter() {
+            s.clear();
+            assert!(reader.read_line(&mut s).is_ok());
+            assert_eq!(s, *line);
+        }
+        let mut v = vec![b'a'; CHUNK_SIZE - 1];
+        v.extend_from_slice(b"\r\nb");
+        let mut reader = TextReader::new(&v[..], encoding_from_whatwg_label("sjis").unwrap(), DecoderTrap::Strict);
+        reader.set_newline_policy(NewlinePolicy::Universal);
+        let res: Vec<_> = reader.lines().map(|l| l.unwrap()).collect();
+        assert_eq!(res.len(), 2);
+        assert_eq!(res[0].len(), CHUNK_SIZE - 1);
+        assert_eq!(res[1], "b");
+    }
+    #[test]
+    fn lines_unicode() {
+        let v = "a\u{85}b\u{2028}c\u{2029}d\r\ne".as_bytes();
+        let mut reader = TextReader::new(v, encoding_from_whatwg_label("utf-8").unwrap(), DecoderTrap::Strict);
+        reader.set_newline_policy(NewlinePolicy::Unicode);
+        let res: Vec<_> = reader.lines().map(|l| l.unwrap()).collect();
+        assert_eq!(res, ["a", "b", "c", "d", "e"]);
+        let reader = TextReader::new(v, encoding_from_whatwg_label("utf-8").unwrap(), DecoderTrap::Strict);
+        assert_eq!(reader.lines().count(), 2);
+    }
+    #[test]
+    fn read_char_test() {
+        let sjis_aiueo = [0x82, 0xa0, 0x82, 0xa2, 0x82, 0xa4, 0x82, 0xa6, 0x82, 0xa8];
+        let mut v = vec![0x41u8];
+        let mut s_answer = "A".to_string();
+        for _ in 0..300 {
+            v.extend_from_slice(&sjis_aiueo);
+            s_answer += "あいうえお";
+        }
+        let reader = TextReader::new(&v[..], encoding_from_whatwg_label("sjis").unwrap(), DecoderTrap::Strict);
+        let s: Result<String> = reader.chars().collect();
+        assert_eq!(s.unwrap(), s_answer);
+        let mut reader = TextReader::new(&[0x41, 0x0a, 0x42, 0x82][..], encoding_from_whatwg_label("sjis").unwrap(), DecoderTrap::Strict);
+        let mut s = String::new();
+        assert!(reader.read_line(&mut s).is_ok());
+        assert_eq!(reader.read_char().unwrap(), Some('B'));
+        assert!(reader.read_char().is_err());
+    }
+    #[test]
+    fn read_line_too_long() {
+        let mut reader = TextReader::new(&b"abcdef\nab\n"[..], encoding_from_whatwg_label("sjis").unwrap(), DecoderTrap::Strict);
+        reader.set_max_line_length(Some(4));
+        let mut s = String::new();
+        assert!(matches!(reader.read_line(&mut s), Err(Error::LineTooLong)));
+        assert_eq!(s, "abcd");
+        s.clear();
+        assert!(matches!(reader.read_line(&mut s), Ok(3)));
+        assert_eq!(s, "ef\n");
+        s.clear();
+        assert!(matches!(reader.read_line(&mut s), Ok(3)));
+        assert_eq!(s, "ab\n");
+        let mut reader = TextReader::new("あい".as_bytes(), encoding_from_whatwg_label("utf-8").unwrap(), DecoderTrap::Strict);
+        reader.set_max_line_length(Some(4));
+        s.clear();
+        assert!(reader.read_line(&mut s).is_err());
+        assert_eq!(s, "あ");
+    }
+    #[test]
+    fn open_test() {
+        let path = env::temp_dir().join(format!("textstream-open-{}.txt", process::id()));
+        fs::write(&path, "あ\n").unwrap();
+        let mut reader = TextReader::open_with_label(&path, "utf-8", DecoderTrap::Strict).unwrap();
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(s, "あ\n");
+        assert!(matches!(TextReader::open_with_label(&path, "bazinga", DecoderTrap::Strict),
+                         Err(Error::UnknownEncoding(_))));
+        assert!(matches!(TextReader::open_with_label(&path, "utf-8", DecoderTrap::Strict),
+                         Err(Error::IOError(_))));
+    }
+    #[test]
+    fn position_test() {
+        let sjis = b"\x82\xa0\r\n\x82\xa2\r\x82\xa4";
+        let mut reader = TextReader::new(&sjis[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        reader.read_line(&mut s).unwrap();
+        assert_eq!(reader.position(), Position { byte: 4, line: 2, column: 1 });
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(reader.position(), Position { byte: 9, line: 2, column: 4 });
+        let mut reader = TextReader::new(&sjis[..], WINDOWS_31J, DecoderTrap::Strict);
+        reader.set_newline_policy(NewlinePolicy::Universal);
+        for _ in 0..4 {
+            reader.read_char().unwrap();
+        }
+        assert_eq!(reader.position(), Position { byte: 6, line: 2, column: 2 });
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(reader.position(), Position { byte: 9, line: 3, column: 2 });
+    }
+    #[test]
+    fn decode_error_position() {
+        let sjis = b"abc\n\x82\xa0x\xff\n";
+        let mut reader = TextReader::new(&sjis[..], WINDOWS_31J, DecoderTrap::Strict);
+        match reader.read_to_end(&mut String::new()) {
+            Err(Error::DecodeError { position, .. }) => {
+                assert_eq!(position, Position { byte: 7, line: 2, column: 3 });
+            },
+            _ => panic!("expected DecodeError"),
+        }
+        let mut lines = TextReader::new(&sjis[..], WINDOWS_31J, DecoderTrap::Strict).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "abc");
+        match lines.next().unwrap() {
+            Err(Error::DecodeError { position, .. }) => assert_eq!(position.byte, 7),
+            _ => panic!("expected DecodeError"),
+        }
+        let mut reader = TextReader::new(&b"a\xff"[..], WINDOWS_31J, DecoderTrap::Strict);
+        assert_eq!(reader.read_char().unwrap(), Some('a'));
+        match reader.read_char() {
+            Err(Error::DecodeError { position, .. }) => {
+                assert_eq!(position, Position { byte: 1, line: 1, column: 2 });
+            },
+            _ => panic!("expected DecodeError"),
+        }
+    }
+    #[test]
+    fn incomplete_sequence() {
+        let mut reader = TextReader::new(&b"ab\n\x82\xa0\x82"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        match reader.read_to_end(&mut s) {
+            Err(Error::IncompleteSequence(position)) => {
+                assert_eq!(position, Position { byte: 5, line: 2, column: 2 });
+            },
+            _ => panic!("expected IncompleteSequence"),
+        }
+        assert_eq!(s, "ab\nあ");
+        let mut lines = TextReader::new(&b"ab\n\x82"[..], WINDOWS_31J, DecoderTrap::Strict).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "ab");
+        assert!(matches!(lines.next(), Some(Err(Error::IncompleteSequence(_)))));
+        let mut reader = TextReader::new(&b"ab\n\x82\xa0\x82"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        assert_eq!(reader.read_line(&mut s).unwrap(), 3);
+        s.clear();
+        match reader.read_line(&mut s) {
+            Err(Error::IncompleteSequence(position)) => {
+                assert_eq!(position, Position { byte: 5, line: 2, column: 2 });
+            },
+            _ => panic!("expected IncompleteSequence"),
+        }
+        assert_eq!(s, "あ");
+        let mut lines = TextReader::new(&b"ab\n\x82\xa0\x82"[..], WINDOWS_31J, DecoderTrap::Strict).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "ab");
+        assert!(matches!(lines.next(), Some(Err(Error::IncompleteSequence(_)))));
+        let mut lines = TextReader::new(&b"ab\n\x82\xa0\x82"[..], WINDOWS_31J, DecoderTrap::Replace).lines();
+        assert_eq!(lines.nth(1).unwrap().unwrap(), "あ\u{fffd}");
+        let mut reader = TextReader::new(&b"a\x82"[..], WINDOWS_31J, DecoderTrap::Strict);
+        assert_eq!(reader.read_char().unwrap(), Some('a'));
+        assert!(matches!(reader.read_char(), Err(Error::IncompleteSequence(_))));
+    }
+    #[test]
+    fn error_display() {
+        let mut reader = TextReader::new(&b"ab\n\xff"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let err = reader.read_to_end(&mut String::new()).unwrap_err();
+        assert_eq!(err.to_string(), "invalid sequence at line 2, column 1 (byte 3)");
+        assert!(std::error::Error::source(&err).is_none());
+        let err = Error::from(io::Error::new(ErrorKind::Other, "disk on fire"));
+        assert_eq!(err.to_string(), "disk on fire");
+        assert!(std::error::Error::source(&err).is_some());
+    }
+    #[test]
+    fn into_io_error() {
+        fn read(bytes: &[u8]) -> io::Result<String> {
+            let mut s = String::new();
+            TextReader::new(bytes, WINDOWS_31J, DecoderTrap::Strict).read_to_end(&mut s)?;
+            Ok(s)
+        }
+        assert_eq!(read(b"\x82\xa0").unwrap(), "あ");
+        assert_eq!(read(b"\xff").unwrap_err().kind(), ErrorKind::InvalidData);
+        let err = io::Error::from(Error::UnknownEncoding("bogus".to_string()));
+        assert_eq!(err.kind(), ErrorKind::InvalidInput);
+    }
+    #[test]
+    fn lines_mut_test() {
+        let mut reader = TextReader::new(&b"a\r\nb\n\x82\xa0\nc"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let head: Vec<_> = reader.lines_mut().take(2).map(|l| l.unwrap()).collect();
+        assert_eq!(head, ["a", "b"]);
+        assert_eq!(reader.lines_mut().next().unwrap().unwrap(), "あ");
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "c");
+    }
+    #[test]
+    fn lines_with_terminator_test() {
+        let mut reader = TextReader::new(&b"a\rb\r\n\nc\r"[..], WINDOWS_31J, DecoderTrap::Strict);
+        reader.set_newline_policy(NewlinePolicy::Universal);
+        let lines: Vec<_> = reader.lines_with_terminator().map(|l| l.unwrap()).collect();
+        assert_eq!(lines.concat(), "a\rb\r\n\nc\r");
+        assert_eq!(lines, ["a\r", "b\r\n", "\n", "c\r"]);
+    }
+    #[test]
+    fn lines_into_inner() {
+        let mut lines = TextReader::new(&b"a\nb\nc"[..], WINDOWS_31J, DecoderTrap::Strict).lines();
+        assert_eq!(lines.next().unwrap().unwrap(), "a");
+        let mut reader = lines.into_inner();
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "b\nc");
+    }
+    #[test]
+    fn for_each_line_test() {
+        let mut reader = TextReader::new(&b"a\r\n\nb\nc"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut lines = vec![];
+        reader.for_each_line(|line| {
+            lines.push(line.to_string());
+            if line == "b" { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        }).unwrap();
+        assert_eq!(lines, ["a", "", "b"]);
+        let pos = reader.position();
+        assert_eq!((pos.line, pos.column), (4, 1));
+        assert_eq!(reader.lines().next().unwrap().unwrap(), "c");
+        let mut reader = TextReader::new(&b"ab\nabcd\nab\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+        reader.set_max_line_length(Some(3));
+        let mut lines = vec![];
+        let result = reader.for_each_line(|line| { lines.push(line.to_string()); ControlFlow::Continue(()) });
+        assert!(matches!(result, Err(Error::LineTooLong)));
+        assert_eq!(lines, ["ab"]);
+        let mut reader = TextReader::new(&b"a\n\xff\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut count = 0;
+        assert!(reader.for_each_line(|_| { count += 1; ControlFlow::Continue(()) }).is_err());
+        assert_eq!(count, 1);
+    }
+    #[test]
+    fn read_line_borrowed_test() {
+        let mut reader = TextReader::new(&b"a\r\n\x82\xa0\n\nb"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut lines = vec![];
+        while let Some(line) = reader.read_line_borrowed().unwrap() {
+            lines.push(line.to_string());
+        }
+        assert_eq!(lines, ["a\r\n", "あ\n", "\n", "b"]);
+        assert!(reader.read_line_borrowed().unwrap().is_none());
+    }
+    #[test]
+    fn split_test() {
+        let mut v = vec![];
+        for _ in 0..CHUNK_SIZE {
+            v.extend_from_slice(b"\x82\xa0\t");
+        }
+        let reader = TextReader::new(&v[..], WINDOWS_31J, DecoderTrap::Strict);
+        let fields: Vec<_> = reader.split('\t').map(|f| f.unwrap()).collect();
+        assert_eq!(fields.len(), CHUNK_SIZE);
+        assert!(fields.iter().all(|f| f == "あ"));
+        let reader = TextReader::new(&b"a\r\nb\x82\xa0"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let fields: Vec<_> = reader.split('\r').map(|f| f.unwrap()).collect();
+        assert_eq!(fields, ["a", "\nbあ"]);
+    }
+    #[test]
+    fn read_until_char_test() {
+        let mut reader = TextReader::new(&b"k\x82\xa0:v\nk2:"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        assert_eq!(reader.read_until_char(':', &mut s).unwrap(), 5);
+        assert_eq!(s, "kあ:");
+        s.clear();
+        reader.read_line(&mut s).unwrap();
+        assert_eq!(s, "v\n");
+        assert_eq!(reader.position().line, 2);
+        s.clear();
+        reader.read_until_char(':', &mut s).unwrap();
+        assert_eq!(s, "k2:");
+        assert_eq!(reader.read_until_char(':', &mut s).unwrap(), 0);
+    }
+    #[test]
+    fn paragraphs_test() {
+        let text = b"\n\na\nb\r\n\t\r\nc\r\n";
+        let reader = TextReader::new(&text[..], WINDOWS_31J, DecoderTrap::Strict);
+        let paragraphs: Vec<_> = reader.paragraphs().map(|p| p.unwrap()).collect();
+        assert_eq!(paragraphs, ["a\nb", "c"]);
+        let reader = TextReader::new(&b"\r\n\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+        assert_eq!(reader.paragraphs().count(), 0);
+    }
+    #[test]
+    fn tokens_test() {
+        let mut v = b"  ".to_vec();
+        for _ in 0..CHUNK_SIZE {
+            v.extend_from_slice(b"\x82\xa0 \r\n");
+        }
+        let reader = TextReader::new(&v[..], WINDOWS_31J, DecoderTrap::Strict);
+        let tokens: Vec<_> = reader.tokens().map(|t| t.unwrap()).collect();
+        assert_eq!(tokens.len(), CHUNK_SIZE);
+        assert!(tokens.iter().all(|t| t == "あ"));
+        let reader = TextReader::new(&b"a\xa0b\xffc"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut tokens = reader.tokens();
+        assert!(tokens.next().unwrap().is_err());
+    }
+    #[test]
+    fn split_byte_test() {
+        let reader = TextReader::new(&b"a\x1eb\x1e"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let records: Vec<_> = reader.split_byte(0x1e).unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(records, ["a", "b"]);
+        assert!(TextReader::new(&b""[..], WINDOWS_31J, DecoderTrap::Strict).split_byte(b';').is_err());
+        let utf16 = encoding_from_whatwg_label("utf-16le").unwrap();
+        assert!(TextReader::new(&b""[..], utf16, DecoderTrap::Strict).split_nul().is_err());
+    }
+    #[test]
+    fn binary_detection() {
+        let mut reader = TextReader::new(&b"GIF89a\x01\0\x01\0"[..], WINDOWS_31J, DecoderTrap::Replace);
+        reader.set_binary_detection(true);
+        assert!(matches!(reader.lines().next(), Some(Err(Error::BinaryData))));
+        let mut reader = TextReader::new(&b"\x82\xa0\t\x1b\r\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+        reader.set_binary_detection(true);
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "あ\t\x1b\r\n");
+        let utf16 = encoding_from_whatwg_label("utf-16le").unwrap();
+        let mut reader = TextReader::new(&b"a\0"[..], utf16, DecoderTrap::Strict);
+        reader.set_binary_detection(true);
+        assert_eq!(reader.read_char().unwrap(), Some('a'));
+    }
+    #[test]
+    fn peek_line_test() {
+        let mut reader = TextReader::new(&b"a\r\n\x82\xa0\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+        assert_eq!(reader.peek_line().unwrap(), Some("a\r\n"));
+        assert_eq!(reader.peek_line().unwrap(), Some("a\r\n"));
+        assert_eq!(reader.position(), Position { byte: 0, line: 1, column: 1 });
+        let mut lines = reader.lines_mut();
+        assert_eq!(lines.next().unwrap().unwrap(), "a");
+        assert_eq!(reader.peek_line().unwrap(), Some("あ\n"));
+        assert_eq!(reader.read_char().unwrap(), Some('あ'));
+        assert_eq!(reader.peek_line().unwrap(), Some("\n"));
+        assert_eq!(reader.lines().collect::<Vec<_>>().len(), 1);
+        let mut reader = TextReader::new(&b""[..], WINDOWS_31J, DecoderTrap::Strict);
+        assert_eq!(reader.peek_line().unwrap(), None);
+    }
+    #[test]
+    fn peek_char_test() {
+        let mut reader = TextReader::new(&b"\x82\xa0b"[..], WINDOWS_31J, DecoderTrap::Strict);
+        assert_eq!(reader.peek_char().unwrap(), Some('あ'));
+        assert_eq!(reader.peek_char().unwrap(), Some('あ'));
+        assert_eq!(reader.position().column, 1);
+        assert_eq!(reader.read_char().unwrap(), Some('あ'));
+        assert_eq!(reader.peek_char().unwrap(), Some('b'));
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "b");
+        assert_eq!(reader.peek_char().unwrap(), None);
+        let mut reader = TextReader::new(&b"\xff"[..], WINDOWS_31J, DecoderTrap::Strict);
+        assert!(reader.peek_char().is_err());
+    }
+    #[test]
+    fn unread_test() {
+        let mut reader = TextReader::new(&b"ab\ncd"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        reader.read_line(&mut s).unwrap();
+        reader.unread("b\n");
+        reader.unread("x");
+        assert_eq!(reader.position().byte, 0);
+        s.clear();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "xb\ncd");
+        reader.unread("\u{3042}");
+        assert_eq!(reader.read_char().unwrap(), Some('あ'));
+        assert_eq!(reader.read_char().unwrap(), None);
+    }
+    #[test]
+    fn skip_test() {
+        let mut v = vec![];
+        for _ in 0..CHUNK_SIZE {
+            v.extend_from_slice(b"\x82\xa0\n");
+        }
+        v.extend_from_slice(b"end");
+        let mut reader = TextReader::new(&v[..], WINDOWS_31J, DecoderTrap::Strict);
+        assert_eq!(reader.skip_lines(CHUNK_SIZE - 1).unwrap(), CHUNK_SIZE - 1);
+        assert_eq!(reader.skip_chars(3).unwrap(), 3);
+        assert_eq!(reader.position().line, CHUNK_SIZE as u64 + 1);
+        assert_eq!(reader.position().column, 2);
+        assert_eq!(reader.skip_chars(5).unwrap(), 2);
+        assert_eq!(reader.skip_lines(1).unwrap(), 0);
+    }
+    #[test]
+    fn read_chars_test() {
+        let mut v = vec![];
+        for _ in 0..CHUNK_SIZE {
+            v.extend_from_slice(b"\x82\xa0a");
+        }
+        let mut reader = TextReader::new(&v[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        assert_eq!(reader.read_chars(CHUNK_SIZE + 1, &mut s).unwrap(), CHUNK_SIZE + 1);
+        assert_eq!(s.chars().count(), CHUNK_SIZE + 1);
+        assert!(s.ends_with('あ'));
+        s.clear();
+        assert_eq!(reader.read_chars(CHUNK_SIZE * 2, &mut s).unwrap(), CHUNK_SIZE - 1);
+        assert!(s.starts_with("aあ"));
+        assert_eq!(reader.read_chars(1, &mut s).unwrap(), 0);
+    }
+    #[test]
+    fn take_chars_test() {
+        let reader = TextReader::new(&b"ab\r\ncd\n\x82\xa0"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut take = reader.take_chars(5);
+        let mut s = String::new();
+        assert_eq!(take.read_line(&mut s).unwrap(), 4);
+        assert_eq!(s, "ab\r\n");
+        s.clear();
+        assert_eq!(take.read_line(&mut s).unwrap(), 1);
+        assert_eq!(s, "c");
+        assert_eq!(take.read_char().unwrap(), None);
+        take.set_limit(10);
+        assert_eq!(take.by_ref().map(|c| c.unwrap()).collect::<String>(), "d\nあ");
+        assert_eq!(take.limit(), 7);
+        let reader = take.into_inner();
+        assert_eq!(reader.position(), Position { byte: 9, line: 3, column: 2 });
+        let reader = TextReader::new(&b"ab\xffcdef"[..], ASCII, DecoderTrap::Strict);
+        let mut take = reader.take_chars(5);
+        let mut s = String::new();
+        assert!(take.read_to_end(&mut s).is_err());
+        assert_eq!(s, "ab");
+        assert_eq!(take.limit(), 3);
+    }
+    #[test]
+    fn std_names() {
+        let mut reader = TextReader::new(Cursor::new(b"ab".to_vec()), WINDOWS_31J, DecoderTrap::Strict);
+        reader.get_mut().get_mut().set_position(1);
+        assert_eq!(reader.get_ref().get_ref().position(), 1);
+        let mut s = String::new();
+        reader.read_to_string(&mut s).unwrap();
+        assert_eq!(s, "b");
+        assert_eq!(reader.into_inner().into_inner().into_inner(), b"ab");
+    }
+    #[test]
+    fn from_bufread_small_buffer() {
+        let sjis = b"a\x82\xa0\x82\xa2\n\x82\xa4\xff\x82\xa6";
+        let bufreader = io::BufReader::with_capacity(3, &sjis[..]);
+        let mut reader = TextReader::from_bufread(bufreader, WINDOWS_31J, DecoderTrap::Strict);
+        let (s, err, rest) = reader.read_to_string_partial();
+        assert_eq!(s, "aあい\nう");
+        match err {
+            Some(Error::DecodeError { position, .. }) => {
+                assert_eq!(position, Position { byte: 8, line: 2, column: 2 });
+            },
+            _ => panic!("expected DecodeError"),
+        }
+        assert_eq!(rest, b"\xff\x82\xa6");
+        let bufreader = io::BufReader::with_capacity(1, &sjis[..8]);
+        let mut reader = TextReader::from_bufread(bufreader, WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "aあい\nう");
+    }
+    #[test]
+    fn into_parts_test() {
+        let bufreader = io::BufReader::with_capacity(4, &b"a\nb\x82\xa0\x82cd"[..]);
+        let mut reader = TextReader::from_bufread(bufreader, WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        reader.read_line(&mut s).unwrap();
+        let (mut rest, bytes, text) = reader.into_parts();
+        assert_eq!(text, "b");
+        assert_eq!(bytes, b"\x82");
+        let mut tail = vec![];
+        rest.read_to_end(&mut tail).unwrap();
+        assert_eq!(tail, b"\xa0\x82cd");
+    }
+    #[test]
+    fn read_line_from_textbuf() {
+        let mut reader = TextReader::new(&b"a\nb\r\nc"[..], WINDOWS_31J, DecoderTrap::Strict);
+        reader.set_newline_policy(NewlinePolicy::Universal);
+        let mut s = String::new();
+        reader.read_line(&mut s).unwrap();
+        assert_eq!((&reader.textbuf[..], reader.textpos), ("b\r\nc", 0));
+        reader.read_line(&mut s).unwrap();
+        assert_eq!((&reader.textbuf[..], reader.textpos), ("b\r\nc", 3));
+        reader.read_line(&mut s).unwrap();
+        assert_eq!(s, "a\nb\r\nc");
+    }
+    #[test]
+    fn split_sequence_leaves_binbuf() {
+        let mut bytes = b"a".to_vec();
+        for _ in 0..8 {
+            bytes.extend_from_slice(b"\x82\xa0");
+        }
+        bytes.extend_from_slice(b"bcdefghijklmnop");
+        let bufreader = io::BufReader::with_capacity(16, &bytes[..]);
+        let mut reader = TextReader::from_bufread(bufreader, WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        for _ in 0..9 {
+            s.push(reader.read_char().unwrap().unwrap());
+        }
+        assert_eq!(s, "aああああああああ");
+        assert!(reader.binbuf.is_empty());
+        assert_eq!(reader.get_ref().buffer(), b"bcdefghijklmnop");
+        s.clear();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "bcdefghijklmnop");
+    }
+    #[test]
+    #[cfg(feature = "japanese")]
+    fn stateful_across_chunks() {
+        use encoding::all::ISO_2022_JP;
+        use encoding::Encoding;
+        let text = "aあい\nうえお";
+        let bytes = ISO_2022_JP.encode(text, EncoderTrap::Strict).unwrap();
+        for capacity in 1..bytes.len() + 1 {
+            let bufreader = io::BufReader::with_capacity(capacity, &bytes[..]);
+            let mut reader = TextReader::from_bufread(bufreader, ISO_2022_JP, DecoderTrap::Strict);
+            let mut s = String::new();
+            reader.read_to_end(&mut s).unwrap();
+            assert_eq!(s, text);
+            assert_eq!(reader.position().byte, bytes.len() as u64);
+            let bufreader = io::BufReader::with_capacity(capacity, &bytes[..]);
+            let lines = TextReaderBuilder::new(ISO_2022_JP).chunk_size(capacity).build(bufreader).lines();
+            assert_eq!(lines.map(|l| l.unwrap()).collect::<Vec<_>>(), ["aあい", "うえお"]);
+        }
+        for capacity in [1, 2, 8192] {
+            let bufreader = io::BufReader::with_capacity(capacity, &b"a\x1b$B\x30"[..]);
+            let mut reader = TextReader::from_bufread(bufreader, ISO_2022_JP, DecoderTrap::Strict);
+            let mut s = String::new();
+            assert!(matches!(reader.read_to_end(&mut s), Err(Error::IncompleteSequence(_))));
+            assert_eq!(s, "a");
+            let invalid = b"\x1b$B\x30\x22\x1b$Ax\x1b(Bb";
+            let bufreader = io::BufReader::with_capacity(capacity, &invalid[..]);
+            let mut reader = TextReader::from_bufread(bufreader, ISO_2022_JP, DecoderTrap::Replace);
+            let mut s = String::new();
+            reader.read_to_end(&mut s).unwrap();
+            assert_eq!(s, ISO_2022_JP.decode(invalid, DecoderTrap::Replace).unwrap());
+        }
+    }
+    #[test]
+    #[cfg(feature = "japanese")]
+    fn reset_at_newline_test() {
+        use encoding::all::ISO_2022_JP;
+        // Katakana shifted at every line, and an escape sequence cut by a line terminator.
+        let bytes = b"\x1b(I\x31\n\x1b(I\x32\nab\x1b$\nc";
+        for capacity in [1, 2, 3, 8192] {
+            let bufreader = io::BufReader::with_capacity(capacity, &bytes[..]);
+            let mut reader = TextReaderBuilder::new(ISO_2022_JP).trap(DecoderTrap::Replace).reset_at_newline(true)
+                .build_bufread(bufreader);
+            let mut s = String::new();
+            reader.read_to_end(&mut s).unwrap();
+            assert_eq!(s, "\u{ff71}\n\u{ff72}\nab\u{fffd}$\nc");
+            assert_eq!(reader.position().byte, bytes.len() as u64);
+            let bufreader = io::BufReader::with_capacity(capacity, &bytes[..]);
+            let mut reader = TextReader::from_bufread(bufreader, ISO_2022_JP, DecoderTrap::Strict);
+            assert!(!reader.reset_at_newline());
+            assert!(reader.read_to_end(&mut String::new()).is_err());
+        }
+    }
+    #[test]
+    fn seek_test() {
+        let sjis_aiueo = [0x82, 0xa0, 0x82, 0xa2, 0x82, 0xa4, 0x82, 0xa6, 0x82, 0xa8];
+        let mut v = vec![];
+        v.extend_from_slice(&sjis_aiueo);
+        v.push(10);
+        v.extend_from_slice("abcd".as_bytes());
+        let mut reader = TextReader::new(Cursor::new(v), encoding_from_whatwg_label("sjis").unwrap(), DecoderTrap::Strict);
+        let mut s = String::new();
+        assert!(reader.read_line(&mut s).is_ok());
+        assert_eq!(reader.seek(SeekFrom::Start(4)).unwrap(), 4);
+        s.clear();
+        assert!(reader.read_to_end(&mut s).is_ok());
+        assert_eq!(s, "うえお\nabcd");
+        assert_eq!(reader.seek(SeekFrom::End(-2)).unwrap(), 13);
+        s.clear();
+        assert!(reader.read_to_end(&mut s).is_ok());
+        assert_eq!(s, "cd");
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        s.clear();
+        assert!(reader.read_line(&mut s).is_ok());
+        assert_eq!(reader.seek(SeekFrom::Current(0)).unwrap(), 11);
+        assert_eq!(reader.seek(SeekFrom::Current(-1)).unwrap(), 10);
+        s.clear();
+        assert!(reader.read_to_end(&mut s).is_ok());
+        assert_eq!(s, "\nabcd");
+        assert!(reader.seek(SeekFrom::Current(-16)).is_err());
+        assert_eq!(reader.position().byte, 15);
+    }
+    #[test]
     fn lines_test() {
         let sjis_aiueo = [0x82, 0xa0, 0x82, 0xa2, 0x82, 0xa4, 0x82, 0xa6, 0x82, 0xa8];
         let mut v = vec![];
@@ -408,4 +2852,15 @@ mod tests {
         assert_eq!(res.pop().unwrap().unwrap(), "あいうえお"); // res[1]
         assert_eq!(res.pop().unwrap().unwrap(), "あいうえお"); // res[0]
     }
+    #[test]
+    fn debug_test() {
+        let mut reader = TextReader::new(&b"a\n\x82\xa0b\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        reader.read_line(&mut s).unwrap();
+        assert_eq!(format!("{:?}", reader), "TextReader { encoding: \"windows-31j\", buffered_bytes: 0, \
+            buffered_chars: 3, position: Position { byte: 2, line: 2, column: 1 }, .. }");
+        let take = reader.take_chars(1);
+        assert!(format!("{:?}", take).starts_with("TakeChars { textreader: TextReader { encoding:"));
+        assert!(format!("{:?}", take).ends_with(", limit: 1 }"));
+    }
 }
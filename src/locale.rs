@@ -0,0 +1,100 @@
+//! The default encoding of the platform and the locale.
+
+#[cfg(not(windows))]
+use std::env;
+use std::io::{BufReader, Read};
+
+use encoding::all::UTF_8;
+use encoding::{DecoderTrap, EncodingRef};
+
+use crate::label::encoding_from_whatwg_label;
+use crate::TextReader;
+
+/// Returns the encoding of the codeset of a POSIX locale name, such as `ja_JP.eucJP` or
+/// `ru_RU.KOI8-R@modifier`, if known to `textstream::label`.
+/// Locales without a codeset, such as `C` and `POSIX`, and those in ASCII are taken for UTF-8,
+/// its superset, as Python does.
+///
+/// # Examples
+/// ```
+/// use textstream::encoding_from_locale;
+/// assert_eq!(encoding_from_locale("en_US.UTF-8").map(|e| e.name()), Some("utf-8"));
+/// assert_eq!(encoding_from_locale("C").map(|e| e.name()), Some("utf-8"));
+/// assert!(encoding_from_locale("en_US.X-UNKNOWN").is_none());
+/// ```
+pub fn encoding_from_locale(locale: &str) -> Option<EncodingRef> {
+    let locale = locale.split('@').next().unwrap_or_default();
+    let codeset = match locale.split_once('.') {
+        Some((_, codeset)) => codeset.to_ascii_lowercase(),
+        None => return Some(UTF_8),
+    };
+    // The names of glibc and the other C libraries which are not WHATWG labels.
+    let label = match &codeset[..] {
+        "ansi_x3.4-1968" | "ascii" | "646" => "utf-8",
+        "eucjp" | "ujis" => "euc-jp",
+        "pck" => "shift_jis",
+        "euckr" => "euc-kr",
+        "euccn" => "gbk",
+        codeset => codeset,
+    };
+    encoding_from_whatwg_label(label)
+}
+
+/// Returns the default encoding of text files on this platform, like `open()` of Python:
+/// the ANSI code page on Windows, and the codeset of the locale, from the `LC_ALL`,
+/// `LC_CTYPE` or `LANG` environment variables, on the others.
+/// Falls back to UTF-8 if the encoding is unknown.
+pub fn native_encoding() -> EncodingRef {
+    #[cfg(windows)]
+    let encoding = crate::windows::ansi_encoding();
+    #[cfg(not(windows))]
+    let encoding = ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+        .filter_map(|name| env::var(name).ok())
+        .find(|locale| !locale.is_empty())
+        .map_or(Some(UTF_8 as EncodingRef), |locale| encoding_from_locale(&locale));
+    encoding.unwrap_or(UTF_8)
+}
+
+impl<R: Read> TextReader<BufReader<R>> {
+    /// Creates a new `TextReader` with the default encoding of the platform and the locale.
+    /// Please see `native_encoding`.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::native(&b"abc"[..], DecoderTrap::Replace);
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s)?;
+    /// assert_eq!(s, "abc");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn native(reader: R, trap: DecoderTrap) -> TextReader<BufReader<R>> {
+        TextReader::new(reader, native_encoding(), trap)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locale_encoding() {
+        let name = |locale| encoding_from_locale(locale).map(|e| e.name());
+        assert_eq!(name("en_US.UTF-8"), Some("utf-8"));
+        assert_eq!(name("de_DE.utf8@euro"), Some("utf-8"));
+        assert_eq!(name("POSIX"), Some("utf-8"));
+        assert_eq!(name("C.ANSI_X3.4-1968"), Some("utf-8"));
+        assert_eq!(name("xx_XX.unknown"), None);
+        #[cfg(feature = "japanese")]
+        {
+            assert_eq!(name("ja_JP.eucJP"), Some("euc-jp"));
+            assert_eq!(name("ja_JP.SJIS"), Some("windows-31j"));
+        }
+        #[cfg(feature = "singlebyte")]
+        assert_eq!(name("ru_RU.KOI8-R"), Some("koi8-r"));
+    }
+}
@@ -0,0 +1,105 @@
+//! Best-effort decoding, replacing invalid sequences.
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read};
+
+use encoding::{DecoderTrap, EncodingRef};
+
+use crate::TextReader;
+
+impl<R: Read> TextReader<BufReader<R>> {
+    /// Creates a new `TextReader` with `DecoderTrap::Replace`, which decodes invalid sequences
+    /// into U+FFFD instead of failing.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::lossy(&b"\x82\xa0\xff"[..], WINDOWS_31J);
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s)?;
+    /// assert_eq!(s, "あ\u{fffd}");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn lossy(reader: R, encoding: EncodingRef) -> TextReader<BufReader<R>> {
+        TextReader::new(reader, encoding, DecoderTrap::Replace)
+    }
+}
+
+impl<R: BufRead> TextReader<R> {
+    /// Returns an iterator over the lines of this reader, like `lines()`, decoding invalid
+    /// sequences into U+FFFD whatever the trap of this reader is. The trap handler, if any, is
+    /// removed. The iterator returned from this function will yield instances of
+    /// `io::Result<String>`, failing only on errors of the underlying reader, and of the checks
+    /// enabled on this reader, such as the maximum line length.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::EUC_JP;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> std::io::Result<()> {
+    /// let reader = TextReader::new(&b"GET /\xa4\xa2\nGET /\xff\n"[..], EUC_JP, DecoderTrap::Strict);
+    /// for line in reader.lines_lossy() {
+    ///     println!("{}", line?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn lines_lossy(mut self) -> LinesLossy<R> {
+        self.trap = DecoderTrap::Replace;
+        self.trap_handler = None;
+        LinesLossy { textreader: self }
+    }
+}
+
+/// An iterator over the lines of an `TextReader`, decoded with invalid sequences replaced.
+/// This struct is generally created by calling `lines_lossy()` on a `TextReader`. Please see
+/// the documentation of `lines_lossy()` for more details.
+pub struct LinesLossy<R: BufRead> {
+    textreader: TextReader<R>
+}
+impl<R: BufRead> LinesLossy<R> {
+    /// Unwraps this `LinesLossy`, returning the underlying `TextReader`, which keeps
+    /// `DecoderTrap::Replace`.
+    pub fn into_inner(self) -> TextReader<R> { self.textreader }
+}
+impl<R: BufRead> Iterator for LinesLossy<R> {
+    type Item = io::Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.textreader.next_line().map(|line| line.map_err(io::Error::from))
+    }
+}
+impl<R: BufRead> fmt::Debug for LinesLossy<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LinesLossy").field("textreader", &self.textreader).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::all::{UTF_8, WINDOWS_31J};
+
+    #[test]
+    fn lossy() {
+        let mut reader = TextReader::lossy(&b"a\xff\n\x82"[..], WINDOWS_31J);
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "a\u{fffd}\n\u{fffd}");
+    }
+    #[test]
+    fn lines_lossy() {
+        let mut reader = TextReader::new(&b"a\xff\n\xe3\x81\x82\nb\xe3"[..], UTF_8, DecoderTrap::Strict);
+        reader.set_trap_handler(|_, _| false);
+        let lines = reader.lines_lossy().collect::<io::Result<Vec<_>>>().unwrap();
+        assert_eq!(lines, ["a\u{fffd}", "あ", "b\u{fffd}"]);
+        let mut reader = TextReader::new(&b"abc\nd\n"[..], UTF_8, DecoderTrap::Strict);
+        reader.set_max_line_length(Some(2));
+        assert!(reader.lines_lossy().next().unwrap().is_err());
+    }
+}
@@ -0,0 +1,67 @@
+//! Memory mapped files.
+
+use std::fs::File;
+use std::io::{self, Cursor};
+use std::path::Path;
+
+use encoding::{DecoderTrap, EncodingRef};
+use memmap2::Mmap;
+
+use crate::TextReader;
+
+impl TextReader<Cursor<Mmap>> {
+    /// Opens a file in read-only mode and creates a new `TextReader` decoding directly from a
+    /// memory mapping of it, without read system calls.
+    ///
+    /// The mapping is `get_ref().get_ref()`. Its bytes can also be decoded by other readers,
+    /// such as `par_lines()` on a `TextReader` of the slice.
+    ///
+    /// # Safety
+    /// The file must not be modified or truncated while it is mapped, by this process or
+    /// another one. Please see `memmap2::Mmap::map`.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// // The log is rotated rather than written in place.
+    /// let mut reader = unsafe { TextReader::open_mmap("shiftjis.txt", WINDOWS_31J, DecoderTrap::Strict)? };
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s)?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() { let _ = foo(); }
+    /// ```
+    pub unsafe fn open_mmap<P: AsRef<Path>>(path: P, encoding: EncodingRef, trap: DecoderTrap) -> io::Result<TextReader<Cursor<Mmap>>> {
+        let file = File::open(path)?;
+        let mmap = Mmap::map(&file)?;
+        Ok(TextReader::from_bufread(Cursor::new(mmap), encoding, trap))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, fs, process};
+    use std::io::SeekFrom;
+    use encoding::all::WINDOWS_31J;
+
+    #[test]
+    fn open_mmap_test() {
+        let path = env::temp_dir().join(format!("textstream-mmap-{}.txt", process::id()));
+        fs::write(&path, b"\x82\xa0\n\x82\xa2\n").unwrap();
+        let mut reader = unsafe { TextReader::open_mmap(&path, WINDOWS_31J, DecoderTrap::Strict).unwrap() };
+        assert_eq!(&reader.get_ref().get_ref()[..], b"\x82\xa0\n\x82\xa2\n");
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "あ\nい\n");
+        reader.seek(SeekFrom::Start(3)).unwrap();
+        assert_eq!(reader.lines().next().unwrap().unwrap(), "い");
+        fs::write(&path, b"").unwrap();
+        let mut reader = unsafe { TextReader::open_mmap(&path, WINDOWS_31J, DecoderTrap::Strict).unwrap() };
+        assert_eq!(reader.read_char().unwrap(), None);
+        fs::remove_file(&path).unwrap();
+    }
+}
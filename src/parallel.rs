@@ -0,0 +1,229 @@
+//! Parallel decoding of byte slices.
+
+use std::vec;
+
+use memchr::memchr;
+use rayon::prelude::*;
+
+use crate::{EncodingInfo, Error, Position, Result, TextReader, TextReaderBuilder};
+
+/// Approximate number of bytes in a partition decoded by a thread.
+const PARTITION_SIZE: usize = 1024 * 1024;
+
+impl<'a> TextReader<&'a [u8]> {
+    /// Returns an iterator over the lines of this reader, like `lines()`, which decodes the
+    /// rest of the slice on all the threads of the rayon pool. A memory mapped file can be
+    /// read this way.
+    ///
+    /// The slice is split into partitions after `\n` bytes, which are decoded in parallel a few
+    /// at a time, and the lines are yielded in order. The positions of errors are the same as
+    /// those of `lines()`, and the iteration ends after an error.
+    ///
+    /// Fails with `InvalidInput` unless the `EncodingInfo` of the encoding is
+    /// `ascii_compatible` and not `stateful`.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let log = b"INFO a\nERROR \x82\xa0\nINFO b\n";
+    /// let reader = TextReader::from_bufread(&log[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let lines = reader.par_lines()?.collect::<textstream::Result<Vec<_>>>()?;
+    /// assert_eq!(lines, ["INFO a", "ERROR あ", "INFO b"]);
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn par_lines(self) -> Result<ParLines<'a>> {
+        self.par_lines_with(PARTITION_SIZE)
+    }
+
+    /// For internal use. `par_lines` with partitions of about `partition_size` bytes.
+    fn par_lines_with(self, partition_size: usize) -> Result<ParLines<'a>> {
+        EncodingInfo::require_line_splittable(self.encoding, "split lines in parallel")?;
+        let mut builder = TextReaderBuilder::new(self.encoding)
+            .trap(self.trap)
+            .chunk_size(self.chunk_size)
+            .newline_policy(self.newline);
+        if let Some(max) = self.max_line_length {
+            builder = builder.max_line_length(max);
+        }
+        Ok(ParLines {
+            rest: self.reader,
+            offset: self.consumed + self.binbuf.len() as u64,
+            line: 0,
+            first: true,
+            template: self,
+            builder,
+            partition_size,
+            lines: Vec::new().into_iter(),
+        })
+    }
+}
+
+/// An iterator over the lines of a `TextReader` decoded in parallel.
+/// This struct is generally created by calling `par_lines()` on a `TextReader`.
+/// Please see the documentation of `par_lines()` for more details.
+pub struct ParLines<'a> {
+    template: TextReader<&'a [u8]>,
+    builder: TextReaderBuilder,
+    rest: &'a [u8],
+    offset: u64,
+    line: u64,
+    first: bool,
+    partition_size: usize,
+    lines: vec::IntoIter<Result<String>>,
+}
+
+impl<'a> ParLines<'a> {
+    /// For internal use. Splits the next partition off `rest`, ending after a `\n` byte.
+    fn take_partition(&mut self) -> &'a [u8] {
+        let mut end = self.rest.len().min(self.partition_size);
+        if end < self.rest.len() {
+            end = memchr(b'\n', &self.rest[end..]).map_or(self.rest.len(), |i| end + i + 1);
+        }
+        let (partition, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        partition
+    }
+
+    /// For internal use. Decodes partitions for all the threads, and queues their lines.
+    /// The first partition is decoded by the original reader, with the text it has buffered.
+    fn next_wave(&mut self) {
+        let head = if self.first { Some(self.take_partition()) } else { None };
+        let mut partitions = Vec::new();
+        while partitions.len() < rayon::current_num_threads() * 2 && !self.rest.is_empty() {
+            partitions.push(self.take_partition());
+        }
+        let builder = self.builder;
+        let template = &mut self.template;
+        let (head_lines, partition_lines) = rayon::join(
+            || head.map(|head| {
+                template.reader = head;
+                read_partition(template)
+            }),
+            || partitions.par_iter()
+                .map(|&partition| read_partition(&mut builder.build_bufread(partition)))
+                .collect::<Vec<_>>(),
+        );
+        let mut lines = Vec::new();
+        if let (Some(head), Some(head_lines)) = (head, head_lines) {
+            self.first = false;
+            self.offset += head.len() as u64;
+            self.line = self.template.line;
+            if head_lines.last().is_some_and(|line| line.is_err()) {
+                self.rest = &[];
+                self.lines = head_lines.into_iter();
+                return;
+            }
+            lines = head_lines;
+        }
+        for (partition, partition_lines) in partitions.iter().zip(partition_lines) {
+            let mut n = 0;
+            for line in partition_lines {
+                match line {
+                    Ok(line) => {
+                        lines.push(Ok(line));
+                        n += 1;
+                    },
+                    Err(e) => {
+                        lines.push(Err(shift_error(e, self.offset, self.line)));
+                        self.rest = &[];
+                        self.lines = lines.into_iter();
+                        return;
+                    },
+                }
+            }
+            self.offset += partition.len() as u64;
+            self.line += n;
+        }
+        self.lines = lines.into_iter();
+    }
+}
+
+impl<'a> Iterator for ParLines<'a> {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.lines.next() {
+                return Some(line);
+            }
+            if !self.first && self.rest.is_empty() {
+                return None;
+            }
+            self.next_wave();
+        }
+    }
+}
+
+/// Reads the lines of a partition, up to the first error.
+fn read_partition(textreader: &mut TextReader<&[u8]>) -> Vec<Result<String>> {
+    let mut lines = Vec::new();
+    while let Some(line) = textreader.next_line() {
+        let failed = line.is_err();
+        lines.push(line);
+        if failed {
+            break;
+        }
+    }
+    lines
+}
+
+/// Moves the position of an error in a partition by the offset and the line of the partition.
+fn shift_error(e: Error, offset: u64, line: u64) -> Error {
+    let shift = |position: Position| Position {
+        byte: position.byte + offset,
+        line: position.line + line,
+        ..position
+    };
+    match e {
+        Error::DecodeError { cause, position } => Error::DecodeError { cause, position: shift(position) },
+        Error::IncompleteSequence(position) => Error::IncompleteSequence(shift(position)),
+        e => e,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::DecoderTrap;
+    use encoding::all::{UTF_16LE, WINDOWS_31J};
+
+    fn numbered_lines() -> Vec<u8> {
+        let mut v = vec![];
+        for i in 0..500 {
+            v.extend_from_slice(i.to_string().as_bytes());
+            v.extend_from_slice(b"\x82\xa0\r\n");
+        }
+        v
+    }
+    #[test]
+    fn par_lines_ordered() {
+        let v = numbered_lines();
+        let mut reader = TextReader::from_bufread(&v[..], WINDOWS_31J, DecoderTrap::Strict);
+        assert_eq!(reader.read_char().unwrap(), Some('0'));
+        let lines: Vec<String> = reader.par_lines_with(16).unwrap().map(|l| l.unwrap()).collect();
+        let mut expected: Vec<String> = (0..500).map(|i| format!("{}あ", i)).collect();
+        expected[0].remove(0);
+        assert_eq!(lines, expected);
+    }
+    #[test]
+    fn par_lines_error() {
+        let mut v = numbered_lines();
+        v.extend_from_slice(b"abc\xff\n");
+        v.extend_from_slice(&numbered_lines());
+        let reader = TextReader::from_bufread(&v[..], WINDOWS_31J, DecoderTrap::Strict);
+        let expected: Vec<_> = reader.lines().take(501).map(|l| l.map_err(|e| e.to_string())).collect();
+        let reader = TextReader::from_bufread(&v[..], WINDOWS_31J, DecoderTrap::Strict);
+        let lines: Vec<_> = reader.par_lines_with(16).unwrap().map(|l| l.map_err(|e| e.to_string())).collect();
+        assert_eq!(lines, expected);
+        assert!(expected[500].as_ref().unwrap_err().contains("line 501, column 4"));
+    }
+    #[test]
+    fn par_lines_not_splittable() {
+        let reader = TextReader::from_bufread(&b"a\x00\n\x00"[..], UTF_16LE, DecoderTrap::Strict);
+        assert!(reader.par_lines().is_err());
+    }
+}
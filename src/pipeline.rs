@@ -0,0 +1,143 @@
+//! Decoding on a background thread.
+
+use std::io::BufRead;
+use std::panic;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread::{self, JoinHandle};
+use std::vec;
+
+use crate::{Result, TextReader};
+
+impl<R: BufRead + Send + 'static> TextReader<R> {
+    /// Returns an iterator over the lines of this reader, which are read and decoded on a
+    /// background thread while the current thread processes the previous ones. Overlapping I/O
+    /// and decoding pays off for slow sources, such as network file systems or decompressors.
+    ///
+    /// Lines are sent in batches of about the chunk size, and at most `depth` batches are
+    /// decoded ahead. The lines are yielded like `lines()`, and the iteration ends after an
+    /// error. If reading panics, the panic is resumed on the thread iterating over the lines.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let reader = TextReader::new(&b"a\n\x82\xa0\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let lines: Vec<_> = reader.spawn_pipelined(4).map(|l| l.unwrap()).collect();
+    /// assert_eq!(lines, ["a", "あ"]);
+    /// # }
+    /// ```
+    pub fn spawn_pipelined(self, depth: usize) -> PipelinedLines {
+        let batch_size = self.chunk_size;
+        let (sender, receiver) = sync_channel(depth);
+        let worker = thread::spawn(move || {
+            let mut lines = self.lines();
+            loop {
+                let mut batch = Vec::new();
+                let mut size = 0;
+                let mut error = None;
+                while size < batch_size {
+                    match lines.next() {
+                        Some(Ok(line)) => {
+                            size += line.len() + 1;
+                            batch.push(line);
+                        },
+                        Some(Err(e)) => {
+                            error = Some(e);
+                            break;
+                        },
+                        None => break,
+                    }
+                }
+                let last = size < batch_size;
+                if !batch.is_empty() && sender.send(Ok(batch)).is_err() {
+                    return;
+                }
+                if let Some(e) = error {
+                    let _ = sender.send(Err(e));
+                    return;
+                }
+                if last {
+                    return;
+                }
+            }
+        });
+        PipelinedLines {
+            receiver,
+            batch: Vec::new().into_iter(),
+            worker: Some(worker),
+        }
+    }
+}
+
+/// An iterator over the lines of a `TextReader` decoded on a background thread.
+/// This struct is generally created by calling `spawn_pipelined()` on a `TextReader`.
+/// Please see the documentation of `spawn_pipelined()` for more details.
+pub struct PipelinedLines {
+    receiver: Receiver<Result<Vec<String>>>,
+    batch: vec::IntoIter<String>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Iterator for PipelinedLines {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(line) = self.batch.next() {
+                return Some(Ok(line));
+            }
+            match self.receiver.recv() {
+                Ok(Ok(batch)) => self.batch = batch.into_iter(),
+                Ok(Err(e)) => return Some(Err(e)),
+                Err(_) => {
+                    if let Some(Err(payload)) = self.worker.take().map(JoinHandle::join) {
+                        panic::resume_unwind(payload);
+                    }
+                    return None;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Read};
+    use encoding::DecoderTrap;
+    use encoding::all::WINDOWS_31J;
+
+    #[test]
+    fn pipelined_lines() {
+        let mut v = vec![];
+        for i in 0..2000 {
+            v.extend_from_slice(i.to_string().as_bytes());
+            v.extend_from_slice(b"\x82\xa0\n");
+        }
+        let expected: Vec<String> = (0..2000).map(|i| format!("{}あ", i)).collect();
+        let reader = TextReader::new(io::Cursor::new(v), WINDOWS_31J, DecoderTrap::Strict);
+        let lines: Vec<String> = reader.spawn_pipelined(2).map(|l| l.unwrap()).collect();
+        assert_eq!(lines, expected);
+    }
+    #[test]
+    fn pipelined_lines_error() {
+        let reader = TextReader::new(&b"a\nb\n\xff\nc\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut lines = reader.spawn_pipelined(0);
+        assert_eq!(lines.next().unwrap().unwrap(), "a");
+        assert_eq!(lines.next().unwrap().unwrap(), "b");
+        assert!(lines.next().unwrap().is_err());
+        assert!(lines.next().is_none());
+    }
+    #[test]
+    #[should_panic(expected = "boom")]
+    fn pipelined_lines_panic() {
+        struct Boom;
+        impl Read for Boom {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                panic!("boom")
+            }
+        }
+        for _ in TextReader::new(Boom, WINDOWS_31J, DecoderTrap::Strict).spawn_pipelined(1) {}
+    }
+}
@@ -0,0 +1,37 @@
+//! The types and functions needed to read and write text, for a glob import.
+//!
+//! The traps and the encodings of the `encoding` crate are re-exported, so that a direct
+//! dependency on it is not needed for common uses. The encodings are those of the families
+//! enabled by cargo features, like `textstream::label`. `textstream::Result` and
+//! `textstream::Error` are left out, not to shadow `std::result::Result`.
+//!
+//! # Examples
+//! ```
+//! use textstream::prelude::*;
+//! # fn foo() -> textstream::Result<()> {
+//! let mut reader = TextReader::new(&b"\x82\xa0\n"[..], encoding_from_whatwg_label("utf-8").unwrap(), DecoderTrap::Replace);
+//! let mut writer = TextWriter::new(Vec::new(), UTF_16LE, EncoderTrap::Strict);
+//! for line in reader.lines_mut() {
+//!     writer.write_line(&line?)?;
+//! }
+//! assert_eq!(writer.finish()?, b"\xfd\xff\xfd\xff\n\x00");
+//! # Ok(())
+//! # }
+//! # fn main() { foo().unwrap(); }
+//! ```
+
+pub use encoding::{DecoderTrap, EncoderTrap, Encoding, EncodingRef};
+pub use encoding::all::{ASCII, UTF_16BE, UTF_16LE, UTF_8};
+#[cfg(feature = "singlebyte")]
+pub use encoding::all::WINDOWS_1252;
+#[cfg(feature = "japanese")]
+pub use encoding::all::{EUC_JP, ISO_2022_JP, WINDOWS_31J};
+#[cfg(feature = "korean")]
+pub use encoding::all::WINDOWS_949;
+#[cfg(feature = "simpchinese")]
+pub use encoding::all::{GB18030, GBK};
+#[cfg(feature = "tradchinese")]
+pub use encoding::all::BIG5_2003;
+
+pub use crate::label::encoding_from_whatwg_label;
+pub use crate::{LineTerminator, TextReader, TextReaderBuilder, TextWriter};
@@ -0,0 +1,160 @@
+//! Decoding the output of child processes.
+
+use std::io::{self, BufReader};
+use std::process::{Child, ChildStderr, ChildStdout, Command, ExitStatus, Output, Stdio};
+
+use encoding::{DecoderTrap, EncodingRef};
+
+use crate::{Result, TextReader};
+
+#[cfg(windows)]
+use crate::Error;
+
+/// The output of a finished child process, decoded. Please see `decode_output`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DecodedOutput {
+    /// The exit status of the process.
+    pub status: ExitStatus,
+    /// The text written to the standard output.
+    pub stdout: String,
+    /// The text written to the standard error.
+    pub stderr: String,
+}
+
+/// Decodes the standard output and the standard error of a finished child process.
+///
+/// # Examples
+/// ```no_run
+/// use std::process::Command;
+/// use encoding::all::WINDOWS_31J;
+/// use encoding::DecoderTrap;
+/// use textstream::decode_output;
+/// # fn foo() -> textstream::Result<()> {
+/// let output = Command::new("ping").arg("localhost").output()?;
+/// let output = decode_output(output, WINDOWS_31J, DecoderTrap::Replace)?;
+/// println!("{}", output.stdout);
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub fn decode_output(output: Output, encoding: EncodingRef, trap: DecoderTrap) -> Result<DecodedOutput> {
+    let decode = |bytes: &[u8]| {
+        let mut s = String::with_capacity(bytes.len());
+        TextReader::from_bufread(bytes, encoding, trap).read_to_end(&mut s)?;
+        Ok::<_, crate::Error>(s)
+    };
+    Ok(DecodedOutput {
+        status: output.status,
+        stdout: decode(&output.stdout)?,
+        stderr: decode(&output.stderr)?,
+    })
+}
+
+/// A child process whose standard output and standard error are read as text.
+/// This struct is generally created by calling `spawn_text()` on a `Command`.
+///
+/// Reading one of the pipes to the end while the child process fills the other may block
+/// forever. Read them on different threads, or use `output_text()` to read both.
+pub struct TextChild {
+    /// The child process, without its standard output and standard error.
+    pub child: Child,
+    /// The standard output of the child process.
+    pub stdout: TextReader<BufReader<ChildStdout>>,
+    /// The standard error of the child process.
+    pub stderr: TextReader<BufReader<ChildStderr>>,
+}
+
+/// Extensions of `Command` to decode the output of the child process.
+pub trait CommandExt {
+    /// Spawns the command with its standard output and standard error piped, and wraps the
+    /// pipes in `TextReader`s.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::process::Command;
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::CommandExt;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut child = Command::new("ping").arg("localhost").spawn_text(WINDOWS_31J, DecoderTrap::Replace)?;
+    /// for line in child.stdout.lines() {
+    ///     println!("{}", line?);
+    /// }
+    /// child.child.wait()?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    fn spawn_text(&mut self, encoding: EncodingRef, trap: DecoderTrap) -> io::Result<TextChild>;
+
+    /// Runs the command to the end, like `Command::output`, and decodes its output.
+    fn output_text(&mut self, encoding: EncodingRef, trap: DecoderTrap) -> Result<DecodedOutput>;
+
+    /// `spawn_text` with the OEM code page of the system, which console programs such as
+    /// `ping` and `dir` use for their output.
+    ///
+    /// Fails with `Error::UnknownEncoding` if the code page is unknown to
+    /// `windows::encoding_from_code_page`.
+    #[cfg(windows)]
+    fn spawn_oem(&mut self, trap: DecoderTrap) -> Result<TextChild> {
+        let encoding = oem_encoding()?;
+        Ok(self.spawn_text(encoding, trap)?)
+    }
+
+    /// `output_text` with the OEM code page of the system. Please see `spawn_oem`.
+    #[cfg(windows)]
+    fn output_oem(&mut self, trap: DecoderTrap) -> Result<DecodedOutput> {
+        let encoding = oem_encoding()?;
+        self.output_text(encoding, trap)
+    }
+}
+
+impl CommandExt for Command {
+    fn spawn_text(&mut self, encoding: EncodingRef, trap: DecoderTrap) -> io::Result<TextChild> {
+        let mut child = self.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        Ok(TextChild {
+            child,
+            stdout: TextReader::new(stdout, encoding, trap),
+            stderr: TextReader::new(stderr, encoding, trap),
+        })
+    }
+
+    fn output_text(&mut self, encoding: EncodingRef, trap: DecoderTrap) -> Result<DecodedOutput> {
+        decode_output(self.output()?, encoding, trap)
+    }
+}
+
+/// For internal use. Returns the encoding of the OEM code page.
+#[cfg(windows)]
+fn oem_encoding() -> Result<EncodingRef> {
+    let code_page = crate::windows::oem_code_page();
+    crate::windows::encoding_from_code_page(code_page)
+        .ok_or_else(|| Error::UnknownEncoding(format!("cp{}", code_page)))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use encoding::all::UTF_16LE;
+
+    #[test]
+    fn output_text() {
+        let output = Command::new("sh").args(["-c", r"printf 'a\000\n\000'; printf 'b\000' >&2; exit 3"])
+            .output_text(UTF_16LE, DecoderTrap::Strict).unwrap();
+        assert_eq!(output.stdout, "a\n");
+        assert_eq!(output.stderr, "b");
+        assert_eq!(output.status.code(), Some(3));
+        let output = Command::new("sh").args(["-c", r"printf 'a'"]).output_text(UTF_16LE, DecoderTrap::Strict);
+        assert!(output.is_err());
+    }
+    #[test]
+    fn spawn_text() {
+        let mut child = Command::new("sh").args(["-c", r"printf 'a\000\n\000b\000'"])
+            .spawn_text(UTF_16LE, DecoderTrap::Strict).unwrap();
+        let lines = child.stdout.lines().collect::<Result<Vec<_>>>().unwrap();
+        assert_eq!(lines, ["a", "b"]);
+        assert!(child.child.wait().unwrap().success());
+    }
+}
@@ -0,0 +1,108 @@
+//! Reports of invalid sequences handled by traps.
+
+use std::io::BufRead;
+
+use crate::TextReader;
+
+/// Maximum number of offsets kept by `DecodeReport`.
+const MAX_OFFSETS: usize = 1024;
+
+/// The invalid sequences which the trap of a `TextReader` has handled, such as those replaced by
+/// `DecoderTrap::Replace`. Please see `TextReader::decode_report`.
+///
+/// With `DecoderTrap::Ignore`, which drops invalid sequences, the report tells how much was
+/// dropped, such as for a metric of a job which wants clean output rather than an error.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct DecodeReport {
+    count: u64,
+    offsets: Vec<u64>,
+    last: Option<u64>,
+}
+
+impl DecodeReport {
+    /// Returns the number of invalid sequences.
+    pub fn count(&self) -> u64 { self.count }
+
+    /// Returns whether no invalid sequence has been found.
+    pub fn is_empty(&self) -> bool { self.count == 0 }
+
+    /// Returns the byte offsets of the first 1024 invalid sequences.
+    pub fn offsets(&self) -> &[u64] { &self.offsets }
+
+    /// Returns the byte offset of the first invalid sequence.
+    pub fn first(&self) -> Option<u64> { self.offsets.first().copied() }
+
+    /// Returns the byte offset of the last invalid sequence, which may be beyond `offsets`.
+    pub fn last(&self) -> Option<u64> { self.last }
+
+    /// For internal use. Records an invalid sequence at `offset`.
+    pub(crate) fn record(&mut self, offset: u64) {
+        self.count += 1;
+        self.last = Some(offset);
+        if self.offsets.len() < MAX_OFFSETS {
+            self.offsets.push(offset);
+        }
+    }
+}
+
+impl<R: BufRead> TextReader<R> {
+    /// Returns the report of the invalid sequences which the trap has handled since this reader
+    /// was created, telling how lossy the text returned so far is.
+    /// Sequences decoded ahead into the buffer of this reader are included. `DecoderTrap::Strict`
+    /// fails instead, leaving the report empty.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::new(&b"a\xff\n\x82\xa0\xfd"[..], WINDOWS_31J, DecoderTrap::Replace);
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s)?;
+    /// assert_eq!(s, "a\u{fffd}\nあ\u{fffd}");
+    /// assert_eq!(reader.decode_report().count(), 2);
+    /// assert_eq!(reader.decode_report().offsets(), [1, 5]);
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn decode_report(&self) -> &DecodeReport { &self.report }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::DecoderTrap;
+    use encoding::all::{UTF_8, WINDOWS_31J};
+    use crate::TextReaderBuilder;
+
+    #[test]
+    fn decode_report_test() {
+        let mut bytes = Vec::new();
+        for _ in 0..1100 {
+            bytes.extend_from_slice(b"\x82\xa0\xff");
+        }
+        bytes.push(b'\x82');
+        for chunk_size in [1, 2048] {
+            let mut reader = TextReaderBuilder::new(WINDOWS_31J).trap(DecoderTrap::Ignore).chunk_size(chunk_size).build(&bytes[..]);
+            let mut s = String::new();
+            reader.read_to_end(&mut s).unwrap();
+            assert_eq!(s, "あ".repeat(1100));
+            let report = reader.decode_report();
+            assert_eq!(report.count(), 1101);
+            assert_eq!(report.offsets().len(), 1024);
+            assert_eq!(report.offsets()[..2], [2, 5]);
+            assert_eq!(report.first(), Some(2));
+            assert_eq!(report.last(), Some(3300));
+        }
+        let mut reader = TextReader::new(&b"a\xe3\x81"[..], UTF_8, DecoderTrap::Replace);
+        reader.read_to_end(&mut String::new()).unwrap();
+        assert_eq!(reader.decode_report().offsets(), [1]);
+        let mut reader = TextReader::new(&b"\xff"[..], UTF_8, DecoderTrap::Strict);
+        assert!(reader.read_to_end(&mut String::new()).is_err());
+        assert!(reader.decode_report().is_empty());
+        assert_eq!(reader.decode_report().first(), None);
+        assert_eq!(reader.decode_report().last(), None);
+    }
+}
@@ -0,0 +1,276 @@
+//! Resuming decoding at an arbitrary offset, or after a line which fails to decode.
+
+use std::io::{self, BufRead, ErrorKind, Seek};
+use std::mem;
+
+use crate::trap::LineCounter;
+use crate::{EncodingInfo, Result, TextReader};
+
+/// Where `TextReader::resync` resumes decoding.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Resync {
+    /// The start of the next character.
+    Char,
+    /// The start of the next line, after a `\n`.
+    Line,
+}
+
+impl<R: BufRead + Seek> TextReader<R> {
+    /// Skips the bytes from the current offset of the underlying reader to the next `to`, and
+    /// resumes decoding there. The reader may have been positioned at any offset by `get_mut`,
+    /// such as one saved before a crash, or a partition of a large file.
+    /// The text and the bytes buffered in this reader are discarded, and the line and the
+    /// column of the position are reset.
+    /// Returns the number of bytes skipped.
+    ///
+    /// The start of a character is found without decoding, after which the following bytes
+    /// may be decoded in the same way as from the start:
+    ///
+    /// * UTF-8: the first byte other than a continuation byte.
+    /// * UTF-16: the next even offset, which is not a low surrogate.
+    /// * Single byte encodings: the current offset.
+    /// * The other ASCII compatible encodings: after a byte below `0x30`, which is never a part
+    ///   of a multibyte character.
+    /// * Stateful encodings: the start of the next line, where the state is expected to be the
+    ///   initial one.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::{Cursor, Seek, SeekFrom};
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::{Resync, TextReader};
+    /// # fn foo() -> textstream::Result<()> {
+    /// let data = Cursor::new(b"\x82\xa0 \x82\xa2\n\x82\xa4");
+    /// let mut reader = TextReader::from_bufread(data, WINDOWS_31J, DecoderTrap::Strict);
+    /// reader.get_mut().seek(SeekFrom::Start(1))?;
+    /// assert_eq!(reader.resync(Resync::Char)?, 2);
+    /// assert_eq!(reader.read_char()?, Some('い'));
+    /// reader.get_mut().seek(SeekFrom::Start(1))?;
+    /// assert_eq!(reader.resync(Resync::Line)?, 5);
+    /// assert_eq!(reader.read_char()?, Some('う'));
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn resync(&mut self, to: Resync) -> Result<u64> {
+        let start = self.reader.stream_position()?;
+        let name = self.encoding.name();
+        if name == "utf-16le" || name == "utf-16be" {
+            return Ok(self.resync_utf16(start, to, name == "utf-16be")?);
+        }
+        let info = EncodingInfo::for_encoding(self.encoding);
+        let stateful = info.map_or(true, |info| info.stateful);
+        let skipped = match to {
+            Resync::Char if info.is_some_and(|info| info.fixed_width) => 0,
+            Resync::Char if name == "utf-8" => self.skip_bytes(|b| match b {
+                0x80..=0xbf => None,
+                _ => Some(false),
+            })?,
+            Resync::Char if !stateful => self.skip_bytes(|b| if b < 0x30 { Some(true) } else { None })?,
+            _ => self.skip_bytes(|b| if b == b'\n' { Some(true) } else { None })?,
+        };
+        self.reset(start + skipped);
+        Ok(skipped)
+    }
+
+    /// For internal use. `resync` for UTF-16, whose code units are read in pairs of bytes.
+    /// A code unit which is kept is left in `binbuf`.
+    fn resync_utf16(&mut self, start: u64, to: Resync, big_endian: bool) -> io::Result<u64> {
+        let mut skipped = start % 2;
+        if skipped == 1 {
+            self.skip_bytes(|_| Some(true))?;
+        }
+        let mut bytes = [0; 2];
+        let kept = loop {
+            match self.reader.read_exact(&mut bytes) {
+                Ok(()) => {},
+                Err(ref e) if e.kind() == ErrorKind::UnexpectedEof => break None,
+                Err(e) => return Err(e),
+            }
+            let unit = if big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) };
+            match to {
+                Resync::Char if (0xdc00..0xe000).contains(&unit) => {},
+                Resync::Char => break Some(bytes),
+                Resync::Line if unit == 0x0a => {
+                    skipped += 2;
+                    break None;
+                },
+                Resync::Line => {},
+            }
+            skipped += 2;
+        };
+        self.reset(start + skipped);
+        if let Some(bytes) = kept {
+            self.binbuf.extend_from_slice(&bytes);
+        }
+        Ok(skipped)
+    }
+}
+
+impl<R: BufRead> TextReader<R> {
+    /// Skips the rest of the line, such as after a `DecodeError` of `DecoderTrap::Strict`, so
+    /// that reading goes on with the next line rather than failing again on the same bytes.
+    /// The bytes are skipped without decoding up to the next `\n`, after which the decoder is
+    /// reset. Returns false if the end of the stream is reached first.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut reader = TextReader::new(&b"a\n\x82\xa0\xff\n\x82\xa2\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// let mut records = Vec::new();
+    /// loop {
+    ///     let mut s = String::new();
+    ///     match reader.read_line(&mut s) {
+    ///         Ok(0) => break,
+    ///         Ok(_) => records.push(s),
+    ///         Err(_) => if !reader.skip_to_next_line()? { break },
+    ///     }
+    /// }
+    /// assert_eq!(records, ["a\n", "い\n"]);
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn skip_to_next_line(&mut self) -> Result<bool> {
+        // The text decoded ahead is before the bytes, and may have the line terminator.
+        let mut textbuf = mem::take(&mut self.textbuf);
+        let rest = &textbuf[self.textpos..];
+        let end = self.find_line_end(rest, false);
+        self.advance(&rest[..end.unwrap_or(rest.len())]);
+        if let Some(n) = end {
+            self.textpos += n;
+            self.textbuf = textbuf;
+            return Ok(true);
+        }
+        textbuf.clear();
+        self.textbuf = textbuf;
+        self.textpos = 0;
+        self.textbuf_completeseq = true;
+
+        let newline: &[u8] = match self.encoding.name() {
+            "utf-16le" => b"\n\0",
+            "utf-16be" => b"\0\n",
+            _ => b"\n",
+        };
+        let mut found = false;
+        let mut unit = Vec::with_capacity(newline.len());
+        let mut stop = |b| {
+            unit.push(b);
+            if unit.len() == newline.len() {
+                found = unit == newline;
+                unit.clear();
+            }
+            if found { Some(true) } else { None }
+        };
+        let mut binbuf = mem::take(&mut self.binbuf);
+        let end = binbuf.iter().position(|&b| stop(b).is_some()).map(|n| n + 1);
+        let mut skipped = end.unwrap_or(binbuf.len()) as u64;
+        binbuf.drain(..skipped as usize);
+        self.binbuf = binbuf;
+        if end.is_none() {
+            skipped += self.skip_bytes(stop)?;
+        }
+        self.consumed += skipped;
+        self.queued = 0;
+        self.decoder.reset();
+        if found {
+            self.line += 1;
+            self.column = 0;
+        }
+        self.after_cr = false;
+        self.decoded_lines = LineCounter::default();
+        self.decoded_lines.line = self.line;
+        Ok(found)
+    }
+
+    /// For internal use. Skips bytes until `stop` returns whether to stop after or before a
+    /// byte. Returns the number of bytes skipped.
+    fn skip_bytes<F: FnMut(u8) -> Option<bool>>(&mut self, mut stop: F) -> io::Result<u64> {
+        let mut skipped = 0;
+        loop {
+            let buf = match self.reader.fill_buf() {
+                Ok(buf) => buf,
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if buf.is_empty() {
+                return Ok(skipped);
+            }
+            let found = buf.iter().enumerate().find_map(|(i, &b)| stop(b).map(|after| i + after as usize));
+            let n = found.unwrap_or(buf.len());
+            self.reader.consume(n);
+            skipped += n as u64;
+            if found.is_some() {
+                return Ok(skipped);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, SeekFrom};
+    use encoding::{DecoderTrap, EncoderTrap, Encoding, EncodingRef};
+    use encoding::all::{ISO_8859_1, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_31J};
+    use crate::TextReaderBuilder;
+
+    fn resync_at(bytes: &[u8], encoding: EncodingRef, offset: u64, to: Resync) -> (u64, String) {
+        let mut reader = TextReader::from_bufread(Cursor::new(bytes), encoding, DecoderTrap::Strict);
+        reader.get_mut().seek(SeekFrom::Start(offset)).unwrap();
+        let skipped = reader.resync(to).unwrap();
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(reader.position().byte, bytes.len() as u64);
+        (skipped, s)
+    }
+    #[test]
+    fn resync_test() {
+        let utf8 = "aあ\nい".as_bytes();
+        assert_eq!(resync_at(utf8, UTF_8, 2, Resync::Char), (2, "\nい".to_string()));
+        assert_eq!(resync_at(utf8, UTF_8, 1, Resync::Char), (0, "あ\nい".to_string()));
+        assert_eq!(resync_at(utf8, UTF_8, 1, Resync::Line), (4, "い".to_string()));
+        assert_eq!(resync_at(b"ab", ISO_8859_1, 1, Resync::Char), (0, "b".to_string()));
+        let sjis = b"\x82\xa0\x82\xa2 \x82\xa4";
+        assert_eq!(resync_at(sjis, WINDOWS_31J, 1, Resync::Char), (4, "う".to_string()));
+        assert_eq!(resync_at(sjis, WINDOWS_31J, 1, Resync::Line), (6, "".to_string()));
+        let utf16 = UTF_16BE.encode("a\u{1f600}\nb", EncoderTrap::Strict).unwrap();
+        assert_eq!(resync_at(&utf16, UTF_16BE, 1, Resync::Char), (1, "\u{1f600}\nb".to_string()));
+        assert_eq!(resync_at(&utf16, UTF_16BE, 3, Resync::Char), (3, "\nb".to_string()));
+        assert_eq!(resync_at(&utf16, UTF_16BE, 4, Resync::Char), (2, "\nb".to_string()));
+        assert_eq!(resync_at(&utf16, UTF_16BE, 1, Resync::Line), (7, "b".to_string()));
+    }
+    fn read_skipping(bytes: &[u8], encoding: EncodingRef, chunk_size: usize) -> Vec<(String, u64, u64)> {
+        let mut reader = TextReaderBuilder::new(encoding).chunk_size(chunk_size).build(bytes);
+        let mut lines = Vec::new();
+        loop {
+            let mut s = String::new();
+            match reader.read_line(&mut s) {
+                Ok(0) => break,
+                Ok(_) => lines.push((s, reader.position().line, reader.position().byte)),
+                Err(_) => if !reader.skip_to_next_line().unwrap() { break },
+            }
+        }
+        lines
+    }
+    #[test]
+    fn skip_to_next_line_test() {
+        let sjis = b"a\n\x82\xa0\xff\x82\n\xff\n\x82\xa2\n\xff";
+        for chunk_size in [1, 2048] {
+            assert_eq!(read_skipping(sjis, WINDOWS_31J, chunk_size),
+                [("a\n".to_string(), 2, 2), ("い\n".to_string(), 5, 12)]);
+        }
+        let utf16 = b"a\0\n\0\0\xdc\n\x0a\n\0b\0";
+        assert_eq!(read_skipping(utf16, UTF_16LE, 2048),
+            [("a\n".to_string(), 2, 4), ("b".to_string(), 3, 12)]);
+        let mut reader = TextReader::new(&b"a\nb\nc"[..], UTF_8, DecoderTrap::Strict);
+        assert_eq!(reader.read_char().unwrap(), Some('a'));
+        assert!(reader.skip_to_next_line().unwrap());
+        assert_eq!(reader.read_char().unwrap(), Some('b'));
+        assert_eq!(reader.position().line, 2);
+    }
+}
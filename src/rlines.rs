@@ -0,0 +1,224 @@
+//! Reading lines backwards.
+
+use std::fmt;
+use std::io::{self, BufRead, Seek, SeekFrom};
+use std::mem;
+
+use memchr::{memrchr, memrchr2};
+
+use crate::{decode, EncodingInfo, Error, NewlinePolicy, Position, Result, TextReader, Trap};
+
+/// Number of bytes read at once from the end.
+const BLOCK_SIZE: usize = 8192;
+
+impl<R: BufRead + Seek> TextReader<R> {
+    /// Returns an iterator over the lines of the underlying reader from the end backwards,
+    /// without reading the rest of the stream. The last lines of a large file are read in a
+    /// few blocks.
+    ///
+    /// Lines are split at `\n` bytes, and at `\r` bytes unless the newline policy is
+    /// `NewlinePolicy::Lf`. The other Unicode line terminators are not recognized. Each line
+    /// is yielded without the line terminator, like `lines()`. Text already read from this
+    /// reader is ignored. The positions of errors have the byte offset, with the line and the
+    /// column 0, as lines are not counted from the start.
+    ///
+    /// Fails with `InvalidInput` unless the `EncodingInfo` of the encoding is
+    /// `ascii_compatible` and not `stateful`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Cursor;
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let log = Cursor::new(b"a\r\n\x82\xa0\r\nc\r\n");
+    /// let reader = TextReader::from_bufread(log, WINDOWS_31J, DecoderTrap::Strict);
+    /// let last = reader.rlines()?.take(2).collect::<textstream::Result<Vec<_>>>()?;
+    /// assert_eq!(last, ["c", "あ"]);
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn rlines(self) -> Result<RLines<R>> {
+        self.rlines_with(BLOCK_SIZE)
+    }
+
+    /// For internal use. `rlines` reading blocks of `block_size` bytes.
+    fn rlines_with(mut self, block_size: usize) -> Result<RLines<R>> {
+        EncodingInfo::require_line_splittable(self.encoding, "read lines backwards")?;
+        let pos = self.reader.seek(SeekFrom::End(0))?;
+        Ok(RLines {
+            textreader: self,
+            pos,
+            pending: Vec::new(),
+            block_size,
+            after_lf: false,
+            at_end: true,
+            done: false,
+        })
+    }
+}
+
+/// An iterator over the lines of a `TextReader` from the end backwards.
+/// This struct is generally created by calling `rlines()` on a `TextReader`.
+/// Please see the documentation of `rlines()` for more details.
+pub struct RLines<R: BufRead + Seek> {
+    textreader: TextReader<R>,
+    pos: u64,
+    pending: Vec<u8>,
+    block_size: usize,
+    after_lf: bool,
+    at_end: bool,
+    done: bool,
+}
+
+impl<R: BufRead + Seek> RLines<R> {
+    /// For internal use. Prepends the block before `pending` to it.
+    /// Blocks grow with `pending`, so that a long line is not copied for every block.
+    fn read_block(&mut self) -> io::Result<()> {
+        let n = self.pos.min(self.block_size.max(self.pending.len()) as u64);
+        let start = self.pos - n;
+        self.textreader.reader.seek(SeekFrom::Start(start))?;
+        let mut block = vec![0; n as usize];
+        self.textreader.reader.read_exact(&mut block)?;
+        block.extend_from_slice(&self.pending);
+        self.pending = block;
+        self.pos = start;
+        Ok(())
+    }
+
+    /// For internal use. Removes the `\r` of a CRLF whose `\n` has been removed, once the byte
+    /// before it is known.
+    fn strip_cr(&mut self) {
+        if self.after_lf && !self.pending.is_empty() {
+            if self.pending.last() == Some(&b'\r') {
+                self.pending.pop();
+            }
+            self.after_lf = false;
+        }
+    }
+
+    /// For internal use. Decodes the bytes of a line starting at `offset`.
+    fn decode_line(&mut self, bytes: &[u8], offset: u64) -> Result<String> {
+        let mut s = String::new();
+        let mut start = 0;
+        let decoder = &mut *self.textreader.decoder;
+        // The bytes after an invalid sequence handled by the trap are decoded again.
+        let result = loop {
+            let (processed, result, _) = decode(decoder, Trap::Builtin(self.textreader.trap), &bytes[start..], 0, &mut s);
+            start += processed;
+            match result {
+                Ok(true) if start < bytes.len() => {},
+                Ok(_) => break Ok(decoder.finish(&mut s).is_none()),
+                Err(cause) => break Err(cause),
+            }
+        };
+        decoder.reset();
+        let position = Position { byte: offset + start as u64, line: 0, column: 0 };
+        match result {
+            Ok(true) => Ok(s),
+            Ok(false) => Err(Error::IncompleteSequence(position)),
+            Err(cause) => Err(Error::DecodeError { cause, position }),
+        }
+    }
+}
+
+impl<R: BufRead + Seek> Iterator for RLines<R> {
+    type Item = Result<String>;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if self.done {
+                return None;
+            }
+            let found = match self.textreader.newline {
+                NewlinePolicy::Lf => memrchr(b'\n', &self.pending),
+                _ => memrchr2(b'\r', b'\n', &self.pending),
+            };
+            if let Some(i) = found {
+                let line = self.pending.split_off(i + 1);
+                let lf = self.pending.pop() == Some(b'\n');
+                // The terminator at the end of the stream doesn't start an empty line.
+                let skip = self.at_end && line.is_empty();
+                self.at_end = false;
+                self.after_lf = lf;
+                self.strip_cr();
+                if !skip {
+                    let offset = self.pos + i as u64 + 1;
+                    return Some(self.decode_line(&line, offset));
+                }
+            }
+            else if self.pos == 0 {
+                self.done = true;
+                if self.at_end && self.pending.is_empty() {
+                    return None;
+                }
+                let line = mem::take(&mut self.pending);
+                return Some(self.decode_line(&line, 0));
+            }
+            else {
+                if let Err(e) = self.read_block() {
+                    self.done = true;
+                    return Some(Err(Error::IOError(e)));
+                }
+                self.strip_cr();
+            }
+        }
+    }
+}
+
+impl<R: BufRead + Seek> fmt::Debug for RLines<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RLines")
+            .field("textreader", &self.textreader)
+            .field("pos", &self.pos)
+            .field("pending_bytes", &self.pending.len())
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use encoding::DecoderTrap;
+    use encoding::all::{UTF_16LE, WINDOWS_31J};
+
+    fn rlines(bytes: &[u8], policy: NewlinePolicy, block_size: usize) -> Vec<String> {
+        let mut reader = TextReader::from_bufread(Cursor::new(bytes), WINDOWS_31J, DecoderTrap::Strict);
+        reader.set_newline_policy(policy);
+        reader.rlines_with(block_size).unwrap().map(|l| l.unwrap()).collect()
+    }
+    #[test]
+    fn rlines_test() {
+        let mut v = vec![];
+        for i in 0..300 {
+            v.extend_from_slice(i.to_string().as_bytes());
+            v.extend_from_slice(if i % 3 == 0 { b"\x82\xa0\r\n" } else { b"\x82\xa0\n" });
+        }
+        let expected: Vec<String> = (0..300).rev().map(|i| format!("{}あ", i)).collect();
+        for &block_size in &[1, 2, 3, 7, 8192] {
+            assert_eq!(rlines(&v, NewlinePolicy::Lf, block_size), expected);
+        }
+        assert_eq!(rlines(b"a\r\rb\r\n", NewlinePolicy::Universal, 1), ["b", "", "a"]);
+        assert_eq!(rlines(b"a\r\rb\r\n", NewlinePolicy::Lf, 1), ["a\r\rb"]);
+        assert_eq!(rlines(b"\n\nb", NewlinePolicy::Lf, 2), ["b", "", ""]);
+        assert!(rlines(b"", NewlinePolicy::Lf, 2).is_empty());
+        assert_eq!(rlines(b"\n", NewlinePolicy::Lf, 2), [""]);
+    }
+    #[test]
+    fn rlines_error() {
+        let reader = TextReader::from_bufread(Cursor::new(b"a\nb\xff\nc"), WINDOWS_31J, DecoderTrap::Strict);
+        let mut lines = reader.rlines().unwrap();
+        assert_eq!(lines.next().unwrap().unwrap(), "c");
+        match lines.next().unwrap() {
+            Err(Error::DecodeError { position, .. }) => assert_eq!(position.byte, 3),
+            _ => panic!("expected a decode error"),
+        }
+        assert_eq!(lines.next().unwrap().unwrap(), "a");
+        let reader = TextReader::from_bufread(Cursor::new(b"a\xffb\xfd"), WINDOWS_31J, DecoderTrap::Replace);
+        assert_eq!(reader.rlines().unwrap().next().unwrap().unwrap(), "a\u{fffd}b\u{fffd}");
+        let reader = TextReader::from_bufread(Cursor::new(b"a\x00"), UTF_16LE, DecoderTrap::Strict);
+        assert!(reader.rlines().is_err());
+    }
+}
@@ -0,0 +1,233 @@
+//! Files made of sections with different encodings or structures.
+
+use std::io::{self, BufRead, BufReader, Cursor, ErrorKind, Read};
+
+use crate::{EncodingInfo, Error, Result, TextReader, TextReaderBuilder};
+
+/// Extent of a section in the raw bytes of a file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SectionLength {
+    /// Exactly this number of bytes, e.g. fixed-width records.
+    Bytes(usize),
+    /// Exactly this number of lines terminated by `\n`. The last line of the file may lack it.
+    Lines(usize),
+    /// Lines up to, not including, the first line starting with the bytes, or the end of the file.
+    LinesUntil(&'static [u8]),
+    /// Everything up to the end of the file.
+    Rest,
+}
+
+/// A section of a file: its name, how to decode it and where it ends.
+#[derive(Clone, Copy)]
+pub struct Section {
+    /// Name of the section.
+    pub name: &'static str,
+    /// Settings of the `TextReader` for the section.
+    pub builder: TextReaderBuilder,
+    /// Extent of the section.
+    pub length: SectionLength,
+}
+
+/// The `Schema` struct describes the sections of a file, in order.
+///
+/// Line-based lengths are found by searching the raw bytes for `\n`, so they need an ASCII
+/// compatible encoding (see `EncodingInfo::ascii_compatible`).
+///
+/// # Examples
+/// ```
+/// use encoding::all::{ASCII, WINDOWS_31J};
+/// use textstream::{Schema, SectionLength, TextReaderBuilder};
+/// # fn foo() -> textstream::Result<()> {
+/// let file = b"HDR 2\n\x82\xa0\x82\xa2\x82\xa4\x82\xa6\x82\xa8\x82\xa9TRL\n";
+/// let schema = Schema::new()
+///     .section("header", TextReaderBuilder::new(ASCII), SectionLength::Lines(1))
+///     .section("body", TextReaderBuilder::new(WINDOWS_31J), SectionLength::Bytes(2 * 6))
+///     .section("trailer", TextReaderBuilder::new(ASCII), SectionLength::Rest);
+/// let mut reader = schema.reader(&file[..]);
+/// let mut texts = vec![];
+/// while let Some((name, mut section)) = reader.next_section()? {
+///     let mut s = String::new();
+///     section.read_to_end(&mut s)?;
+///     texts.push((name, s));
+/// }
+/// assert_eq!(texts[1], ("body", "あいうえおか".to_string()));
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+#[derive(Clone, Default)]
+pub struct Schema {
+    sections: Vec<Section>,
+}
+
+impl Schema {
+    /// Creates a new `Schema` without sections.
+    pub fn new() -> Schema {
+        Schema::default()
+    }
+
+    /// Appends a section.
+    pub fn section(mut self, name: &'static str, builder: TextReaderBuilder, length: SectionLength) -> Schema {
+        self.sections.push(Section { name, builder, length });
+        self
+    }
+
+    /// Returns the sections.
+    pub fn sections(&self) -> &[Section] { &self.sections }
+
+    /// Creates a `SectionReader` reading `reader` according to this schema.
+    pub fn reader<R: Read>(&self, reader: R) -> SectionReader<R> {
+        SectionReader {
+            bufreader: BufReader::new(reader),
+            sections: self.sections.clone(),
+            index: 0,
+            pending: None,
+        }
+    }
+}
+
+/// `TextReader` for a section, reading its raw bytes from memory.
+pub type SectionText = TextReader<Cursor<Vec<u8>>>;
+
+/// The `SectionReader` struct splits a file into the sections of a `Schema`.
+/// Please see the documentation of `Schema` for more details.
+pub struct SectionReader<R: Read> {
+    bufreader: BufReader<R>,
+    sections: Vec<Section>,
+    index: usize,
+    pending: Option<Vec<u8>>,
+}
+
+impl<R: Read> SectionReader<R> {
+    /// Reads the raw bytes of the next section, and returns its name and a `TextReader` for it.
+    /// Returns `None` after the last section.
+    ///
+    /// A section shorter than its length, or data left after the last section, is an error
+    /// of kind `UnexpectedEof` or `InvalidData`.
+    pub fn next_section(&mut self) -> Result<Option<(&'static str, SectionText)>> {
+        let section = match self.sections.get(self.index) {
+            Some(section) => *section,
+            None => {
+                if self.index == self.sections.len() {
+                    self.index += 1;
+                    if self.next_line()?.is_some() {
+                        return Err(Error::IOError(io::Error::new(ErrorKind::InvalidData, "data after the last section")));
+                    }
+                }
+                return Ok(None);
+            },
+        };
+        self.index += 1;
+        let mut bytes = Vec::new();
+        match section.length {
+            SectionLength::Bytes(n) => {
+                bytes = self.pending.take().unwrap_or_default();
+                if bytes.len() > n {
+                    self.pending = Some(bytes.split_off(n));
+                }
+                else {
+                    let rest = (n - bytes.len()) as u64;
+                    self.bufreader.by_ref().take(rest).read_to_end(&mut bytes)?;
+                }
+                if bytes.len() < n {
+                    return Err(truncated(section.name));
+                }
+            },
+            SectionLength::Lines(n) => {
+                check_ascii_compatible(&section)?;
+                for _ in 0..n {
+                    match self.next_line()? {
+                        Some(line) => bytes.extend_from_slice(&line),
+                        None => return Err(truncated(section.name)),
+                    }
+                }
+            },
+            SectionLength::LinesUntil(prefix) => {
+                check_ascii_compatible(&section)?;
+                while let Some(line) = self.next_line()? {
+                    if line.starts_with(prefix) {
+                        self.pending = Some(line);
+                        break;
+                    }
+                    bytes.extend_from_slice(&line);
+                }
+            },
+            SectionLength::Rest => {
+                bytes = self.pending.take().unwrap_or_default();
+                self.bufreader.read_to_end(&mut bytes)?;
+            },
+        }
+        Ok(Some((section.name, section.builder.build_bufread(Cursor::new(bytes)))))
+    }
+
+    /// For internal use. Reads raw bytes up to and including `\n`, or `None` at the end of file.
+    fn next_line(&mut self) -> io::Result<Option<Vec<u8>>> {
+        let mut line = self.pending.take().unwrap_or_default();
+        if line.last() != Some(&b'\n') {
+            self.bufreader.read_until(b'\n', &mut line)?;
+        }
+        if line.is_empty() {
+            Ok(None)
+        }
+        else {
+            Ok(Some(line))
+        }
+    }
+}
+
+/// For internal use. Line-based lengths search for `\n` in the raw bytes.
+fn check_ascii_compatible(section: &Section) -> Result<()> {
+    let encoding = section.builder.encoding();
+    match EncodingInfo::for_encoding(encoding) {
+        Some(ref info) if info.ascii_compatible => Ok(()),
+        _ => Err(Error::IOError(io::Error::new(ErrorKind::InvalidInput,
+            format!("section {} is line-based, but {} is not ASCII compatible", section.name, encoding.name())))),
+    }
+}
+
+fn truncated(name: &str) -> Error {
+    Error::IOError(io::Error::new(ErrorKind::UnexpectedEof, format!("section {} is truncated", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::all::{ASCII, UTF_16LE, WINDOWS_31J};
+
+    fn schema() -> Schema {
+        Schema::new()
+            .section("header", TextReaderBuilder::new(ASCII), SectionLength::Lines(1))
+            .section("body", TextReaderBuilder::new(WINDOWS_31J), SectionLength::LinesUntil(b"9"))
+            .section("trailer", TextReaderBuilder::new(ASCII), SectionLength::Lines(1))
+    }
+    fn read_all<R: Read>(reader: &mut SectionReader<R>) -> Result<Vec<String>> {
+        let mut texts = vec![];
+        while let Some((_, mut section)) = reader.next_section()? {
+            let mut s = String::new();
+            section.read_to_end(&mut s)?;
+            texts.push(s);
+        }
+        Ok(texts)
+    }
+    #[test]
+    fn lines_until() {
+        let file = b"1HDR\n2\x82\xa0\n2\x82\xa2\n9TRL 2";
+        let texts = read_all(&mut schema().reader(&file[..])).unwrap();
+        assert_eq!(texts, ["1HDR\n", "2あ\n2い\n", "9TRL 2"]);
+    }
+    #[test]
+    fn boundaries_enforced() {
+        assert!(read_all(&mut schema().reader(&b"1HDR\n2\x82\xa0\n"[..])).is_err());
+        assert!(read_all(&mut schema().reader(&b"1HDR\n9TRL\nextra"[..])).is_err());
+        let schema = Schema::new()
+            .section("fixed", TextReaderBuilder::new(ASCII), SectionLength::Bytes(4))
+            .section("rest", TextReaderBuilder::new(ASCII), SectionLength::Rest);
+        assert_eq!(read_all(&mut schema.reader(&b"abcdef"[..])).unwrap(), ["abcd", "ef"]);
+        assert!(read_all(&mut schema.reader(&b"abc"[..])).is_err());
+    }
+    #[test]
+    fn lines_need_ascii_compatible() {
+        let schema = Schema::new().section("utf16", TextReaderBuilder::new(UTF_16LE), SectionLength::Lines(1));
+        assert!(schema.reader(&b"a\0\n\0"[..]).next_section().is_err());
+    }
+}
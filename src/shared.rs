@@ -0,0 +1,122 @@
+//! Reader shared between threads.
+
+use std::io::{BufRead, Read};
+use std::sync::Mutex;
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::thread;
+
+use encoding::{DecoderTrap, EncodingRef};
+
+use crate::{Result, TextReader};
+
+/// Number of lines decoded ahead of the workers.
+const READ_AHEAD: usize = 64;
+
+/// The `SharedTextReader` struct lets multiple threads pull lines from one `TextReader`.
+///
+/// The `TextReader` lives on a dedicated thread and decodes lines ahead of the workers.
+/// `read_line` is internally synchronized, so `SharedTextReader` can be shared by reference
+/// (e.g. with `std::thread::scope`) or in an `Arc`.
+///
+/// Each line is returned to exactly one caller, in the order of the calls which acquired the
+/// internal lock. The lock is not fair: a thread calling `read_line` in a tight loop may get
+/// more lines than the others. A thread panicking while holding the lock doesn't lose any line.
+pub struct SharedTextReader {
+    receiver: Mutex<Receiver<Result<String>>>,
+}
+
+impl SharedTextReader {
+    /// Creates a new `SharedTextReader` decoding `reader` with `encoding`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::thread;
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::SharedTextReader;
+    /// # fn main() {
+    /// let reader = SharedTextReader::new(&b"a\nb\nc\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// thread::scope(|scope| {
+    ///     for _ in 0..2 {
+    ///         scope.spawn(|| {
+    ///             while let Some(line) = reader.read_line().unwrap() {
+    ///                 println!("{}", line);
+    ///             }
+    ///         });
+    ///     }
+    /// });
+    /// # }
+    /// ```
+    pub fn new<R>(reader: R, encoding: EncodingRef, trap: DecoderTrap) -> SharedTextReader
+        where R: Read + Send + 'static
+    {
+        SharedTextReader::spawn_with(move || TextReader::new(reader, encoding, trap))
+    }
+
+    /// Creates a new `SharedTextReader` with the `TextReader` created by `f` on the reading
+    /// thread. Use this to configure the `TextReader`.
+    pub fn spawn_with<R, F>(f: F) -> SharedTextReader
+        where R: BufRead, F: FnOnce() -> TextReader<R> + Send + 'static
+    {
+        let (sender, receiver) = sync_channel(READ_AHEAD);
+        thread::spawn(move || {
+            for line in f().lines() {
+                let is_err = line.is_err();
+                if sender.send(line).is_err() || is_err {
+                    break;
+                }
+            }
+        });
+        SharedTextReader { receiver: Mutex::new(receiver) }
+    }
+
+    /// Reads the next line, without the line terminator like `TextReader::lines`.
+    /// Returns `None` at the end of the stream, or after an error has been returned.
+    pub fn read_line(&self) -> Result<Option<String>> {
+        let receiver = match self.receiver.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => poisoned.into_inner(),
+        };
+        match receiver.recv() {
+            Ok(line) => line.map(Some),
+            Err(_) => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use std::sync::Arc;
+    use encoding::all::WINDOWS_31J;
+
+    #[test]
+    fn read_line_threads() {
+        let mut v = vec![];
+        for i in 0..1000 {
+            v.extend_from_slice(format!("{}\n", i).as_bytes());
+        }
+        let reader = Arc::new(SharedTextReader::new(Cursor::new(v), WINDOWS_31J, DecoderTrap::Strict));
+        let handles: Vec<_> = (0..4).map(|_| {
+            let reader = reader.clone();
+            thread::spawn(move || {
+                let mut lines = vec![];
+                while let Some(line) = reader.read_line().unwrap() {
+                    lines.push(line.parse::<usize>().unwrap());
+                }
+                lines
+            })
+        }).collect();
+        let mut lines: Vec<usize> = handles.into_iter().flat_map(|h| h.join().unwrap()).collect();
+        lines.sort();
+        assert_eq!(lines, (0..1000).collect::<Vec<_>>());
+    }
+    #[test]
+    fn read_line_error() {
+        let reader = SharedTextReader::new(&b"a\n\xff\nb\n"[..], WINDOWS_31J, DecoderTrap::Strict);
+        assert_eq!(reader.read_line().unwrap().unwrap(), "a");
+        assert!(reader.read_line().is_err());
+        assert!(reader.read_line().unwrap().is_none());
+    }
+}
@@ -0,0 +1,155 @@
+//! Reading and writing text over one duplex stream.
+
+use std::io::{BufReader, Read, Write};
+
+use encoding::{DecoderTrap, EncoderTrap, EncodingRef};
+
+use crate::{BufferMode, LineTerminator, LinesMut, Result, TextReader, TextWriter};
+
+/// A `TextReader` and a `TextWriter` over one duplex stream, such as a `TcpStream` or a serial
+/// port, for line oriented protocols in legacy encodings, such as IRC in ISO-2022-JP.
+///
+/// Text is written to the stream as soon as it is encoded. Text read ahead of the lines
+/// returned is kept in the read half.
+///
+/// There is no `split` into a read half and a write half borrowed at the same time: the one
+/// stream is owned by the read half, and writing to it while the read half is borrowed would
+/// need a second handle. For streams which have one, such as `TcpStream::try_clone` or
+/// `&TcpStream`, create a `TextReader` over one handle and a `TextWriter` over the other
+/// instead, which can also be moved to different threads.
+///
+/// # Examples
+/// ```no_run
+/// use std::net::TcpStream;
+/// use encoding::all::ISO_2022_JP;
+/// use encoding::{DecoderTrap, EncoderTrap};
+/// use textstream::{LineTerminator, TextStream};
+/// # fn foo() -> textstream::Result<()> {
+/// let socket = TcpStream::connect("irc.example.jp:6667")?;
+/// let mut stream = TextStream::new(socket, ISO_2022_JP, DecoderTrap::Replace, EncoderTrap::Strict);
+/// stream.set_line_terminator(LineTerminator::CrLf);
+/// stream.write_line("NICK たろう")?;
+/// let mut line = String::new();
+/// while stream.read_line(&mut line)? > 0 {
+///     if let Some(token) = line.strip_prefix("PING ") {
+///         stream.write_line(&format!("PONG {}", token.trim_end()))?;
+///     }
+///     line.clear();
+/// }
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub struct TextStream<S: Read + Write> {
+    reader: TextReader<BufReader<S>>,
+    writer: TextWriter<Vec<u8>>,
+}
+
+impl<S: Read + Write> TextStream<S> {
+    /// Creates a new `TextStream` reading and writing `stream` in `encoding`.
+    pub fn new(stream: S, encoding: EncodingRef, decoder_trap: DecoderTrap, encoder_trap: EncoderTrap) -> TextStream<S> {
+        let mut writer = TextWriter::new(Vec::new(), encoding, encoder_trap);
+        writer.set_buffer_mode(BufferMode::Unbuffered);
+        TextStream { reader: TextReader::new(stream, encoding, decoder_trap), writer }
+    }
+
+    /// Reads a line from the stream, like `TextReader::read_line`.
+    pub fn read_line(&mut self, buf: &mut String) -> Result<usize> {
+        self.reader.read_line(buf)
+    }
+
+    /// Returns an iterator over the lines read from the stream, like `TextReader::lines_mut`.
+    pub fn lines(&mut self) -> LinesMut<'_, BufReader<S>> {
+        self.reader.lines_mut()
+    }
+
+    /// Encodes `s` and writes it to the stream, like `TextWriter::write_str`.
+    pub fn write_str(&mut self, s: &str) -> Result<()> {
+        let result = self.writer.write_str(s);
+        self.send()?;
+        result
+    }
+
+    /// Encodes `s` and a line terminator, and writes them to the stream, like
+    /// `TextWriter::write_line`.
+    pub fn write_line(&mut self, s: &str) -> Result<()> {
+        let result = self.writer.write_line(s);
+        self.send()?;
+        result
+    }
+
+    /// Sets the line terminator written by `write_line`, like `TextWriter::set_line_terminator`.
+    pub fn set_line_terminator(&mut self, terminator: LineTerminator) {
+        self.writer.set_line_terminator(terminator);
+    }
+
+    /// Gets the line terminator written by `write_line`.
+    pub fn line_terminator(&self) -> LineTerminator { self.writer.line_terminator() }
+
+    /// Flushes the stream.
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.get_mut().flush()?)
+    }
+
+    /// Gets a reference to the read half, the `TextReader` of the stream.
+    pub fn reader(&self) -> &TextReader<BufReader<S>> { &self.reader }
+
+    /// Gets a mutable reference to the read half, for the methods of `TextReader` other than
+    /// those above.
+    pub fn reader_mut(&mut self) -> &mut TextReader<BufReader<S>> { &mut self.reader }
+
+    /// Gets a reference to the underlying stream.
+    pub fn get_ref(&self) -> &S { self.reader.get_ref().get_ref() }
+
+    /// Gets a mutable reference to the underlying stream.
+    /// It is inadvisable to directly read from or write to the underlying stream.
+    pub fn get_mut(&mut self) -> &mut S { self.reader.get_mut().get_mut() }
+
+    /// Unwraps this `TextStream`, returning the underlying stream.
+    /// The bytes and the text read ahead are lost.
+    pub fn into_inner(self) -> S { self.reader.into_inner().into_inner() }
+
+    /// For internal use. Writes the encoded text to the stream.
+    fn send(&mut self) -> Result<()> {
+        let stream = self.reader.get_mut().get_mut();
+        let buf = self.writer.get_mut();
+        let result = stream.write_all(buf);
+        buf.clear();
+        Ok(result?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{self, Cursor};
+    use encoding::all::ISO_2022_JP;
+
+    /// A duplex stream reading given bytes and keeping the bytes written.
+    struct Duplex {
+        input: Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+    impl Read for Duplex {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> { self.input.read(buf) }
+    }
+    impl Write for Duplex {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> { self.output.write(buf) }
+        fn flush(&mut self) -> io::Result<()> { Ok(()) }
+    }
+    #[test]
+    fn text_stream() {
+        let duplex = Duplex { input: Cursor::new(b"PING \x1b$B$\"\x1b(B\nQUIT\n".to_vec()), output: Vec::new() };
+        let mut stream = TextStream::new(duplex, ISO_2022_JP, DecoderTrap::Strict, EncoderTrap::Strict);
+        let mut line = String::new();
+        stream.read_line(&mut line).unwrap();
+        assert_eq!(line, "PING あ\n");
+        stream.write_line(line.replace("PING", "PONG").trim_end()).unwrap();
+        assert!(stream.write_str("\u{1F600}").is_err());
+        stream.flush().unwrap();
+        assert_eq!(stream.get_ref().output, b"PONG \x1b$B$\"\x1b(B\n");
+        assert_eq!(stream.lines().collect::<Result<Vec<_>>>().unwrap(), ["QUIT"]);
+        assert_eq!(stream.reader().position().line, 3);
+        assert!(stream.into_inner().output.ends_with(b"\n"));
+    }
+}
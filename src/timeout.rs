@@ -0,0 +1,188 @@
+//! Reading with a timeout from sources which may stall.
+
+use std::io::{self, BufReader, ErrorKind, Read};
+use std::sync::mpsc::{sync_channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+use encoding::{DecoderTrap, EncodingRef};
+
+use crate::{TextReader, CHUNK_SIZE};
+
+/// A reader which fails with `ErrorKind::TimedOut` if a read waits longer than the timeout,
+/// for sources without a timeout of their own, such as pipes from child processes and serial
+/// devices. Sockets have `TcpStream::set_read_timeout` instead.
+///
+/// The underlying reader is read on a background thread, at most one chunk ahead. A read which
+/// times out is not cancelled; its bytes are returned by a later read. The thread ends at the
+/// end of the stream or on an error, or once this reader is dropped and the pending read
+/// returns.
+pub struct TimeoutReader {
+    receiver: Receiver<io::Result<Vec<u8>>>,
+    chunk: Vec<u8>,
+    pos: usize,
+    timeout: Option<Duration>,
+    done: bool,
+}
+
+impl TimeoutReader {
+    /// Creates a new `TimeoutReader` reading `reader` on a background thread.
+    /// Reads wait at most `timeout`, or forever if it is `None`.
+    pub fn new<R: Read + Send + 'static>(mut reader: R, timeout: Option<Duration>) -> TimeoutReader {
+        let (sender, receiver) = sync_channel(1);
+        thread::spawn(move || {
+            let mut buf = vec![0; CHUNK_SIZE];
+            loop {
+                let result = match reader.read(&mut buf) {
+                    Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                    result => result.map(|n| buf[..n].to_vec()),
+                };
+                let last = !matches!(result, Ok(ref chunk) if !chunk.is_empty());
+                if sender.send(result).is_err() || last {
+                    return;
+                }
+            }
+        });
+        TimeoutReader { receiver, chunk: Vec::new(), pos: 0, timeout, done: false }
+    }
+
+    /// Returns the timeout of reads.
+    pub fn timeout(&self) -> Option<Duration> { self.timeout }
+
+    /// Sets the timeout of reads, or `None` to wait forever.
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) {
+        self.timeout = timeout;
+    }
+}
+
+impl Read for TimeoutReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.chunk.len() {
+            if self.done {
+                return Ok(0);
+            }
+            let received = match self.timeout {
+                Some(timeout) => self.receiver.recv_timeout(timeout),
+                None => self.receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            };
+            self.chunk = match received {
+                Ok(Ok(chunk)) => chunk,
+                Ok(Err(e)) => {
+                    self.done = true;
+                    return Err(e);
+                },
+                Err(RecvTimeoutError::Timeout) => {
+                    return Err(io::Error::new(ErrorKind::TimedOut, "read timed out"));
+                },
+                Err(RecvTimeoutError::Disconnected) => Vec::new(),
+            };
+            self.pos = 0;
+            if self.chunk.is_empty() {
+                self.done = true;
+                return Ok(0);
+            }
+        }
+        let n = (&self.chunk[self.pos..]).read(buf)?;
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl TextReader<BufReader<TimeoutReader>> {
+    /// Creates a new `TextReader` for `reader`, whose reads fail with `ErrorKind::TimedOut`
+    /// after waiting `timeout`. Please see `TimeoutReader`.
+    ///
+    /// `read_line` keeps the text read before a timeout in this reader, so that the line can be
+    /// read again as a whole after the error.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use std::io::ErrorKind;
+    /// use std::process::{Command, Stdio};
+    /// use std::time::Duration;
+    /// use encoding::all::UTF_8;
+    /// use encoding::DecoderTrap;
+    /// use textstream::{Error, TextReader};
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut child = Command::new("ping").arg("localhost").stdout(Stdio::piped()).spawn()?;
+    /// let stdout = child.stdout.take().unwrap();
+    /// let mut reader = TextReader::with_timeout(stdout, UTF_8, DecoderTrap::Replace, Duration::from_secs(5));
+    /// let mut line = String::new();
+    /// match reader.read_line(&mut line) {
+    ///     Err(Error::IOError(e)) if e.kind() == ErrorKind::TimedOut => println!("no output for 5 seconds"),
+    ///     result => println!("{:?}", result.map(|_| line)),
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn with_timeout<R: Read + Send + 'static>(reader: R, encoding: EncodingRef, trap: DecoderTrap,
+        timeout: Duration) -> TextReader<BufReader<TimeoutReader>>
+    {
+        TextReader::new(TimeoutReader::new(reader, Some(timeout)), encoding, trap)
+    }
+
+    /// Sets the timeout of reads, or `None` to wait forever.
+    pub fn set_read_timeout(&mut self, timeout: Option<Duration>) {
+        self.get_mut().get_mut().set_timeout(timeout);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc::channel;
+    use encoding::all::WINDOWS_31J;
+    use crate::Error;
+
+    /// Bytes sent by the test, read as a stalling pipe.
+    struct Pipe(Receiver<Vec<u8>>);
+    impl Read for Pipe {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            match self.0.recv() {
+                Ok(bytes) => (&bytes[..]).read(buf),
+                Err(_) => Ok(0),
+            }
+        }
+    }
+    #[test]
+    fn read_line_timeout() {
+        let (sender, receiver) = channel();
+        let mut reader = TextReader::with_timeout(Pipe(receiver), WINDOWS_31J, DecoderTrap::Strict,
+            Duration::from_millis(50));
+        let timed_out = |result: crate::Result<usize>| {
+            matches!(result, Err(Error::IOError(ref e)) if e.kind() == ErrorKind::TimedOut)
+        };
+        let mut s = String::new();
+        sender.send(b"a\x82".to_vec()).unwrap();
+        assert!(timed_out(reader.read_line(&mut s)));
+        assert_eq!(s, "");
+        sender.send(b"\xa0b\nc".to_vec()).unwrap();
+        reader.set_read_timeout(None);
+        reader.read_line(&mut s).unwrap();
+        assert_eq!(s, "aあb\n");
+        reader.set_read_timeout(Some(Duration::from_millis(50)));
+        s.clear();
+        assert!(timed_out(reader.read_line(&mut s)));
+        drop(sender);
+        reader.set_read_timeout(None);
+        reader.read_line(&mut s).unwrap();
+        assert_eq!(s, "c");
+        assert_eq!(reader.read_line(&mut s).unwrap(), 0);
+        // The partial line is not counted by `TakeChars` either.
+        let (sender, receiver) = channel();
+        let reader = TextReader::with_timeout(Pipe(receiver), WINDOWS_31J, DecoderTrap::Strict,
+            Duration::from_millis(50));
+        let mut take = reader.take_chars(3);
+        let mut s = String::new();
+        sender.send(b"a\x82".to_vec()).unwrap();
+        assert!(timed_out(take.read_line(&mut s)));
+        assert_eq!(s, "");
+        assert_eq!(take.limit(), 3);
+        sender.send(b"\xa0b\n".to_vec()).unwrap();
+        take.read_line(&mut s).unwrap();
+        assert_eq!(s, "aあb");
+        assert_eq!(take.limit(), 0);
+        assert_eq!(take.into_inner().position().byte, 4);
+    }
+}
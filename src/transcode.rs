@@ -0,0 +1,282 @@
+//! Converting text from a reader to a writer in another encoding.
+
+use std::fs::{self, File};
+use std::io::{self, BufRead, ErrorKind, Write};
+use std::mem;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use encoding::{DecoderTrap, EncoderTrap, EncodingRef};
+
+use crate::{BomPolicy, LineTerminator, Result, TextReader, TextWriter};
+
+/// Counts of a conversion by `transcode`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TranscodeStats {
+    /// Bytes read from the reader.
+    pub bytes_read: u64,
+    /// Bytes written to the writer, including those still in its buffer.
+    pub bytes_written: u64,
+    /// Characters converted.
+    pub chars: u64,
+    /// Invalid sequences which the trap of the reader has handled, such as those replaced by
+    /// `DecoderTrap::Replace`.
+    pub replacements: u64,
+}
+
+/// Reads the rest of `reader` and writes it to `writer`, a chunk at a time, so that files of
+/// any size are converted in bounded memory. Returns the counts of the conversion.
+///
+/// The writer is not finished, so that more text can be written to it; call
+/// `TextWriter::finish` at the end. On an error, the text before it has been written.
+///
+/// # Examples
+/// ```
+/// use encoding::all::{UTF_8, WINDOWS_31J};
+/// use encoding::{DecoderTrap, EncoderTrap};
+/// use textstream::{transcode, TextReader, TextWriter};
+/// # fn foo() -> textstream::Result<()> {
+/// let mut reader = TextReader::new(&b"\x82\xa0\xff\n"[..], WINDOWS_31J, DecoderTrap::Replace);
+/// let mut writer = TextWriter::new(Vec::new(), UTF_8, EncoderTrap::Strict);
+/// let stats = transcode(&mut reader, &mut writer)?;
+/// assert_eq!((stats.bytes_read, stats.bytes_written, stats.chars, stats.replacements), (4, 7, 3, 1));
+/// assert_eq!(writer.finish()?, "あ\u{fffd}\n".as_bytes());
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub fn transcode<R: BufRead, W: Write>(reader: &mut TextReader<R>, writer: &mut TextWriter<W>) -> Result<TranscodeStats> {
+    transcode_with_progress(reader, writer, |_| {})
+}
+
+/// `transcode` calling `progress` with the counts so far after each chunk, such as for a
+/// progress bar of a large file.
+pub fn transcode_with_progress<R, W, F>(reader: &mut TextReader<R>, writer: &mut TextWriter<W>, mut progress: F)
+    -> Result<TranscodeStats>
+    where R: BufRead, W: Write, F: FnMut(&TranscodeStats)
+{
+    let start_byte = reader.position().byte;
+    let start_replacements = reader.decode_report().count();
+    let start_written = writer.bytes_written();
+    let mut stats = TranscodeStats::default();
+    while reader.fill_textbuf()? {
+        let text = mem::take(&mut reader.textbuf);
+        let chunk = &text[reader.textpos..];
+        let result = writer.write_str(chunk);
+        reader.advance(chunk);
+        stats.chars += chunk.chars().count() as u64;
+        reader.textpos = text.len();
+        reader.textbuf = text;
+        result?;
+        stats.bytes_read = reader.position().byte - start_byte;
+        stats.bytes_written = writer.bytes_written() - start_written;
+        stats.replacements = reader.decode_report().count() - start_replacements;
+        progress(&stats);
+    }
+    Ok(stats)
+}
+
+/// The `TranscodeOptions` struct configures `transcode_file`.
+///
+/// # Examples
+/// ```
+/// use encoding::DecoderTrap;
+/// use textstream::{LineTerminator, TranscodeOptions};
+/// # fn main() {
+/// let options = TranscodeOptions::new()
+///     .decoder_trap(DecoderTrap::Replace)
+///     .newline(LineTerminator::CrLf)
+///     .write_bom(true);
+/// # }
+/// ```
+#[derive(Clone, Copy)]
+pub struct TranscodeOptions {
+    decoder_trap: DecoderTrap,
+    encoder_trap: EncoderTrap,
+    newline: Option<LineTerminator>,
+    strip_bom: bool,
+    write_bom: bool,
+}
+
+impl TranscodeOptions {
+    /// Creates a new `TranscodeOptions` with `DecoderTrap::Strict` and `EncoderTrap::Strict`,
+    /// stripping a BOM of the source and keeping the newlines.
+    pub fn new() -> TranscodeOptions {
+        TranscodeOptions {
+            decoder_trap: DecoderTrap::Strict,
+            encoder_trap: EncoderTrap::Strict,
+            newline: None,
+            strip_bom: true,
+            write_bom: false,
+        }
+    }
+
+    /// Sets the trap for decoding errors of the source.
+    pub fn decoder_trap(mut self, trap: DecoderTrap) -> TranscodeOptions {
+        self.decoder_trap = trap;
+        self
+    }
+
+    /// Sets the trap for characters which the destination encoding can't encode.
+    pub fn encoder_trap(mut self, trap: EncoderTrap) -> TranscodeOptions {
+        self.encoder_trap = trap;
+        self
+    }
+
+    /// Sets the line terminator to which `\n` and `\r\n` are converted, such as
+    /// `LineTerminator::CrLf` for Windows. By default, newlines are kept.
+    pub fn newline(mut self, terminator: LineTerminator) -> TranscodeOptions {
+        self.newline = Some(terminator);
+        self
+    }
+
+    /// Sets whether to strip a BOM at the start of the source, which most legacy encodings
+    /// can't encode. The default is true.
+    pub fn strip_bom(mut self, strip: bool) -> TranscodeOptions {
+        self.strip_bom = strip;
+        self
+    }
+
+    /// Sets whether to write a BOM for a destination in UTF-8 or UTF-16.
+    /// Please see `TextWriter::set_write_bom`. The default is false.
+    pub fn write_bom(mut self, write_bom: bool) -> TranscodeOptions {
+        self.write_bom = write_bom;
+        self
+    }
+}
+
+impl Default for TranscodeOptions {
+    fn default() -> TranscodeOptions { TranscodeOptions::new() }
+}
+
+/// Converts the file at `src` in `src_encoding` to a file at `dst` in `dst_encoding`, a chunk
+/// at a time. Returns the counts of the conversion.
+///
+/// The text is written to a temporary file next to `dst`, which is renamed to `dst` once
+/// complete, so that `dst` is never left half written, and can be the same file as `src`.
+/// The temporary file is removed on an error.
+///
+/// # Examples
+/// ```no_run
+/// use encoding::all::{UTF_8, WINDOWS_31J};
+/// use textstream::{transcode_file, LineTerminator, TranscodeOptions};
+/// # fn foo() -> textstream::Result<()> {
+/// let options = TranscodeOptions::new().newline(LineTerminator::Lf);
+/// transcode_file("shiftjis.txt", WINDOWS_31J, "utf8.txt", UTF_8, &options)?;
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub fn transcode_file<P, Q>(src: P, src_encoding: EncodingRef, dst: Q, dst_encoding: EncodingRef,
+    options: &TranscodeOptions) -> Result<TranscodeStats>
+    where P: AsRef<Path>, Q: AsRef<Path>
+{
+    let dst = dst.as_ref();
+    let mut reader = TextReader::open(src, src_encoding, options.decoder_trap)?;
+    if options.strip_bom {
+        reader.set_bom_policy(BomPolicy::Strip);
+    }
+    let tmp = temp_path(dst)?;
+    let result = write_file(&mut reader, &tmp, dst_encoding, options).and_then(|stats| {
+        fs::rename(&tmp, dst)?;
+        Ok(stats)
+    });
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    result
+}
+
+/// For internal use. Returns the path of the temporary file for `dst`, in the same directory
+/// for `fs::rename`.
+fn temp_path(dst: &Path) -> io::Result<PathBuf> {
+    let name = dst.file_name()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "destination is not a file name"))?;
+    let mut tmp = name.to_os_string();
+    tmp.push(format!(".{}.tmp", process::id()));
+    Ok(dst.with_file_name(tmp))
+}
+
+/// For internal use. Writes the rest of `reader` to a new file at `path`.
+fn write_file<R: BufRead>(reader: &mut TextReader<R>, path: &Path, encoding: EncodingRef,
+    options: &TranscodeOptions) -> Result<TranscodeStats>
+{
+    let mut writer = TextWriter::new(File::create(path)?, encoding, options.encoder_trap);
+    if let Some(terminator) = options.newline {
+        writer.set_line_terminator(terminator);
+        writer.set_translate_newlines(true);
+        writer.set_collapse_crlf(true);
+    }
+    writer.set_write_bom(options.write_bom);
+    let stats = transcode(reader, &mut writer)?;
+    writer.finish()?.sync_all()?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::{DecoderTrap, EncoderTrap, Encoding};
+    use encoding::all::{ISO_2022_JP, UTF_16LE, WINDOWS_31J};
+    use std::env;
+    use encoding::all::UTF_8;
+    use crate::TextReaderBuilder;
+
+    #[test]
+    fn transcode_test() {
+        let text = "あいう\r\nabc\n".repeat(1000);
+        let bytes = WINDOWS_31J.encode(&text, EncoderTrap::Strict).unwrap();
+        let mut reader = TextReaderBuilder::new(WINDOWS_31J).chunk_size(100).build(&bytes[..]);
+        let mut writer = TextWriter::new(Vec::new(), UTF_16LE, EncoderTrap::Strict);
+        let mut calls = 0;
+        let stats = transcode_with_progress(&mut reader, &mut writer, |stats| {
+            calls += 1;
+            assert!(stats.bytes_read <= bytes.len() as u64);
+        }).unwrap();
+        assert!(calls > 10);
+        assert_eq!(stats, TranscodeStats {
+            bytes_read: bytes.len() as u64,
+            bytes_written: 18 * 1000,
+            chars: 9 * 1000,
+            replacements: 0,
+        });
+        assert_eq!(reader.position().line, 2001);
+        assert_eq!(writer.finish().unwrap(), UTF_16LE.encode(&text, EncoderTrap::Strict).unwrap());
+    }
+    #[test]
+    fn transcode_error() {
+        let mut reader = TextReader::new(&b"a\n\x82\xa0"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut s = String::new();
+        reader.read_line(&mut s).unwrap();
+        let mut writer = TextWriter::new(Vec::new(), ISO_2022_JP, EncoderTrap::Strict);
+        let stats = transcode(&mut reader, &mut writer).unwrap();
+        assert_eq!((stats.bytes_read, stats.chars), (2, 1));
+        assert_eq!(writer.finish().unwrap(), b"\x1b$B$\"");
+        let mut reader = TextReader::new(&b"a\xff"[..], WINDOWS_31J, DecoderTrap::Strict);
+        let mut writer = TextWriter::new(Vec::new(), ISO_2022_JP, EncoderTrap::Strict);
+        assert!(transcode(&mut reader, &mut writer).is_err());
+        assert_eq!(writer.finish().unwrap(), b"a");
+    }
+    #[test]
+    fn transcode_file_test() {
+        let dir = env::temp_dir();
+        let src = dir.join(format!("textstream-transcode-src-{}.txt", process::id()));
+        let dst = dir.join(format!("textstream-transcode-dst-{}.txt", process::id()));
+        fs::write(&src, b"\xef\xbb\xbf\xe3\x81\x82\r\nb\n").unwrap();
+        let options = TranscodeOptions::new().newline(LineTerminator::CrLf);
+        let stats = transcode_file(&src, UTF_8, &dst, WINDOWS_31J, &options).unwrap();
+        assert_eq!(fs::read(&dst).unwrap(), b"\x82\xa0\r\nb\r\n");
+        assert_eq!((stats.bytes_read, stats.chars), (10, 5));
+        // In place.
+        let options = TranscodeOptions::new().newline(LineTerminator::Lf).write_bom(true);
+        transcode_file(&dst, WINDOWS_31J, &dst, UTF_8, &options).unwrap();
+        assert_eq!(fs::read(&dst).unwrap(), b"\xef\xbb\xbf\xe3\x81\x82\nb\n");
+        // The destination is kept on an error.
+        fs::write(&src, b"a\xff").unwrap();
+        assert!(transcode_file(&src, WINDOWS_31J, &dst, UTF_8, &TranscodeOptions::default()).is_err());
+        assert_eq!(fs::read(&dst).unwrap(), b"\xef\xbb\xbf\xe3\x81\x82\nb\n");
+        assert!(!temp_path(&dst).unwrap().exists());
+        fs::remove_file(&src).unwrap();
+        fs::remove_file(&dst).unwrap();
+    }
+}
@@ -0,0 +1,213 @@
+//! Trap handlers given the context of invalid sequences.
+
+use std::io::BufRead;
+use std::mem;
+
+use encoding::DecoderTrap;
+use memchr::memchr_iter;
+
+use crate::{is_unicode_newline, NewlinePolicy, TextDecoder, TextReader};
+
+/// An invalid sequence given to the handler set by `TextReader::set_trap_handler`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct InvalidSequence<'a> {
+    /// The invalid bytes.
+    pub bytes: &'a [u8],
+    /// Offset of the bytes from the start of the underlying reader.
+    pub offset: u64,
+    /// Line of the bytes, starting at 1, counted with the `NewlinePolicy`.
+    pub line: u64,
+}
+
+/// The text emitted for invalid sequences, set by `TextReader::set_replacement`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Replacement {
+    /// The text, such as `"\u{FFFD}"` or `"?"`, for every invalid sequence.
+    Text(String),
+    /// The bytes in hexadecimal, such as `<0x82>` for each byte, flagging the corruption for
+    /// human review.
+    Hex,
+}
+
+/// For internal use. The handler set by `TextReader::set_trap_handler`.
+pub(crate) type TrapHandler = Box<dyn FnMut(&InvalidSequence, &mut String) -> bool + Send>;
+
+/// For internal use. Counts the lines of the text decoded so far, which may be ahead of the
+/// text returned.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct LineCounter {
+    pub(crate) line: u64,
+    after_cr: bool,
+}
+
+impl LineCounter {
+    /// Counts the line terminators in `text`, the same way as `TextReader::position`.
+    pub(crate) fn count(&mut self, text: &str, newline: NewlinePolicy) {
+        if newline == NewlinePolicy::Lf {
+            self.line += memchr_iter(b'\n', text.as_bytes()).count() as u64;
+            return;
+        }
+        for c in text.chars() {
+            let after_cr = mem::replace(&mut self.after_cr, c == '\r');
+            let terminator = match c {
+                '\n' => !after_cr,
+                '\r' => true,
+                _ => newline == NewlinePolicy::Unicode && is_unicode_newline(c),
+            };
+            self.line += terminator as u64;
+        }
+    }
+}
+
+/// For internal use. The trap applied by `decode`.
+pub(crate) enum Trap<'a> {
+    Builtin(DecoderTrap),
+    Handler {
+        handler: &'a mut TrapHandler,
+        /// Offset of the input.
+        offset: u64,
+        /// Lines before the input.
+        lines: LineCounter,
+        newline: NewlinePolicy,
+    },
+}
+
+impl<'a> Trap<'a> {
+    /// For internal use. The handler if any, or `trap`, for decoding the input at `offset`.
+    pub(crate) fn new(trap: DecoderTrap, handler: &'a mut Option<TrapHandler>, offset: u64, lines: LineCounter,
+        newline: NewlinePolicy) -> Trap<'a>
+    {
+        match handler {
+            Some(handler) => Trap::Handler { handler, offset, lines, newline },
+            None => Trap::Builtin(trap),
+        }
+    }
+
+    /// For internal use. Handles `input[start..end]`, returning whether to continue.
+    /// `s[decoded..]` is the text decoded from `input[..start]`.
+    pub(crate) fn apply(self, decoder: &mut dyn TextDecoder, input: &[u8], start: usize, end: usize, s: &mut String,
+        decoded: usize) -> bool
+    {
+        let bytes = &input[start..end];
+        match self {
+            Trap::Builtin(trap) => decoder.trap(trap, bytes, s),
+            Trap::Handler { handler, offset, mut lines, newline } => {
+                lines.count(&s[decoded..], newline);
+                let invalid = InvalidSequence { bytes, offset: offset + start as u64, line: lines.line + 1 };
+                handler(&invalid, s)
+            },
+        }
+    }
+}
+
+impl<R: BufRead> TextReader<R> {
+    /// Sets a handler of invalid sequences, called instead of the trap with the invalid bytes,
+    /// their offset and their line. The handler pushes the replacement to the `String` and
+    /// returns true to continue, or returns false to fail with `Error::DecodeError`, like
+    /// `DecoderTrap::Call`. The text pushed before returning false is kept.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn main() {
+    /// let mut reader = TextReader::new(&b"a\n\x82\xa0\xff\n\xfd"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// reader.set_trap_handler(|invalid, s| {
+    ///     eprintln!("line {}: invalid bytes {:?} at {}", invalid.line, invalid.bytes, invalid.offset);
+    ///     if invalid.line > 2 {
+    ///         return false;
+    ///     }
+    ///     s.push_str(&format!("<{:02X}>", invalid.bytes[0]));
+    ///     true
+    /// });
+    /// let mut s = String::new();
+    /// assert!(reader.read_to_end(&mut s).is_err());
+    /// assert_eq!(s, "a\nあ<FF>\n");
+    /// # }
+    /// ```
+    pub fn set_trap_handler<F>(&mut self, handler: F)
+        where F: FnMut(&InvalidSequence, &mut String) -> bool + Send + 'static
+    {
+        self.trap_handler = Some(Box::new(handler));
+    }
+
+    /// Sets the text emitted for invalid sequences, instead of the trap.
+    /// This is a handler of `set_trap_handler`, which replaces the one set before.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::DecoderTrap;
+    /// use textstream::{Replacement, TextReader};
+    /// # fn main() {
+    /// let mut reader = TextReader::new(&b"\x82\xa0\xff\x82"[..], WINDOWS_31J, DecoderTrap::Strict);
+    /// reader.set_replacement(Replacement::Hex);
+    /// let mut s = String::new();
+    /// reader.read_to_end(&mut s).unwrap();
+    /// assert_eq!(s, "あ<0xFF><0x82>");
+    /// # }
+    /// ```
+    pub fn set_replacement(&mut self, replacement: Replacement) {
+        match replacement {
+            Replacement::Text(text) => self.set_trap_handler(move |_, s| {
+                s.push_str(&text);
+                true
+            }),
+            Replacement::Hex => self.set_trap_handler(|invalid, s| {
+                for b in invalid.bytes {
+                    s.push_str(&format!("<0x{:02X}>", b));
+                }
+                true
+            }),
+        }
+    }
+
+    /// Removes the handler set by `set_trap_handler` or `set_replacement`, to go back to the
+    /// trap.
+    pub fn clear_trap_handler(&mut self) {
+        self.trap_handler = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use encoding::all::WINDOWS_31J;
+    use crate::TextReaderBuilder;
+
+    #[test]
+    fn trap_handler() {
+        let bytes = b"a\r\n\xff\r\x82\xa0\xfd\n\n\x82";
+        for chunk_size in [1, 2048] {
+            let seen = Arc::new(Mutex::new(Vec::new()));
+            let mut reader = TextReaderBuilder::new(WINDOWS_31J).chunk_size(chunk_size)
+                .newline_policy(NewlinePolicy::Universal).build(&bytes[..]);
+            let log = seen.clone();
+            reader.set_trap_handler(move |invalid, s| {
+                log.lock().unwrap().push((invalid.bytes.to_vec(), invalid.offset, invalid.line));
+                s.push('?');
+                true
+            });
+            let mut s = String::new();
+            reader.read_to_end(&mut s).unwrap();
+            assert_eq!(s, "a\r\n?\rあ?\n\n?");
+            assert_eq!(*seen.lock().unwrap(), [(vec![0xff], 3, 2), (vec![0xfd], 7, 3), (vec![0x82], 10, 5)]);
+            assert_eq!(reader.decode_report().count(), 3);
+        }
+        let mut reader = TextReader::new(&b"\xff"[..], WINDOWS_31J, DecoderTrap::Replace);
+        reader.set_trap_handler(|_, _| false);
+        assert!(reader.read_to_end(&mut String::new()).is_err());
+        reader.clear_trap_handler();
+    }
+    #[test]
+    fn replacement() {
+        let mut reader = TextReader::new(&b"a\xff\x82\xa0\xfd\xfd"[..], WINDOWS_31J, DecoderTrap::Strict);
+        reader.set_replacement(Replacement::Text("?".to_string()));
+        let mut s = String::new();
+        reader.read_to_end(&mut s).unwrap();
+        assert_eq!(s, "a?あ??");
+        assert_eq!(reader.decode_report().count(), 3);
+    }
+}
@@ -0,0 +1,183 @@
+//! Encodings of Windows code pages and of the console.
+//!
+//! `encoding_from_code_page` is available on every platform, for text written on Windows.
+//! The encodings of the running system are only available on Windows.
+
+use encoding::EncodingRef;
+
+use crate::label::encoding_from_whatwg_label;
+
+#[cfg(windows)]
+use std::io::{self, StdinLock};
+
+#[cfg(windows)]
+use encoding::DecoderTrap;
+
+#[cfg(windows)]
+use crate::{Error, Result, TextReader};
+
+#[cfg(windows)]
+extern "system" {
+    fn GetConsoleCP() -> u32;
+    fn GetOEMCP() -> u32;
+    fn GetACP() -> u32;
+}
+
+/// Returns the encoding of a Windows code page, such as 932 for Shift_JIS or 866 for the
+/// Cyrillic code page of DOS, if any.
+/// Returns `None` for code pages unknown to `textstream::label`, including those of encoding
+/// families not enabled by cargo features and DOS code pages such as 437 and 850.
+///
+/// # Examples
+/// ```
+/// use textstream::windows::encoding_from_code_page;
+/// assert_eq!(encoding_from_code_page(65001).map(|e| e.name()), Some("utf-8"));
+/// assert!(encoding_from_code_page(437).is_none());
+/// ```
+pub fn encoding_from_code_page(code_page: u32) -> Option<EncodingRef> {
+    let label = match code_page {
+        65001 => "utf-8",
+        1200 => "utf-16le",
+        1201 => "utf-16be",
+        866 => "ibm866",
+        874 => "windows-874",
+        1250 => "windows-1250",
+        1251 => "windows-1251",
+        1252 => "windows-1252",
+        1253 => "windows-1253",
+        1254 => "windows-1254",
+        1255 => "windows-1255",
+        1256 => "windows-1256",
+        1257 => "windows-1257",
+        1258 => "windows-1258",
+        10000 => "macintosh",
+        10007 => "x-mac-cyrillic",
+        20866 => "koi8-r",
+        21866 => "koi8-u",
+        28592 => "iso-8859-2",
+        28593 => "iso-8859-3",
+        28594 => "iso-8859-4",
+        28595 => "iso-8859-5",
+        28596 => "iso-8859-6",
+        28597 => "iso-8859-7",
+        28598 => "iso-8859-8",
+        38598 => "iso-8859-8-i",
+        28603 => "iso-8859-13",
+        28605 => "iso-8859-15",
+        932 => "shift_jis",
+        20932 => "euc-jp",
+        50220 => "iso-2022-jp",
+        936 => "gbk",
+        54936 => "gb18030",
+        52936 => "hz-gb-2312",
+        949 => "euc-kr",
+        950 => "big5",
+        _ => return None,
+    };
+    encoding_from_whatwg_label(label)
+}
+
+/// Returns the code page of the console input, or the OEM code page, its default, if the
+/// process has no console.
+#[cfg(windows)]
+pub fn console_code_page() -> u32 {
+    match unsafe { GetConsoleCP() } {
+        0 => oem_code_page(),
+        code_page => code_page,
+    }
+}
+
+/// Returns the OEM code page of the system, the default code page of the console, which
+/// console programs such as `ping` and `dir` use for their output, such as 437 in the United
+/// States or 932 in Japan.
+#[cfg(windows)]
+pub fn oem_code_page() -> u32 {
+    unsafe { GetOEMCP() }
+}
+
+/// Returns the ANSI code page of the system, which GUI programs and many older programs use
+/// for files, such as 1252 in Western Europe or 932 in Japan.
+#[cfg(windows)]
+pub fn ansi_code_page() -> u32 {
+    unsafe { GetACP() }
+}
+
+/// Returns the encoding of `console_code_page`, used by programs run from cmd.exe for their
+/// piped output, if known.
+#[cfg(windows)]
+pub fn console_encoding() -> Option<EncodingRef> {
+    encoding_from_code_page(console_code_page())
+}
+
+/// Returns the encoding of `oem_code_page`, if known.
+#[cfg(windows)]
+pub fn oem_encoding() -> Option<EncodingRef> {
+    encoding_from_code_page(oem_code_page())
+}
+
+/// Returns the encoding of `ansi_code_page`, if known.
+#[cfg(windows)]
+pub fn ansi_encoding() -> Option<EncodingRef> {
+    encoding_from_code_page(ansi_code_page())
+}
+
+#[cfg(windows)]
+impl TextReader<StdinLock<'static>> {
+    /// Creates a new `TextReader` for the standard input, decoded with the code page of the
+    /// console, so that the output of programs such as `dir` or `ipconfig` piped from cmd.exe is
+    /// read without looking up the code page.
+    ///
+    /// Fails with `Error::UnknownEncoding` if the code page is unknown to
+    /// `encoding_from_code_page`.
+    ///
+    /// # Examples
+    /// ```no_run
+    /// use encoding::DecoderTrap;
+    /// use textstream::TextReader;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let reader = TextReader::stdin_console(DecoderTrap::Replace)?;
+    /// for line in reader.lines() {
+    ///     println!("{}", line?);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn stdin_console(trap: DecoderTrap) -> Result<TextReader<StdinLock<'static>>> {
+        let code_page = console_code_page();
+        let encoding = encoding_from_code_page(code_page)
+            .ok_or_else(|| Error::UnknownEncoding(format!("cp{}", code_page)))?;
+        Ok(TextReader::from_bufread(io::stdin().lock(), encoding, trap))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn code_page() {
+        let name = |code_page| encoding_from_code_page(code_page).map(|e| e.name());
+        assert_eq!(name(65001), Some("utf-8"));
+        assert_eq!(name(1200), Some("utf-16le"));
+        assert_eq!(name(437), None);
+        assert_eq!(name(0), None);
+        #[cfg(feature = "singlebyte")]
+        assert_eq!(name(866), Some("ibm866"));
+        #[cfg(feature = "japanese")]
+        assert_eq!(name(932), Some("windows-31j"));
+        #[cfg(feature = "all-encodings")]
+        for code_page in [874, 1250, 1258, 10000, 10007, 20866, 21866, 28592, 28605, 38598, 20932, 50220, 936,
+            54936, 52936, 949, 950]
+        {
+            assert!(name(code_page).is_some(), "cp{}", code_page);
+        }
+    }
+    #[cfg(windows)]
+    #[test]
+    fn system_code_page() {
+        assert_ne!(ansi_code_page(), 0);
+        assert_ne!(oem_code_page(), 0);
+        assert_ne!(console_code_page(), 0);
+    }
+}
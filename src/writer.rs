@@ -0,0 +1,1126 @@
+//! Writer for non-utf8 text.
+
+use std::{io, mem, str};
+use std::io::{ErrorKind, Write};
+
+use encoding::{ByteWriter, CodecError, EncoderTrap, EncodingRef, RawEncoder};
+
+use crate::decoder::builtin_encoding;
+use crate::{unescape_char, Error, Result};
+
+/// Size of the head of the stream where encoding declarations are rewritten.
+const PRESCAN_SIZE: usize = 1024;
+
+/// Size of the buffer of `TextWriter`.
+const BUFFER_SIZE: usize = 8192;
+
+/// A trap writing characters which can't be encoded as hexadecimal numeric character references
+/// of HTML and XML, such as `&#x1F600;`. `EncoderTrap::NcrEscape` writes decimal ones.
+///
+/// # Examples
+/// ```
+/// use encoding::all::WINDOWS_31J;
+/// use textstream::{TextWriter, HEX_NCR_ESCAPE};
+/// # fn foo() -> textstream::Result<()> {
+/// let mut writer = TextWriter::new(Vec::new(), WINDOWS_31J, HEX_NCR_ESCAPE);
+/// writer.write_str("あ\u{1F600}")?;
+/// assert_eq!(writer.finish()?, b"\x82\xa0&#x1F600;");
+/// # Ok(())
+/// # }
+/// # fn main() { foo().unwrap(); }
+/// ```
+pub const HEX_NCR_ESCAPE: EncoderTrap = EncoderTrap::Call(hex_ncr_escape);
+
+fn hex_ncr_escape(encoder: &mut dyn RawEncoder, input: &str, output: &mut dyn ByteWriter) -> bool {
+    let escaped: String = input.chars().map(|c| format!("&#x{:X};", c as u32)).collect();
+    encoder.raw_feed(&escaped, output).1.is_none()
+}
+
+/// For internal use. The handler set by `TextWriter::set_trap_handler`.
+type EncoderTrapHandler = Box<dyn FnMut(&str, &mut String) -> bool + Send>;
+
+/// The `EncodeWriter` struct accepts UTF-8 bytes via `io::Write` and writes them to the
+/// underlying writer in the target encoding.
+///
+/// A UTF-8 sequence split across `write` calls is buffered until it is completed.
+pub struct EncodeWriter<W: Write> {
+    writer: W,
+    encoder: Box<dyn RawEncoder + Send>,
+    trap: EncoderTrap,
+    pending: Vec<u8>,
+    outbuf: Vec<u8>,
+    encoding_name: &'static str,
+    prescan: Option<Vec<u8>>,
+    strip_bom: bool,
+    bom_pending: bool,
+    unescape: bool,
+    handler: Option<EncoderTrapHandler>,
+}
+
+impl<W: Write> EncodeWriter<W> {
+    /// Creates a new `EncodeWriter` with `encoding`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use encoding::label::encoding_from_whatwg_label;
+    /// use encoding::{EncoderTrap, Encoding};
+    /// use textstream::EncodeWriter;
+    /// # fn foo() -> std::io::Result<()> {
+    /// let mut writer = EncodeWriter::new(Vec::new(), encoding_from_whatwg_label("shift_jis").unwrap(), EncoderTrap::Strict);
+    /// writer.write_all("あいうえお".as_bytes())?;
+    /// let sjis = writer.finish()?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn new(writer: W, encoding: EncodingRef, trap: EncoderTrap) -> EncodeWriter<W> {
+        EncodeWriter {
+            writer,
+            encoder: new_encoder(encoding),
+            trap,
+            pending: Vec::new(),
+            outbuf: Vec::new(),
+            encoding_name: encoding.whatwg_name().unwrap_or_else(|| encoding.name()),
+            prescan: None,
+            strip_bom: false,
+            bom_pending: false,
+            unescape: false,
+            handler: None,
+        }
+    }
+
+    /// Sets whether to write exactly one BOM at the start of the output.
+    /// If set, every U+FEFF in the written text (e.g. BOMs of concatenated fragments) is dropped
+    /// and a single BOM is written first. No BOM is written for non-Unicode encodings.
+    /// This must be set before the first write.
+    pub fn set_single_bom(&mut self, single: bool) {
+        self.strip_bom = single;
+        self.bom_pending = single && is_unicode(self.encoding_name);
+    }
+
+    /// Sets whether to rewrite the encoding declared by `<meta charset=...>`,
+    /// `<meta http-equiv="Content-Type" content="...; charset=...">` and `<?xml encoding=...?>`
+    /// to the target encoding. Only declarations in the first 1024 bytes are rewritten.
+    /// This must be set before the first write.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use encoding::label::encoding_from_whatwg_label;
+    /// use encoding::{EncoderTrap, Encoding};
+    /// use textstream::EncodeWriter;
+    /// # fn foo() -> std::io::Result<()> {
+    /// let mut writer = EncodeWriter::new(Vec::new(), encoding_from_whatwg_label("shift_jis").unwrap(), EncoderTrap::Strict);
+    /// writer.set_rewrite_declaration(true);
+    /// writer.write_all(b"<meta charset=\"utf-8\">")?;
+    /// assert_eq!(writer.finish()?, b"<meta charset=\"shift_jis\">");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn set_rewrite_declaration(&mut self, rewrite: bool) {
+        self.prescan = if rewrite { Some(Vec::new()) } else { None };
+    }
+
+    /// Sets whether to write the code points reserved by `SURROGATE_ESCAPE` as the bytes which
+    /// they escape, instead of encoding them.
+    pub fn set_unescape(&mut self, unescape: bool) {
+        self.unescape = unescape;
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W { &self.writer }
+
+    /// Gets a mutable reference to the underlying writer.
+    /// It is inadvisable to directly write to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W { &mut self.writer }
+
+    /// For internal use. Returns the underlying writer without finishing the encoder.
+    fn into_inner(self) -> W { self.writer }
+
+    /// Finishes the encoder, flushes the underlying writer and returns it.
+    /// Fails if the written bytes end in the middle of a UTF-8 sequence.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.end_prescan()?;
+        if !self.pending.is_empty() {
+            return Err(io::Error::new(ErrorKind::InvalidData, "incomplete UTF-8 sequence"));
+        }
+        self.outbuf.clear();
+        self.encode_str("")?;
+        if let Some(e) = self.encoder.raw_finish(&mut self.outbuf) {
+            return Err(io::Error::new(ErrorKind::InvalidData, e.cause.into_owned()));
+        }
+        self.writer.write_all(&self.outbuf)?;
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+
+    /// For internal use. Rewrites the declarations in the buffered head and writes it.
+    fn end_prescan(&mut self) -> io::Result<()> {
+        if let Some(head) = self.prescan.take() {
+            let head = rewrite_declaration(&head, self.encoding_name);
+            let mut rest = &head[..];
+            while !rest.is_empty() {
+                let n = self.write_utf8(rest)?;
+                rest = &rest[n..];
+            }
+        }
+        Ok(())
+    }
+
+    /// For internal use. Encodes `input` into `outbuf`, applying the BOM option.
+    fn encode_str(&mut self, input: &str) -> Result<()> {
+        if self.bom_pending {
+            self.bom_pending = false;
+            self.encode_raw("\u{FEFF}")?;
+        }
+        if self.strip_bom && input.contains('\u{FEFF}') {
+            let stripped: String = input.chars().filter(|&c| c != '\u{FEFF}').collect();
+            self.encode_raw(&stripped)
+        }
+        else {
+            self.encode_raw(input)
+        }
+    }
+
+    /// For internal use. Encodes `input` into `outbuf`, applying the unescape option.
+    fn encode_raw(&mut self, input: &str) -> Result<()> {
+        if !self.unescape {
+            return self.encode_chars(input);
+        }
+        let mut rest = input;
+        while let Some((i, c, b)) = rest.char_indices().find_map(|(i, c)| unescape_char(c).map(|b| (i, c, b))) {
+            self.encode_chars(&rest[..i])?;
+            self.outbuf.push(b);
+            rest = &rest[i + c.len_utf8()..];
+        }
+        self.encode_chars(rest)
+    }
+
+    /// For internal use. Encodes `input` into `outbuf`.
+    fn encode_chars(&mut self, input: &str) -> Result<()> {
+        let mut remaining = 0;
+        loop {
+            let (offset, err) = self.encoder.raw_feed(&input[remaining..], &mut self.outbuf);
+            let unprocessed = remaining + offset;
+            match err {
+                Some(e) => {
+                    remaining = (remaining as isize + e.upto) as usize;
+                    let unmappable = &input[unprocessed..remaining];
+                    let handled = match self.handler {
+                        Some(ref mut handler) => {
+                            let mut replacement = String::new();
+                            handler(unmappable, &mut replacement)
+                                && self.encoder.raw_feed(&replacement, &mut self.outbuf).1.is_none()
+                        },
+                        None => self.trap.trap(&mut *self.encoder, unmappable, &mut self.outbuf),
+                    };
+                    if !handled {
+                        return Err(Error::from(e.cause));
+                    }
+                }
+                None => return Ok(())
+            }
+        }
+    }
+
+    /// For internal use. Encodes UTF-8 bytes and writes them.
+    fn write_utf8(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut pending = mem::take(&mut self.pending);
+        let result = write_utf8_prefix(&mut pending, buf, |s| {
+            self.outbuf.clear();
+            self.encode_str(s)?;
+            self.writer.write_all(&self.outbuf)
+        });
+        self.pending = pending;
+        result
+    }
+}
+
+impl<W: Write> Write for EncodeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(ref mut head) = self.prescan {
+            head.extend_from_slice(buf);
+            if head.len() < PRESCAN_SIZE {
+                return Ok(buf.len());
+            }
+        }
+        else {
+            return self.write_utf8(buf);
+        }
+        self.end_prescan()?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.end_prescan()?;
+        self.writer.flush()
+    }
+}
+
+/// Line terminators written by `TextWriter::write_line`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineTerminator {
+    /// `\n`, of Unix.
+    #[default]
+    Lf,
+    /// `\r\n`, of Windows and of network protocols such as SMTP and IRC.
+    CrLf,
+    /// NEL (U+0085), of EBCDIC. Most encodings other than Unicode and ISO-8859 can't encode it.
+    Nel,
+}
+
+impl LineTerminator {
+    /// Returns the line terminator as text.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineTerminator::Lf => "\n",
+            LineTerminator::CrLf => "\r\n",
+            LineTerminator::Nel => "\u{85}",
+        }
+    }
+}
+
+/// When `TextWriter` writes the encoded text to the underlying writer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BufferMode {
+    /// When the buffer is full, like `BufWriter`.
+    #[default]
+    Block,
+    /// When the buffer is full, and after every line terminator, followed by a flush of the
+    /// underlying writer, like `LineWriter`. For logs and terminals.
+    Line,
+    /// On every write, such as for sockets.
+    Unbuffered,
+}
+
+/// For internal use. The buffer of `TextWriter`, written to the underlying writer when it is
+/// full, and when it is dropped.
+struct WriteBuffer<W: Write> {
+    /// The underlying writer, until it is taken by `into_inner`.
+    writer: Option<W>,
+    buf: Vec<u8>,
+}
+
+impl<W: Write> WriteBuffer<W> {
+    fn get_ref(&self) -> &W { self.writer.as_ref().unwrap() }
+
+    fn get_mut(&mut self) -> &mut W { self.writer.as_mut().unwrap() }
+
+    /// Writes the buffered bytes to the underlying writer. They are kept on an error.
+    fn flush_buf(&mut self) -> io::Result<()> {
+        if !self.buf.is_empty() {
+            self.writer.as_mut().unwrap().write_all(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+
+    /// Writes the buffered bytes and returns the underlying writer.
+    fn into_inner(mut self) -> io::Result<W> {
+        self.flush_buf()?;
+        Ok(self.writer.take().unwrap())
+    }
+}
+
+impl<W: Write> Write for WriteBuffer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.buf.len() + buf.len() > BUFFER_SIZE {
+            self.flush_buf()?;
+        }
+        if buf.len() >= BUFFER_SIZE {
+            return self.get_mut().write(buf);
+        }
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.flush_buf()?;
+        self.get_mut().flush()
+    }
+}
+
+impl<W: Write> Drop for WriteBuffer<W> {
+    fn drop(&mut self) {
+        if self.writer.is_some() {
+            // Errors can't be reported here; `into_inner` and `finish` report them.
+            let _ = self.flush_buf();
+        }
+    }
+}
+
+/// The `TextWriter` struct writes text to the underlying writer in the target encoding,
+/// the counterpart of `TextReader`. Unlike `EncodeWriter`, it takes `str` and reports encoding
+/// errors as `Error`.
+///
+/// The encoded text is buffered, and written when the buffer is full or when the
+/// `TextWriter` is dropped, as set by `set_buffer_mode`. The encoder of stateful encodings,
+/// such as ISO-2022-JP, is finished by `finish` only.
+///
+/// `TextWriter` also accepts UTF-8 bytes via `io::Write`, like `EncodeWriter`, with the
+/// settings of the `TextWriter` applied. A UTF-8 sequence split across `write` calls is
+/// buffered until it is completed.
+pub struct TextWriter<W: Write> {
+    inner: EncodeWriter<WriteBuffer<W>>,
+    pending: Vec<u8>,
+    written: u64,
+    buffer_mode: BufferMode,
+    terminator: LineTerminator,
+    translate_newlines: bool,
+    collapse_crlf: bool,
+    held_cr: bool,
+    write_bom: bool,
+}
+
+impl<W: Write> TextWriter<W> {
+    /// Creates a new `TextWriter` with `encoding`.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::EncoderTrap;
+    /// use textstream::TextWriter;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut writer = TextWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+    /// writer.write_line("あ")?;
+    /// assert_eq!(writer.finish()?, b"\x82\xa0\n");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn new(writer: W, encoding: EncodingRef, trap: EncoderTrap) -> TextWriter<W> {
+        TextWriter {
+            inner: EncodeWriter::new(WriteBuffer { writer: Some(writer), buf: Vec::new() }, encoding, trap),
+            pending: Vec::new(),
+            written: 0,
+            buffer_mode: BufferMode::Block,
+            terminator: LineTerminator::Lf,
+            translate_newlines: false,
+            collapse_crlf: false,
+            held_cr: false,
+            write_bom: false,
+        }
+    }
+
+    /// Encodes `s` and writes it, translating its newlines if set.
+    /// If a character can't be encoded under the trap, the text before it is written, so that
+    /// the shift state of stateful encodings matches the output, and an error is returned.
+    pub fn write_str(&mut self, s: &str) -> Result<()> {
+        if !self.translate_newlines || s.is_empty() {
+            return self.write_raw(s);
+        }
+        let mut text = String::with_capacity(s.len() + 16);
+        // A `\r` at the end of the previous text is a part of a `\r\n` if `s` starts with `\n`.
+        if mem::take(&mut self.held_cr) && !s.starts_with('\n') {
+            text.push('\r');
+        }
+        let mut rest = s;
+        if self.collapse_crlf {
+            if let Some(stripped) = rest.strip_suffix('\r') {
+                self.held_cr = true;
+                rest = stripped;
+            }
+        }
+        for piece in rest.split_inclusive('\n') {
+            match piece.strip_suffix('\n') {
+                Some(line) => {
+                    let line = if self.collapse_crlf { line.strip_suffix('\r').unwrap_or(line) } else { line };
+                    text.push_str(line);
+                    text.push_str(self.terminator.as_str());
+                },
+                None => text.push_str(piece),
+            }
+        }
+        self.write_raw(&text)
+    }
+
+    /// Encodes `s` and the line terminator, and writes them.
+    pub fn write_line(&mut self, s: &str) -> Result<()> {
+        self.write_str(s)?;
+        self.release_cr()?;
+        self.write_raw(self.terminator.as_str())
+    }
+
+    /// Sets whether `write_str` and `write_line` translate `\n` in the text to the line
+    /// terminator, like `unix2dos` with `LineTerminator::CrLf`. The default is false.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::EncoderTrap;
+    /// use textstream::{LineTerminator, TextWriter};
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut writer = TextWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+    /// writer.set_line_terminator(LineTerminator::CrLf);
+    /// writer.set_translate_newlines(true);
+    /// writer.set_collapse_crlf(true);
+    /// writer.write_str("a\nb\r\nc")?;
+    /// assert_eq!(writer.finish()?, b"a\r\nb\r\nc");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn set_translate_newlines(&mut self, translate: bool) {
+        self.translate_newlines = translate;
+    }
+
+    /// Gets whether `\n` in the text is translated to the line terminator.
+    pub fn translate_newlines(&self) -> bool { self.translate_newlines }
+
+    /// Sets whether `\r\n` in the text is translated to the line terminator as a whole, rather
+    /// than as `\r` and `\n`, when `\n` is translated, like `dos2unix` with `LineTerminator::Lf`.
+    /// A `\r\n` split between writes is translated as well. The default is false.
+    pub fn set_collapse_crlf(&mut self, collapse: bool) {
+        self.collapse_crlf = collapse;
+    }
+
+    /// Gets whether `\r\n` in the text is translated to the line terminator as a whole.
+    pub fn collapse_crlf(&self) -> bool { self.collapse_crlf }
+
+    /// Sets the line terminator written by `write_line`. The default is `LineTerminator::Lf`.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::EncoderTrap;
+    /// use textstream::{LineTerminator, TextWriter};
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut writer = TextWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+    /// writer.set_line_terminator(LineTerminator::CrLf);
+    /// writer.write_line("あ")?;
+    /// assert_eq!(writer.finish()?, b"\x82\xa0\r\n");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn set_line_terminator(&mut self, terminator: LineTerminator) {
+        self.terminator = terminator;
+    }
+
+    /// Gets the line terminator written by `write_line`.
+    pub fn line_terminator(&self) -> LineTerminator { self.terminator }
+
+    /// Sets whether to write a BOM before the text, for UTF-8 and UTF-16, such as for CSV
+    /// files opened by Excel, which takes UTF-8 without a BOM for the ANSI code page.
+    /// No BOM is written for the other encodings. If set, a BOM is written even if no text is.
+    /// This must be set before the first write.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::UTF_8;
+    /// use encoding::EncoderTrap;
+    /// use textstream::TextWriter;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut writer = TextWriter::new(Vec::new(), UTF_8, EncoderTrap::Strict);
+    /// writer.set_write_bom(true);
+    /// writer.write_line("名前,値")?;
+    /// assert!(writer.finish()?.starts_with(b"\xef\xbb\xbf"));
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn set_write_bom(&mut self, write_bom: bool) {
+        self.write_bom = write_bom;
+        self.inner.bom_pending = write_bom && is_unicode(self.inner.encoding_name);
+    }
+
+    /// Gets whether to write a BOM before the text.
+    pub fn write_bom(&self) -> bool { self.write_bom }
+
+    /// Sets a handler of characters which can't be encoded, called instead of the trap with
+    /// them. The handler pushes the replacement to the `String` and returns true to continue,
+    /// or returns false to fail, like `EncoderTrap::Call`. The replacement must be encodable.
+    ///
+    /// # Examples
+    /// ```
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::EncoderTrap;
+    /// use textstream::TextWriter;
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut writer = TextWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+    /// writer.set_trap_handler(|unmappable, s| {
+    ///     eprintln!("cannot encode {:?}", unmappable);
+    ///     // GETA MARK, used for missing characters in Japanese typesetting.
+    ///     s.push('〓');
+    ///     true
+    /// });
+    /// writer.write_str("髙\u{1F600}")?;
+    /// assert_eq!(writer.finish()?, b"\xfb\xfc\x81\xac");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn set_trap_handler<F>(&mut self, handler: F)
+        where F: FnMut(&str, &mut String) -> bool + Send + 'static
+    {
+        self.inner.handler = Some(Box::new(handler));
+    }
+
+    /// Removes the handler set by `set_trap_handler`, to go back to the trap.
+    pub fn clear_trap_handler(&mut self) {
+        self.inner.handler = None;
+    }
+
+    /// Sets when the encoded text is written to the underlying writer. The default is
+    /// `BufferMode::Block`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io;
+    /// use encoding::all::UTF_8;
+    /// use encoding::EncoderTrap;
+    /// use textstream::{BufferMode, TextWriter};
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut writer = TextWriter::new(io::stderr(), UTF_8, EncoderTrap::Replace);
+    /// writer.set_buffer_mode(BufferMode::Line);
+    /// writer.write_line("started")?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    pub fn set_buffer_mode(&mut self, mode: BufferMode) {
+        self.buffer_mode = mode;
+    }
+
+    /// Gets when the encoded text is written to the underlying writer.
+    pub fn buffer_mode(&self) -> BufferMode { self.buffer_mode }
+
+    /// Returns the number of bytes written so far, including those in the buffer.
+    pub fn bytes_written(&self) -> u64 { self.written }
+
+    /// Writes the buffered text and flushes the underlying writer.
+    pub fn flush(&mut self) -> Result<()> {
+        Ok(self.inner.writer.flush()?)
+    }
+
+    /// Gets a reference to the underlying writer.
+    pub fn get_ref(&self) -> &W { self.inner.writer.get_ref() }
+
+    /// Gets a mutable reference to the underlying writer.
+    /// It is inadvisable to directly write to the underlying writer.
+    pub fn get_mut(&mut self) -> &mut W { self.inner.writer.get_mut() }
+
+    /// Writes the buffered text and returns the underlying writer, without finishing the
+    /// encoder, like `BufWriter::into_inner`. Unlike dropping the `TextWriter`, reports the
+    /// errors of writing.
+    pub fn into_inner(mut self) -> Result<W> {
+        self.check_pending()?;
+        self.release_cr()?;
+        Ok(self.inner.into_inner().into_inner()?)
+    }
+
+    /// Finishes the encoder, writes the buffered text, flushes the underlying writer and
+    /// returns it.
+    pub fn finish(mut self) -> Result<W> {
+        self.check_pending()?;
+        self.release_cr()?;
+        Ok(self.inner.finish()?.into_inner()?)
+    }
+
+    /// For internal use. Fails if the bytes written via `io::Write` end in the middle of a
+    /// UTF-8 sequence.
+    fn check_pending(&self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            Ok(())
+        }
+        else {
+            Err(io::Error::new(ErrorKind::InvalidData, "incomplete UTF-8 sequence"))
+        }
+    }
+
+    /// For internal use. Encodes `s` and writes it, applying the buffer mode.
+    fn write_raw(&mut self, s: &str) -> Result<()> {
+        let inner = &mut self.inner;
+        inner.outbuf.clear();
+        let result = inner.encode_str(s);
+        inner.writer.write_all(&inner.outbuf)?;
+        self.written += inner.outbuf.len() as u64;
+        match self.buffer_mode {
+            BufferMode::Block => {},
+            BufferMode::Line if s.contains(['\n', '\u{85}']) => inner.writer.flush()?,
+            BufferMode::Line => {},
+            BufferMode::Unbuffered => inner.writer.flush_buf()?,
+        }
+        result
+    }
+
+    /// For internal use. Writes the `\r` held by `write_str`, which is not followed by `\n`.
+    fn release_cr(&mut self) -> Result<()> {
+        if mem::take(&mut self.held_cr) {
+            self.write_raw("\r")?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for TextWriter<W> {
+    /// Writes UTF-8 bytes, like `write_str`.
+    ///
+    /// # Examples
+    /// ```
+    /// use std::io::Write;
+    /// use encoding::all::WINDOWS_31J;
+    /// use encoding::EncoderTrap;
+    /// use textstream::{LineTerminator, TextWriter};
+    /// # fn foo() -> textstream::Result<()> {
+    /// let mut writer = TextWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+    /// writer.set_line_terminator(LineTerminator::CrLf);
+    /// writer.set_translate_newlines(true);
+    /// writeln!(writer, "{}={}", "あ", 1)?;
+    /// assert_eq!(writer.finish()?, b"\x82\xa0=1\r\n");
+    /// # Ok(())
+    /// # }
+    /// # fn main() { foo().unwrap(); }
+    /// ```
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut pending = mem::take(&mut self.pending);
+        let result = write_utf8_prefix(&mut pending, buf, |s| Ok(self.write_str(s)?));
+        self.pending = pending;
+        result
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.writer.flush()
+    }
+}
+
+/// For internal use. Creates an encoder for `encoding` which can be sent to another thread, like
+/// `new_decoder`. Encodings of the `encoding` crate in the enabled families are encoded by
+/// their own encoders, and other encodings by `ReplayEncoder`.
+fn new_encoder(encoding: EncodingRef) -> Box<dyn RawEncoder + Send> {
+    match builtin_encoding(encoding.name()) {
+        Some(builtin) => Box::new(BuiltinEncoder(builtin.raw_encoder())),
+        None => Box::new(ReplayEncoder { encoding, last: None }),
+    }
+}
+
+/// Encoder of an encoding of the `encoding` crate.
+struct BuiltinEncoder(Box<dyn RawEncoder>);
+
+// SAFETY: the encoders of the `encoding` crate are plain data, without references to shared or
+// thread-local state. `new_encoder` only wraps encoders created by its own statics.
+unsafe impl Send for BuiltinEncoder {}
+
+impl RawEncoder for BuiltinEncoder {
+    fn from_self(&self) -> Box<dyn RawEncoder> {
+        Box::new(BuiltinEncoder(self.0.from_self()))
+    }
+
+    fn is_ascii_compatible(&self) -> bool {
+        self.0.is_ascii_compatible()
+    }
+
+    fn raw_feed(&mut self, input: &str, output: &mut dyn ByteWriter) -> (usize, Option<CodecError>) {
+        self.0.raw_feed(input, output)
+    }
+
+    fn raw_finish(&mut self, output: &mut dyn ByteWriter) -> Option<CodecError> {
+        self.0.raw_finish(output)
+    }
+}
+
+/// Encoder of an encoding which is not of the `encoding` crate, or whose family is disabled.
+/// The encoder of the encoding is created for every call, so that only `Send` data is kept
+/// between calls. The last character encoded is fed again to the new encoder, discarding its
+/// output, to restore the shift state of stateful encodings, such as ISO-2022-JP, whose state
+/// only depends on the last character.
+struct ReplayEncoder {
+    encoding: EncodingRef,
+    last: Option<char>,
+}
+
+impl ReplayEncoder {
+    /// Creates an encoder of the encoding in the state after the last character encoded.
+    fn resume(&self) -> Box<dyn RawEncoder> {
+        let mut encoder = self.encoding.raw_encoder();
+        if let Some(c) = self.last {
+            encoder.raw_feed(c.encode_utf8(&mut [0; 4]), &mut Vec::new());
+        }
+        encoder
+    }
+}
+
+impl RawEncoder for ReplayEncoder {
+    fn from_self(&self) -> Box<dyn RawEncoder> {
+        Box::new(ReplayEncoder { encoding: self.encoding, last: None })
+    }
+
+    fn is_ascii_compatible(&self) -> bool {
+        self.encoding.raw_encoder().is_ascii_compatible()
+    }
+
+    fn raw_feed(&mut self, input: &str, output: &mut dyn ByteWriter) -> (usize, Option<CodecError>) {
+        let (processed, err) = self.resume().raw_feed(input, output);
+        if let Some(c) = input[..processed].chars().next_back() {
+            self.last = Some(c);
+        }
+        (processed, err)
+    }
+
+    fn raw_finish(&mut self, output: &mut dyn ByteWriter) -> Option<CodecError> {
+        let err = self.resume().raw_finish(output);
+        self.last = None;
+        err
+    }
+}
+
+/// For internal use. Appends `buf` to `pending` and passes the complete UTF-8 sequences at its
+/// start to `write`. An incomplete sequence at the end stays in `pending`; an invalid one is
+/// reported on the next call. Returns the number of bytes of `buf` taken.
+fn write_utf8_prefix<F>(pending: &mut Vec<u8>, buf: &[u8], write: F) -> io::Result<usize>
+    where F: FnOnce(&str) -> io::Result<()>
+{
+    let npending = pending.len();
+    pending.extend_from_slice(buf);
+    let (valid, invalid) = match str::from_utf8(pending) {
+        Ok(_) => (pending.len(), false),
+        Err(e) => (e.valid_up_to(), e.error_len().is_some()),
+    };
+    if valid == 0 && invalid {
+        pending.truncate(npending);
+        return Err(io::Error::new(ErrorKind::InvalidData, "invalid UTF-8 sequence"));
+    }
+    if let Err(e) = write(str::from_utf8(&pending[..valid]).unwrap()) {
+        pending.truncate(npending);
+        return Err(e);
+    }
+    if invalid {
+        // The invalid sequence is reported on the next call.
+        pending.clear();
+        Ok(valid - npending)
+    }
+    else {
+        pending.drain(..valid);
+        Ok(buf.len())
+    }
+}
+
+/// Returns whether the encoding named `name` is a Unicode encoding which has a BOM.
+fn is_unicode(name: &str) -> bool {
+    name == "utf-8" || name == "utf-16le" || name == "utf-16be"
+}
+
+/// Returns whether `s` starts with `key`, ignoring ASCII case.
+fn starts_with_nocase(s: &[u8], key: &[u8]) -> bool {
+    s.len() >= key.len() && s[..key.len()].eq_ignore_ascii_case(key)
+}
+
+/// Returns whether `pos` is inside a `<meta>` tag.
+fn in_meta_tag(s: &[u8], pos: usize) -> bool {
+    match s[..pos].iter().rposition(|&b| b == b'<') {
+        Some(lt) => starts_with_nocase(&s[lt..], b"<meta") && !s[lt..pos].contains(&b'>'),
+        None => false
+    }
+}
+
+/// Replaces the encoding in the declarations found in `head` with `name`.
+fn rewrite_declaration(head: &[u8], name: &str) -> Vec<u8> {
+    let window = &head[..head.len().min(PRESCAN_SIZE)];
+    let xml_end = if window.starts_with(b"<?xml") {
+        window.windows(2).position(|w| w == b"?>").unwrap_or(0)
+    }
+    else {
+        0
+    };
+    let mut out = Vec::with_capacity(head.len());
+    let mut pos = 0;
+    while pos < window.len() {
+        let value_start = if pos < xml_end && starts_with_nocase(&window[pos..], b"encoding=") {
+            pos + 9
+        }
+        else if starts_with_nocase(&window[pos..], b"charset=") && in_meta_tag(window, pos) {
+            pos + 8
+        }
+        else {
+            out.push(head[pos]);
+            pos += 1;
+            continue;
+        };
+        out.extend_from_slice(&head[pos..value_start]);
+        pos = value_start;
+        let quote = match head.get(pos) {
+            Some(&q) if q == b'"' || q == b'\'' => { out.push(q); pos += 1; Some(q) }
+            _ => None
+        };
+        while pos < head.len() {
+            let b = head[pos];
+            let end = match quote {
+                Some(q) => b == q,
+                None => b.is_ascii_whitespace() || b"\"';/>".contains(&b),
+            };
+            if end {
+                break;
+            }
+            pos += 1;
+        }
+        out.extend_from_slice(name.as_bytes());
+    }
+    out.extend_from_slice(&head[pos..]);
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding::all::{ISO_2022_JP, UTF_16LE, UTF_8, WINDOWS_31J};
+
+    #[test]
+    fn write_shortstring() {
+        let mut writer = EncodeWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+        writer.write_all("Aあいうえお".as_bytes()).unwrap();
+        assert_eq!(writer.finish().unwrap(), [0x41, 0x82, 0xa0, 0x82, 0xa2, 0x82, 0xa4, 0x82, 0xa6, 0x82, 0xa8]);
+    }
+    #[test]
+    fn write_split_sequence() {
+        let mut writer = EncodeWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+        let bytes = "あい".as_bytes();
+        assert_eq!(writer.write(&bytes[..2]).unwrap(), 2);
+        assert_eq!(writer.write(&bytes[2..4]).unwrap(), 2);
+        assert_eq!(writer.write(&bytes[4..]).unwrap(), 2);
+        assert_eq!(writer.finish().unwrap(), [0x82, 0xa0, 0x82, 0xa2]);
+    }
+    #[test]
+    fn write_unmappable() {
+        let mut writer = EncodeWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+        assert!(writer.write_all("A\u{1F600}".as_bytes()).is_err());
+        let mut writer = EncodeWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Replace);
+        writer.write_all("A\u{1F600}".as_bytes()).unwrap();
+        assert_eq!(writer.finish().unwrap(), b"A?");
+    }
+    #[test]
+    fn write_invalid_utf8() {
+        let mut writer = EncodeWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+        assert_eq!(writer.write(b"AB\xffC").unwrap(), 2);
+        assert!(writer.write(b"\xffC").is_err());
+        let mut writer = EncodeWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+        writer.write_all(b"\xe3\x81").unwrap();
+        assert!(writer.finish().is_err());
+    }
+    #[test]
+    fn rewrite_declaration_test() {
+        assert_eq!(rewrite_declaration(b"<meta charset=utf-8>", "shift_jis"), b"<meta charset=shift_jis>");
+        assert_eq!(rewrite_declaration(b"<META http-equiv=\"Content-Type\" content=\"text/html; CHARSET=UTF-8\">", "euc-jp"),
+                   &b"<META http-equiv=\"Content-Type\" content=\"text/html; CHARSET=euc-jp\">"[..]);
+        assert_eq!(rewrite_declaration(b"<?xml version=\"1.0\" encoding='UTF-8'?><a encoding=\"x\"/>", "shift_jis"),
+                   &b"<?xml version=\"1.0\" encoding='shift_jis'?><a encoding=\"x\"/>"[..]);
+        assert_eq!(rewrite_declaration(b"<p>charset=utf-8</p>", "shift_jis"), b"<p>charset=utf-8</p>");
+    }
+    #[test]
+    fn write_rewrite_declaration() {
+        let mut writer = EncodeWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+        writer.set_rewrite_declaration(true);
+        writer.write_all(b"<html><head><meta ").unwrap();
+        writer.write_all("charset=\"utf-8\"></head>あ".as_bytes()).unwrap();
+        assert_eq!(writer.finish().unwrap(), &b"<html><head><meta charset=\"shift_jis\"></head>\x82\xa0"[..]);
+        let mut writer = EncodeWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+        writer.set_rewrite_declaration(true);
+        let mut input = vec![b' '; PRESCAN_SIZE];
+        input.extend_from_slice(b"<meta charset=utf-8>");
+        writer.write_all(&input).unwrap();
+        assert_eq!(writer.finish().unwrap(), input);
+    }
+    #[test]
+    fn write_single_bom() {
+        let mut writer = EncodeWriter::new(Vec::new(), UTF_16LE, EncoderTrap::Strict);
+        writer.set_single_bom(true);
+        writer.write_all("\u{FEFF}A".as_bytes()).unwrap();
+        writer.write_all("\u{FEFF}B\u{FEFF}".as_bytes()).unwrap();
+        assert_eq!(writer.finish().unwrap(), b"\xff\xfeA\x00B\x00");
+        let mut writer = EncodeWriter::new(Vec::new(), UTF_8, EncoderTrap::Strict);
+        writer.set_single_bom(true);
+        assert_eq!(writer.finish().unwrap(), b"\xef\xbb\xbf");
+        let mut writer = EncodeWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+        writer.set_single_bom(true);
+        writer.write_all("\u{FEFF}A".as_bytes()).unwrap();
+        assert_eq!(writer.finish().unwrap(), b"A");
+    }
+    #[test]
+    fn write_stateful() {
+        let mut writer = EncodeWriter::new(Vec::new(), ISO_2022_JP, EncoderTrap::Strict);
+        writer.write_all("あ".as_bytes()).unwrap();
+        writer.write_all("い".as_bytes()).unwrap();
+        writer.write_all(b"A").unwrap();
+        assert_eq!(writer.finish().unwrap(), b"\x1b$B$\"$$\x1b(BA");
+    }
+    #[test]
+    fn text_writer() {
+        let mut writer = TextWriter::new(Vec::new(), ISO_2022_JP, EncoderTrap::Strict);
+        writer.write_line("あ").unwrap();
+        writer.write_str("い").unwrap();
+        assert!(writer.write_str("A\u{1F600}").is_err());
+        assert_eq!(writer.finish().unwrap(), b"\x1b$B$\"\x1b(B\n\x1b$B$$\x1b(BA");
+    }
+    #[test]
+    fn line_terminator() {
+        let cases = [(LineTerminator::Lf, &b"a\n"[..]), (LineTerminator::CrLf, b"a\r\n"), (LineTerminator::Nel, b"a\xc2\x85")];
+        for (terminator, bytes) in cases {
+            let mut writer = TextWriter::new(Vec::new(), UTF_8, EncoderTrap::Strict);
+            writer.set_line_terminator(terminator);
+            assert_eq!(writer.line_terminator(), terminator);
+            writer.write_line("a").unwrap();
+            assert_eq!(writer.finish().unwrap(), bytes);
+        }
+        let mut writer = TextWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+        writer.set_line_terminator(LineTerminator::Nel);
+        assert!(writer.write_line("a").is_err());
+    }
+    #[test]
+    fn text_writer_trap() {
+        let mut writer = TextWriter::new(Vec::new(), ISO_2022_JP, HEX_NCR_ESCAPE);
+        writer.write_str("あ\u{1F600}\u{e9}").unwrap();
+        assert_eq!(writer.finish().unwrap(), b"\x1b$B$\"\x1b(B&#x1F600;&#xE9;");
+        let mut writer = TextWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Replace);
+        writer.set_trap_handler(|unmappable, s| {
+            if unmappable == "\u{e9}" {
+                s.push('e');
+            }
+            else {
+                s.push_str(unmappable);
+            }
+            true
+        });
+        writer.write_str("\u{e9}t\u{e9}").unwrap();
+        assert!(writer.write_str("\u{1F600}").is_err());
+        writer.clear_trap_handler();
+        writer.write_str("\u{1F600}").unwrap();
+        assert_eq!(writer.finish().unwrap(), b"ete?");
+    }
+    /// A writer logging the calls.
+    #[derive(Default)]
+    struct Log {
+        writes: Vec<Vec<u8>>,
+        flushes: usize,
+    }
+    impl Write for Log {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.writes.push(buf.to_vec());
+            Ok(buf.len())
+        }
+        fn flush(&mut self) -> io::Result<()> {
+            self.flushes += 1;
+            Ok(())
+        }
+    }
+    #[test]
+    fn buffer_mode() {
+        let mut writer = TextWriter::new(Log::default(), UTF_8, EncoderTrap::Strict);
+        assert_eq!(writer.buffer_mode(), BufferMode::Block);
+        writer.write_line("a").unwrap();
+        writer.write_line("b").unwrap();
+        assert!(writer.get_ref().writes.is_empty());
+        writer.flush().unwrap();
+        assert_eq!(writer.get_ref().writes, [b"a\nb\n"]);
+        let long = "c".repeat(BUFFER_SIZE);
+        writer.write_str(&long).unwrap();
+        assert_eq!(writer.get_ref().writes[1], long.as_bytes());
+        let mut writer = TextWriter::new(Log::default(), UTF_8, EncoderTrap::Strict);
+        writer.set_buffer_mode(BufferMode::Line);
+        writer.write_str("a").unwrap();
+        writer.write_line("b").unwrap();
+        writer.write_str("c").unwrap();
+        assert_eq!(writer.get_ref().writes, [b"ab\n"]);
+        assert_eq!(writer.get_ref().flushes, 1);
+        let log = writer.into_inner().unwrap();
+        assert_eq!(log.writes, [&b"ab\n"[..], b"c"]);
+        let mut writer = TextWriter::new(Log::default(), UTF_8, EncoderTrap::Strict);
+        writer.set_buffer_mode(BufferMode::Unbuffered);
+        writer.write_str("a").unwrap();
+        writer.write_str("b").unwrap();
+        assert_eq!(writer.get_ref().writes, [b"a", b"b"]);
+        assert_eq!(writer.get_ref().flushes, 0);
+    }
+    #[test]
+    fn text_writer_drop() {
+        let mut out = Vec::new();
+        let mut writer = TextWriter::new(&mut out, ISO_2022_JP, EncoderTrap::Strict);
+        writer.write_str("あ").unwrap();
+        drop(writer);
+        assert_eq!(out, b"\x1b$B$\"");
+        let mut writer = TextWriter::new(Vec::new(), ISO_2022_JP, EncoderTrap::Strict);
+        writer.write_str("あ").unwrap();
+        assert_eq!(writer.into_inner().unwrap(), b"\x1b$B$\"");
+    }
+    #[test]
+    fn text_writer_io_write() {
+        let mut writer = TextWriter::new(Vec::new(), WINDOWS_31J, EncoderTrap::Strict);
+        writer.set_translate_newlines(true);
+        writer.set_line_terminator(LineTerminator::CrLf);
+        let bytes = "あい\n".as_bytes();
+        assert_eq!(writer.write(&bytes[..2]).unwrap(), 2);
+        assert_eq!(writer.write(&bytes[2..4]).unwrap(), 2);
+        assert_eq!(writer.write(&bytes[4..]).unwrap(), 3);
+        assert_eq!(writer.write(b"AB\xffC").unwrap(), 2);
+        assert!(writer.write(b"\xffC").is_err());
+        assert!(writer.write("\u{1F600}".as_bytes()).is_err());
+        Write::flush(&mut writer).unwrap();
+        assert_eq!(writer.get_ref(), &[0x82, 0xa0, 0x82, 0xa2, b'\r', b'\n', b'A', b'B']);
+        writer.write_all(b"\xe3\x81").unwrap();
+        assert!(writer.finish().is_err());
+    }
+    #[test]
+    fn text_writer_bom() {
+        let write = |encoding, write_bom, text: &str| {
+            let mut writer = TextWriter::new(Vec::new(), encoding, EncoderTrap::Strict);
+            writer.set_write_bom(write_bom);
+            assert_eq!(writer.write_bom(), write_bom);
+            writer.write_str(text).unwrap();
+            writer.write_str("b").unwrap();
+            writer.finish().unwrap()
+        };
+        assert_eq!(write(UTF_8, true, "a"), b"\xef\xbb\xbfab");
+        assert_eq!(write(UTF_16LE, true, "a"), b"\xff\xfea\0b\0");
+        assert_eq!(write(UTF_8, false, "a"), b"ab");
+        assert_eq!(write(UTF_8, false, "\u{FEFF}a"), b"\xef\xbb\xbfab");
+        assert_eq!(write(WINDOWS_31J, true, "a"), b"ab");
+        let mut writer = TextWriter::new(Vec::new(), UTF_8, EncoderTrap::Strict);
+        writer.set_write_bom(true);
+        assert_eq!(writer.finish().unwrap(), b"\xef\xbb\xbf");
+    }
+    #[test]
+    fn translate_newlines() {
+        let write = |terminator, collapse, texts: &[&str]| {
+            let mut writer = TextWriter::new(Vec::new(), UTF_8, EncoderTrap::Strict);
+            writer.set_line_terminator(terminator);
+            writer.set_translate_newlines(true);
+            writer.set_collapse_crlf(collapse);
+            for text in texts {
+                writer.write_str(text).unwrap();
+            }
+            writer.write_line("z").unwrap();
+            String::from_utf8(writer.finish().unwrap()).unwrap()
+        };
+        assert_eq!(write(LineTerminator::CrLf, false, &["a\nb\r\n"]), "a\r\nb\r\r\nz\r\n");
+        assert_eq!(write(LineTerminator::CrLf, true, &["a\nb\r\n"]), "a\r\nb\r\nz\r\n");
+        assert_eq!(write(LineTerminator::Lf, true, &["a\r", "\nb\r", "", "c\r"]), "a\nb\rc\rz\n");
+        assert_eq!(write(LineTerminator::Nel, true, &["\r\n\n\r"]), "\u{85}\u{85}\rz\u{85}");
+        let mut writer = TextWriter::new(Vec::new(), UTF_8, EncoderTrap::Strict);
+        writer.set_collapse_crlf(true);
+        writer.write_str("a\r").unwrap();
+        assert_eq!(writer.finish().unwrap(), b"a\r");
+        let mut writer = TextWriter::new(Vec::new(), UTF_8, EncoderTrap::Strict);
+        writer.set_translate_newlines(true);
+        writer.set_collapse_crlf(true);
+        writer.write_str("a\r").unwrap();
+        assert_eq!(writer.finish().unwrap(), b"a\r");
+    }
+    #[test]
+    fn text_writer_is_send() {
+        fn assert_send<T: Send>() {}
+        assert_send::<EncodeWriter<std::fs::File>>();
+        assert_send::<TextWriter<std::fs::File>>();
+        assert_send::<crate::TextStream<std::net::TcpStream>>();
+    }
+    #[test]
+    fn replay_encoder() {
+        use encoding::Encoding;
+        let mut encoder = ReplayEncoder { encoding: ISO_2022_JP, last: None };
+        let mut output = Vec::new();
+        for s in ["a", "あ", "い", "b", "\u{ff71}", "\u{ff72}"] {
+            assert!(encoder.raw_feed(s, &mut output).1.is_none());
+        }
+        assert!(encoder.raw_finish(&mut output).is_none());
+        assert_eq!(output, ISO_2022_JP.encode("aあいb\u{ff71}\u{ff72}", EncoderTrap::Strict).unwrap());
+    }
+}
@@ -0,0 +1,56 @@
+//! Checks which tables of the `encoding` crate are linked into a binary.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// A program which only decodes UTF-8.
+const UTF8_ONLY: &str = r#"
+use std::io;
+use textstream::prelude::*;
+
+fn main() {
+    let reader = TextReader::new(io::stdin(), UTF_8, DecoderTrap::Strict);
+    println!("{}", reader.lines().count());
+}
+"#;
+
+#[test]
+fn default_features_without_cjk_tables() {
+    let manifest_dir = env!("CARGO_MANIFEST_DIR");
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("utf8-only");
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("Cargo.toml"), format!(r#"[package]
+name = "utf8-only"
+version = "0.0.0"
+edition = "2021"
+
+[dependencies]
+textstream = {{ path = {:?} }}
+
+[workspace]
+"#, manifest_dir)).unwrap();
+    fs::write(dir.join("src/main.rs"), UTF8_ONLY).unwrap();
+    // Reuse the versions resolved for this crate, so that the build works offline.
+    let lock = Path::new(manifest_dir).join("Cargo.lock");
+    if lock.exists() && !dir.join("Cargo.lock").exists() {
+        fs::copy(lock, dir.join("Cargo.lock")).unwrap();
+    }
+    let cargo = env::var("CARGO").unwrap_or_else(|_| "cargo".to_string());
+    let status = Command::new(cargo)
+        .args(["build", "--release", "--quiet"])
+        .current_dir(&dir)
+        .env("CARGO_TARGET_DIR", dir.join("target"))
+        .status()
+        .unwrap();
+    assert!(status.success());
+    let binary = fs::read(dir.join("target/release").join(format!("utf8-only{}", env::consts::EXE_SUFFIX))).unwrap();
+    for family in ["japanese", "korean", "simpchinese", "tradchinese", "singlebyte"] {
+        // The prefix of the mangled symbols of the crate, such as
+        // `_ZN23encoding_index_japanese7jis020813FORWARD_TABLE`.
+        let name = format!("encoding_index_{}", family);
+        let symbol = format!("{}{}", name.len(), name);
+        assert!(!binary.windows(symbol.len()).any(|w| w == symbol.as_bytes()), "{} is linked in", name);
+    }
+}